@@ -0,0 +1,8 @@
+//! # Benchmark Reports
+//!
+//! Host side of the benchmarks: parses the logs of the Rust benchmark
+//! binary and the C SDK benchmarks, and compares their runs.
+
+pub mod parse;
+pub mod report;
+pub mod stats;
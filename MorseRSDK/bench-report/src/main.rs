@@ -0,0 +1,51 @@
+//! Usage: `bench-report [--csv] <log>...`
+//!
+//! Prints a Markdown comparison of every benchmark run in the logs, or
+//! CSV with `--csv`. Pass the Rust and C SDK logs together to compare them.
+
+use std::process::ExitCode;
+
+use bench_report::{parse, report};
+
+fn main() -> ExitCode {
+    let mut csv = false;
+    let mut paths = Vec::new();
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--csv" => csv = true,
+            "-h" | "--help" => {
+                eprintln!("usage: bench-report [--csv] <log>...");
+                return ExitCode::SUCCESS;
+            }
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() {
+        eprintln!("usage: bench-report [--csv] <log>...");
+        return ExitCode::FAILURE;
+    }
+
+    let mut records = Vec::new();
+    for path in &paths {
+        // Captures may hold stray bytes, e.g. a mis-encoded µs
+        match std::fs::read(path) {
+            Ok(bytes) => records.extend(parse::parse_log(&String::from_utf8_lossy(&bytes))),
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    if records.is_empty() {
+        eprintln!("no benchmark results in {}", paths.join(", "));
+        return ExitCode::FAILURE;
+    }
+
+    let groups = report::group(&records);
+    if csv {
+        print!("{}", report::csv(&groups));
+    } else {
+        print!("{}", report::markdown(&groups));
+    }
+    ExitCode::SUCCESS
+}
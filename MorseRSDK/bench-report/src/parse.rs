@@ -0,0 +1,253 @@
+//! # Log Parsing
+//!
+//! Reads both kinds of benchmark log into [`Record`]s, one per measured
+//! value. Lines that are neither are skipped, so a whole terminal capture
+//! can be passed in, menus and all.
+//!
+//! The Rust benchmarks print a JSON object per line (see
+//! `morse_rsdk::bench`). Each metric of a `sample` record becomes a record
+//! named by its name and unit, e.g. `total_time_us`:
+//!
+//! ```text
+//! {"schema":1,...,"kind":"sample","task":"gpio","method":"toggle","params":{"toggles":1000},
+//!  "iteration":0,"metrics":[{"name":"total_time","value":128,"unit":"us"},...]}
+//! ```
+//!
+//! The C SDK benchmarks print `KEY[n] | Name=value unit | ...` lines,
+//! whose names are mapped to the same metric names:
+//!
+//! ```text
+//! RUN[0] | TotalTime=96 µs | PerToggle=0.10 µs
+//! ```
+
+use serde_json::Value;
+
+/// Which firmware a record came from
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Source {
+    Rust,
+    CSdk,
+}
+
+/// The method name given to every C SDK record
+pub const C_SDK_METHOD: &str = "c_sdk";
+
+/// One measured value
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record {
+    pub source: Source,
+    /// e.g. `gpio`, `uart` or `uart_loopback`
+    pub task: String,
+    /// e.g. `toggle_raw`; [`C_SDK_METHOD`] for the C SDK
+    pub method: String,
+    /// Message size, for the UART benchmarks
+    pub bytes: Option<u32>,
+    /// Baud rate, for the UART loopback benchmarks
+    pub baud: Option<u32>,
+    /// Metric name and unit, e.g. `total_time_us`
+    pub metric: String,
+    pub value: f64,
+}
+
+/// The record layout this parser reads
+const SCHEMA_VERSION: u64 = 1;
+
+/// C SDK line keys and value names, and the task and metric they map to
+const C_FIELDS: [(&str, &str, &str, &str); 7] = [
+    ("RUN", "TotalTime", "gpio", "total_time_us"),
+    ("RUN", "PerToggle", "gpio", "avg_toggle_us"),
+    ("TEST", "SetupTime", "pwm", "setup_time_us"),
+    ("CYCLE", "Avg_Time", "adc", "avg_time_us"),
+    ("EVENT", "ResponseTime", "interrupt", "latency_us"),
+    ("TEST", "Time", "uart", "time_us"),
+    ("TEST", "Throughput", "uart", "throughput_bytes_per_sec"),
+];
+
+/// Every record in `log`, in order
+pub fn parse_log(log: &str) -> Vec<Record> {
+    let mut parser = Parser::default();
+    for line in log.lines() {
+        parser.line(line.trim());
+    }
+    parser.records
+}
+
+#[derive(Default)]
+struct Parser {
+    records: Vec<Record>,
+    /// Message size from the last C `SIZE_TEST` line
+    c_bytes: Option<u32>,
+}
+
+impl Parser {
+    fn line(&mut self, line: &str) {
+        if line.starts_with('{') {
+            self.rust_line(line);
+        } else if line.contains(" | ") {
+            self.c_line(line);
+        }
+    }
+
+    fn rust_line(&mut self, line: &str) {
+        let Ok(record) = serde_json::from_str::<Value>(line) else {
+            return;
+        };
+        if record["schema"].as_u64() != Some(SCHEMA_VERSION) || record["kind"] != "sample" {
+            return;
+        }
+        let (Some(task), Some(method), Some(metrics)) =
+            (record["task"].as_str(), record["method"].as_str(), record["metrics"].as_array())
+        else {
+            return;
+        };
+        let bytes = record["params"]["bytes"].as_u64().map(|b| b as u32);
+        let baud = record["params"]["baud"].as_u64().map(|b| b as u32);
+
+        for metric in metrics {
+            let (Some(name), Some(unit), Some(value)) =
+                (metric["name"].as_str(), metric["unit"].as_str(), metric["value"].as_f64())
+            else {
+                continue;
+            };
+            self.records.push(Record {
+                source: Source::Rust,
+                task: task.to_string(),
+                method: method.to_string(),
+                bytes,
+                baud,
+                metric: format!("{}_{}", name, unit),
+                value,
+            });
+        }
+    }
+
+    fn c_line(&mut self, line: &str) {
+        let mut parts = line.split(" | ");
+        let key = parts.next().unwrap_or("");
+        // `RUN[3]` -> `RUN`
+        let key = key.split('[').next().unwrap_or(key).trim();
+        let values: Vec<(&str, f64)> = parts
+            .filter_map(|part| {
+                let (name, value) = part.split_once('=')?;
+                Some((name.trim(), leading_number(value)?))
+            })
+            .collect();
+
+        if key == "SIZE_TEST" {
+            self.c_bytes = values
+                .iter()
+                .find(|(name, _)| *name == "MessageLength")
+                .map(|&(_, value)| value as u32);
+            return;
+        }
+        for (name, value) in values {
+            let Some(&(_, _, task, metric)) = C_FIELDS.iter().find(|f| f.0 == key && f.1 == name) else {
+                continue;
+            };
+            self.records.push(Record {
+                source: Source::CSdk,
+                task: task.to_string(),
+                method: C_SDK_METHOD.to_string(),
+                bytes: if task == "uart" { self.c_bytes } else { None },
+                baud: None,
+                metric: metric.to_string(),
+                value,
+            });
+        }
+    }
+}
+
+/// `96 µs` -> 96, whatever the unit's encoding came out as
+fn leading_number(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let end = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-'))
+        .unwrap_or(value.len());
+    value[..end].parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(records: &[Record], method: &str, metric: &str) -> Vec<f64> {
+        records
+            .iter()
+            .filter(|r| r.method == method && r.metric == metric)
+            .map(|r| r.value)
+            .collect()
+    }
+
+    #[test]
+    fn rust_metrics_are_named_with_their_unit() {
+        let records = parse_log(include_str!("../samples/rust_gpio.log"));
+        assert_eq!(values(&records, "toggle", "total_time_cycles"), [20145.0, 16012.0, 16012.0, 16013.0]);
+        assert_eq!(values(&records, "toggle_raw", "avg_toggle_us"), [0.064; 4]);
+        // Four metrics a sample; summary records are not samples
+        assert_eq!(records.len(), 2 * 4 * 4);
+        assert!(records.iter().all(|r| r.source == Source::Rust && r.task == "gpio" && r.bytes.is_none()));
+    }
+
+    #[test]
+    fn uart_message_size_comes_from_params() {
+        let records = parse_log(include_str!("../samples/rust_uart.log"));
+        let sizes: Vec<Option<u32>> = records.iter().filter(|r| r.metric == "time_us").map(|r| r.bytes).collect();
+        assert_eq!(sizes, [Some(10), Some(10), Some(250), Some(250)]);
+        assert_eq!(values(&records, "tx_raw", "time_cycles"), [108500.0, 108502.0, 2712625.0, 2712750.0]);
+    }
+
+    #[test]
+    fn loopback_baud_rate_comes_from_params() {
+        let line = r#"{"schema":1,"kind":"sample","task":"uart_loopback","method":"loopback_raw","params":{"baud":921600,"bytes":256},"iteration":0,"metrics":[{"name":"time","value":2780,"unit":"us"}]}"#;
+        let records = parse_log(line);
+        assert_eq!((records[0].baud, records[0].bytes), (Some(921600), Some(256)));
+        assert!(parse_log(include_str!("../samples/rust_uart.log")).iter().all(|r| r.baud.is_none()));
+    }
+
+    #[test]
+    fn other_schema_versions_are_skipped() {
+        let line = r#"{"schema":2,"kind":"sample","task":"gpio","method":"toggle","params":{},"iteration":0,"metrics":[{"name":"total_time","value":1,"unit":"us"}]}"#;
+        assert!(parse_log(line).is_empty());
+        assert_eq!(parse_log(&line.replace(r#""schema":2"#, r#""schema":1"#)).len(), 1);
+    }
+
+    #[test]
+    fn c_lines_map_to_rust_metrics() {
+        let records = parse_log(include_str!("../samples/c_gpio.log"));
+        assert_eq!(values(&records, C_SDK_METHOD, "total_time_us"), [97.0, 96.0, 96.0, 96.0]);
+        assert_eq!(values(&records, C_SDK_METHOD, "avg_toggle_us"), [0.1; 4]);
+        assert!(records.iter().all(|r| r.source == Source::CSdk && r.task == "gpio"));
+    }
+
+    #[test]
+    fn c_uart_sizes_come_from_size_test_lines() {
+        let records = parse_log(include_str!("../samples/c_uart.log"));
+        let times: Vec<(Option<u32>, f64)> =
+            records.iter().filter(|r| r.metric == "time_us").map(|r| (r.bytes, r.value)).collect();
+        assert_eq!(times, [(Some(10), 790.0), (Some(10), 782.0), (Some(250), 21620.0), (Some(250), 21622.0)]);
+        assert_eq!(values(&records, C_SDK_METHOD, "throughput_bytes_per_sec")[0], 12658.23);
+    }
+
+    #[test]
+    fn c_tasks_are_told_apart_by_value_name() {
+        let records = parse_log(include_str!("../samples/c_mixed.log"));
+        let tasks: Vec<(&str, &str, f64)> =
+            records.iter().map(|r| (r.task.as_str(), r.metric.as_str(), r.value)).collect();
+        assert_eq!(
+            tasks,
+            [
+                ("pwm", "setup_time_us", 4.0),
+                ("pwm", "setup_time_us", 3.0),
+                ("adc", "avg_time_us", 2.0),
+                // Unit mangled by the terminal's encoding
+                ("interrupt", "latency_us", 7.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn other_text_is_ignored() {
+        let log = "Choose:\nNo benchmark 'x'\nRUN[0] | Done\n{\"kind\":\"sample\"\n";
+        assert!(parse_log(log).is_empty());
+    }
+}
@@ -0,0 +1,314 @@
+//! # Reports
+//!
+//! Groups records by task, message size, baud rate and metric, and puts the runs of
+//! each group side by side: Rust HAL, Rust raw, Rust DMA, Rust PIO, Rust SIO, other
+//! Rust records, then the C SDK, with each run's median relative to the C SDK's.
+
+use std::fmt::Write;
+
+use crate::parse::{Record, Source};
+use crate::stats::Stats;
+
+/// Column names of [`csv`]'s output
+pub const CSV_HEADER: &str = "task,bytes,baud,metric,run,method,n,min,median,mean,stddev,p99,max,vs_c_sdk";
+
+/// Which implementation a run measures
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Run {
+    RustHal,
+    RustRaw,
+    RustDma,
+    RustPio,
+    /// The CPU driving pins through SIO, as against a PIO state machine
+    RustSio,
+    /// Records that do not compare implementations, such as `stack`
+    Rust,
+    CSdk,
+}
+
+/// The implementation behind each method the firmware prints. Listed
+/// rather than guessed from the name, as names like `sio` or `jumper`
+/// say nothing about it.
+const RUST_METHODS: &[(&str, Run)] = &[
+    ("toggle", Run::RustHal),
+    ("toggle_raw", Run::RustRaw),
+    ("toggle_dma", Run::RustDma),
+    ("toggle_pio", Run::RustPio),
+    ("setup_hal", Run::RustHal),
+    ("setup_raw", Run::RustRaw),
+    ("hal_read", Run::RustHal),
+    ("raw_read", Run::RustRaw),
+    ("dma_read", Run::RustDma),
+    // The interrupt benchmark's methods name the stimulus; its handler
+    // uses the HAL either way
+    ("pad_override", Run::RustHal),
+    ("jumper", Run::RustHal),
+    ("tx_hal", Run::RustHal),
+    ("tx_raw", Run::RustRaw),
+    ("tx_dma", Run::RustDma),
+    ("loopback_hal", Run::RustHal),
+    ("loopback_raw", Run::RustRaw),
+    ("clean_hal", Run::RustHal),
+    ("clean_raw", Run::RustRaw),
+    ("parity_hal", Run::RustHal),
+    ("parity_raw", Run::RustRaw),
+    ("framing_hal", Run::RustHal),
+    ("framing_raw", Run::RustRaw),
+    ("break_hal", Run::RustHal),
+    ("break_raw", Run::RustRaw),
+    ("sio", Run::RustSio),
+    ("pio", Run::RustPio),
+];
+
+impl Run {
+    /// Looks Rust methods up in [`RUST_METHODS`]; any other is a plain
+    /// [`Run::Rust`]
+    pub fn of(source: Source, method: &str) -> Self {
+        match source {
+            Source::CSdk => Run::CSdk,
+            Source::Rust => RUST_METHODS
+                .iter()
+                .find(|(name, _)| *name == method)
+                .map_or(Run::Rust, |&(_, run)| run),
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Run::RustHal => "Rust HAL",
+            Run::RustRaw => "Rust raw",
+            Run::RustDma => "Rust DMA",
+            Run::RustPio => "Rust PIO",
+            Run::RustSio => "Rust SIO",
+            Run::Rust => "Rust",
+            Run::CSdk => "C SDK",
+        }
+    }
+}
+
+/// One metric of one task, e.g. `uart` 250 bytes `time_us`
+#[derive(Debug, PartialEq)]
+pub struct Group {
+    pub task: String,
+    pub bytes: Option<u32>,
+    pub baud: Option<u32>,
+    pub metric: String,
+    /// In [`Run`] order, then by method
+    pub runs: Vec<RunStats>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct RunStats {
+    pub run: Run,
+    pub method: String,
+    pub stats: Stats,
+}
+
+impl Group {
+    /// Median of the C SDK run, if the logs had one
+    fn c_sdk_median(&self) -> Option<f64> {
+        self.runs.iter().find(|r| r.run == Run::CSdk).map(|r| r.stats.median)
+    }
+
+    /// `run`'s median over the C SDK's, e.g. `1.33x`; `-` without one
+    fn vs_c_sdk(&self, run: &RunStats) -> String {
+        match self.c_sdk_median() {
+            Some(c) if c > 0.0 => format!("{:.2}x", run.stats.median / c),
+            _ => "-".to_string(),
+        }
+    }
+
+    fn title(&self) -> String {
+        let mut title = self.task.clone();
+        if let Some(baud) = self.baud {
+            write!(title, ", {} baud", baud).unwrap();
+        }
+        if let Some(bytes) = self.bytes {
+            write!(title, ", {} bytes", bytes).unwrap();
+        }
+        format!("{}: {}", title, self.metric)
+    }
+}
+
+/// Groups in the order their task, size, baud rate and metric first appear
+pub fn group(records: &[Record]) -> Vec<Group> {
+    type Key<'a> = (&'a str, Option<u32>, Option<u32>, &'a str);
+    let mut keys: Vec<Key> = Vec::new();
+    let mut values: Vec<Vec<(Run, &str, f64)>> = Vec::new();
+
+    for record in records {
+        let key = (record.task.as_str(), record.bytes, record.baud, record.metric.as_str());
+        let index = keys.iter().position(|k| *k == key).unwrap_or_else(|| {
+            keys.push(key);
+            values.push(Vec::new());
+            keys.len() - 1
+        });
+        values[index].push((Run::of(record.source, &record.method), &record.method, record.value));
+    }
+
+    keys.into_iter()
+        .zip(values)
+        .map(|((task, bytes, baud, metric), values)| {
+            let mut runs: Vec<(Run, &str)> = values.iter().map(|&(run, method, _)| (run, method)).collect();
+            runs.sort();
+            runs.dedup();
+            let runs = runs
+                .into_iter()
+                .filter_map(|(run, method)| {
+                    let run_values: Vec<f64> =
+                        values.iter().filter(|v| v.0 == run && v.1 == method).map(|v| v.2).collect();
+                    Some(RunStats { run, method: method.to_string(), stats: Stats::of(&run_values)? })
+                })
+                .collect();
+            Group { task: task.to_string(), bytes, baud, metric: metric.to_string(), runs }
+        })
+        .collect()
+}
+
+/// A Markdown document with a table for each group
+pub fn markdown(groups: &[Group]) -> String {
+    let mut out = String::from("# Benchmark Comparison\n");
+    for group in groups {
+        writeln!(out, "\n## {}\n", group.title()).unwrap();
+        out.push_str("| Run | Method | n | min | median | mean | stddev | p99 | max | vs C SDK |\n");
+        out.push_str("|-----|--------|--:|----:|-------:|-----:|-------:|----:|----:|---------:|\n");
+        for run in &group.runs {
+            let s = &run.stats;
+            writeln!(
+                out,
+                "| {} | {} | {} | {} | {} | {} | {} | {} | {} | {} |",
+                run.run.label(),
+                run.method,
+                s.count,
+                number(s.min),
+                number(s.median),
+                number(s.mean),
+                number(s.stddev),
+                number(s.p99),
+                number(s.max),
+                group.vs_c_sdk(run)
+            )
+            .unwrap();
+        }
+    }
+    out
+}
+
+/// One CSV row per run of each group, under [`CSV_HEADER`]
+pub fn csv(groups: &[Group]) -> String {
+    let mut out = format!("{}\n", CSV_HEADER);
+    for group in groups {
+        for run in &group.runs {
+            let s = &run.stats;
+            writeln!(
+                out,
+                "{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+                group.task,
+                group.bytes.map(|b| b.to_string()).unwrap_or_default(),
+                group.baud.map(|b| b.to_string()).unwrap_or_default(),
+                group.metric,
+                run.run.label(),
+                run.method,
+                s.count,
+                number(s.min),
+                number(s.median),
+                number(s.mean),
+                number(s.stddev),
+                number(s.p99),
+                number(s.max),
+                group.vs_c_sdk(run)
+            )
+            .unwrap();
+        }
+    }
+    out
+}
+
+/// Up to two decimals, without trailing zeros: `96`, `0.1`, `17045.5`
+fn number(value: f64) -> String {
+    let text = format!("{:.2}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    if text == "-0" { "0".to_string() } else { text.to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse_log;
+
+    fn gpio_groups() -> Vec<Group> {
+        let mut records = parse_log(include_str!("../samples/c_gpio.log"));
+        records.extend(parse_log(include_str!("../samples/rust_gpio.log")));
+        group(&records)
+    }
+
+    #[test]
+    fn groups_keep_log_order_and_runs_sort_rust_first() {
+        let groups = gpio_groups();
+        let titles: Vec<String> = groups.iter().map(Group::title).collect();
+        assert_eq!(
+            titles,
+            ["gpio: total_time_us", "gpio: avg_toggle_us", "gpio: total_time_cycles", "gpio: avg_toggle_cycles"]
+        );
+        let runs: Vec<Run> = groups[0].runs.iter().map(|r| r.run).collect();
+        assert_eq!(runs, [Run::RustHal, Run::RustRaw, Run::CSdk]);
+        // The C SDK does not count cycles
+        assert_eq!(groups[2].runs.len(), 2);
+    }
+
+    #[test]
+    fn runs_are_told_apart_by_method() {
+        assert_eq!(Run::of(Source::Rust, "tx_hal"), Run::RustHal);
+        assert_eq!(Run::of(Source::Rust, "toggle_raw"), Run::RustRaw);
+        assert_eq!(Run::of(Source::Rust, "dma_read"), Run::RustDma);
+        assert_eq!(Run::of(Source::Rust, "toggle_pio"), Run::RustPio);
+        assert_eq!(Run::of(Source::Rust, "sio"), Run::RustSio);
+        assert_eq!(Run::of(Source::Rust, "pio"), Run::RustPio);
+        assert_eq!(Run::of(Source::Rust, "jumper"), Run::RustHal);
+        // Not an implementation, even with `raw` in the name
+        assert_eq!(Run::of(Source::Rust, "gpio_raw"), Run::Rust);
+        assert_eq!(Run::of(Source::CSdk, "c_sdk"), Run::CSdk);
+    }
+
+    #[test]
+    fn markdown_compares_medians_with_the_c_sdk() {
+        let markdown = markdown(&gpio_groups());
+        assert!(markdown.starts_with("# Benchmark Comparison\n\n## gpio: total_time_us\n\n| Run |"));
+        assert!(markdown.contains("| Rust HAL | toggle | 4 | 128 | 128 | 136.25 | 14.29 | 161 | 161 | 1.33x |\n"));
+        assert!(markdown.contains("| Rust raw | toggle_raw | 4 | 64 | 64 | 64 | 0 | 64 | 64 | 0.67x |\n"));
+        assert!(markdown.contains("| C SDK | c_sdk | 4 | 96 | 96 | 96.25 | 0.43 | 97 | 97 | 1.00x |\n"));
+        // No C SDK run to compare cycles with
+        assert!(markdown.contains("| Rust raw | toggle_raw | 4 | 8003 | 8003 | 8003.25 | 0.43 | 8004 | 8004 | - |\n"));
+    }
+
+    #[test]
+    fn csv_has_a_row_per_run_with_the_message_size() {
+        let mut records = parse_log(include_str!("../samples/rust_uart.log"));
+        records.extend(parse_log(include_str!("../samples/c_uart.log")));
+        let csv = csv(&group(&records));
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], CSV_HEADER);
+        assert_eq!(lines[1], "uart,10,,time_us,Rust raw,tx_raw,2,868,868,868,0,868,868,1.10x");
+        assert_eq!(lines[2], "uart,10,,time_us,C SDK,c_sdk,2,782,786,786,4,790,790,1.00x");
+        assert!(lines.contains(&"uart,250,,time_cycles,Rust raw,tx_raw,2,2712625,2712687.5,2712687.5,62.5,2712750,2712750,-"));
+    }
+
+    #[test]
+    fn baud_rates_are_grouped_apart() {
+        let log = [115200, 921600, 115200]
+            .map(|baud| format!(r#"{{"schema":1,"kind":"sample","task":"uart_loopback","method":"loopback_hal","params":{{"baud":{},"bytes":256}},"iteration":0,"metrics":[{{"name":"time","value":1,"unit":"us"}}]}}"#, baud))
+            .join("\n");
+        let groups = group(&parse_log(&log));
+        let titles: Vec<String> = groups.iter().map(Group::title).collect();
+        assert_eq!(titles, ["uart_loopback, 115200 baud, 256 bytes: time_us", "uart_loopback, 921600 baud, 256 bytes: time_us"]);
+        assert_eq!(groups[0].runs[0].stats.count, 2);
+    }
+
+    #[test]
+    fn numbers_drop_trailing_zeros() {
+        assert_eq!(number(96.0), "96");
+        assert_eq!(number(0.1), "0.1");
+        assert_eq!(number(0.004), "0");
+        assert_eq!(number(1.333), "1.33");
+    }
+}
@@ -0,0 +1,82 @@
+//! # Statistics
+//!
+//! Exact statistics over every value of a run. The firmware's own summary
+//! lines come from a histogram; these are what the report compares.
+
+/// Summary of one run's values of one metric
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stats {
+    pub count: usize,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub median: f64,
+    pub p99: f64,
+    /// Population standard deviation
+    pub stddev: f64,
+}
+
+impl Stats {
+    /// `None` if there are no values
+    pub fn of(values: &[f64]) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        let mut sorted = values.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let count = sorted.len();
+        let mean = sorted.iter().sum::<f64>() / count as f64;
+        let variance = sorted.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / count as f64;
+        let median = if count % 2 == 1 {
+            sorted[count / 2]
+        } else {
+            (sorted[count / 2 - 1] + sorted[count / 2]) / 2.0
+        };
+
+        Some(Self {
+            count,
+            min: sorted[0],
+            max: sorted[count - 1],
+            mean,
+            median,
+            p99: percentile(&sorted, 99),
+            stddev: variance.sqrt(),
+        })
+    }
+}
+
+/// Nearest-rank percentile of sorted values
+fn percentile(sorted: &[f64], percent: usize) -> f64 {
+    let rank = (sorted.len() * percent).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarises_a_run() {
+        let stats = Stats::of(&[9.0, 2.0, 4.0, 4.0, 5.0, 5.0, 4.0, 7.0]).unwrap();
+        assert_eq!(stats.count, 8);
+        assert_eq!((stats.min, stats.max), (2.0, 9.0));
+        assert_eq!(stats.mean, 5.0);
+        assert_eq!(stats.median, 4.5);
+        assert_eq!(stats.p99, 9.0);
+        assert_eq!(stats.stddev, 2.0);
+    }
+
+    #[test]
+    fn p99_skips_the_worst_one_in_a_hundred() {
+        let mut values: Vec<f64> = (1..=200).map(f64::from).collect();
+        values.reverse();
+        let stats = Stats::of(&values).unwrap();
+        assert_eq!(stats.p99, 198.0);
+        assert_eq!(stats.median, 100.5);
+    }
+
+    #[test]
+    fn no_values_no_stats() {
+        assert_eq!(Stats::of(&[]), None);
+    }
+}
@@ -3,7 +3,9 @@ MEMORY {
     BOOT2 : ORIGIN = 0x10000000, LENGTH = 0x100
     /* Important: RP2040 flash starts at 0x10000100 after the bootloader */
     /* Begin flash at 0x10000100, after the bootloader */
-    FLASH : ORIGIN = 0x10000100, LENGTH = 2048K - 0x100
+    /* The last 72K is kept free for the message log (64K) and config
+       records (8K), see morse_rsdk::storage */
+    FLASH : ORIGIN = 0x10000100, LENGTH = 2048K - 0x100 - 72K
     RAM   : ORIGIN = 0x20000000, LENGTH = 256K
     /* SRAM banks can be used for special purposes */
     SRAM4 : ORIGIN = 0x20040000, LENGTH = 4K
@@ -32,7 +34,7 @@ MEMORY {
     BOOT2 : ORIGIN = 0x10000000, LENGTH = 0x200  /* Increased from 0x100 to 0x200 */
     
     /* Start flash right after the enlarged boot2 section */
-    FLASH : ORIGIN = 0x10000200, LENGTH = 2048K - 0x200  /* Adjusted to account for larger BOOT2 */
+    FLASH : ORIGIN = 0x10000200, LENGTH = 2048K - 0x200 - 72K  /* Adjusted to account for larger BOOT2, last 72K holds the message log and config records */
     
     /* RAM configuration */
     RAM   : ORIGIN = 0x20000000, LENGTH = 256K
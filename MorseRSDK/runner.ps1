@@ -8,6 +8,9 @@ Write-Host "Building release versions of all binaries..."
 cargo build --release --bin transmitter
 cargo build --release --bin receiver
 cargo build --release --bin benchmarks
+cargo build --release --bin transmitter_embassy
+cargo build --release --bin receiver_embassy
+cargo build --release --bin receiver_rtic
 
 Write-Host "`n==== FLASH SIZE (TRANSMITTER) ===="
 cargo size --release --bin transmitter
@@ -107,4 +110,42 @@ $rxRamTotal = $dataSize + $bssSize
 Write-Host "  Flash usage: $rxFlashTotal bytes of 2,048 KB ($('{0:F2}' -f ($rxFlashTotal / 2097152 * 100))%)"
 Write-Host "  Static RAM: $rxRamTotal bytes"
 Write-Host "  Estimated stack: 2,048 bytes"
-Write-Host "  Total RAM: $(2048 + $rxRamTotal) bytes of 264 KB ($('{0:F2}' -f ((2048 + $rxRamTotal) / 270336 * 100))%)"
\ No newline at end of file
+Write-Host "  Total RAM: $(2048 + $rxRamTotal) bytes of 264 KB ($('{0:F2}' -f ((2048 + $rxRamTotal) / 270336 * 100))%)"
+
+# Flash and static RAM of one binary, from the section sizes
+function Get-BinarySize($bin) {
+    $sections = cargo size --release --bin $bin -- -A
+    $size = @{}
+    foreach ($name in "text", "rodata", "data", "bss") {
+        $line = $sections | Select-String -Pattern "^\.$name\s+(\d+)"
+        $size[$name] = if ($line) { [int]$line.Matches[0].Groups[1].Value } else { 0 }
+    }
+    [PSCustomObject]@{
+        Binary = $bin
+        Flash  = $size["text"] + $size["rodata"] + $size["data"]
+        RAM    = $size["data"] + $size["bss"]
+    }
+}
+
+Write-Host "`n==== BLOCKING VS ASYNC ===="
+Write-Host "Same firmware as a superloop, with embassy_futures::join, as Embassy tasks and as RTIC tasks"
+$sizes = "transmitter", "transmitter_embassy", "receiver", "receiver_async", "receiver_embassy", "receiver_rtic" | ForEach-Object {
+    cargo build --release --bin $_ | Out-Null
+    Get-BinarySize $_
+}
+$sizes | Format-Table -AutoSize
+
+Write-Host "`n==== TIMING ===="
+# Timing needs a board: save each binary's RTT output to logs/<binary>.log
+# while it runs, and the sample records in it are tabulated here. Only
+# `receiver` emits any (`symbol_to_screen`, arrival on core0 to on
+# screen); the async, Embassy and RTIC variants and the transmitters have
+# no timing instrumentation yet, so they are compared on size only.
+$logs = "transmitter", "transmitter_embassy", "receiver", "receiver_async", "receiver_embassy", "receiver_rtic" |
+    ForEach-Object { "logs/$_.log" } |
+    Where-Object { Test-Path $_ }
+if ($logs) {
+    cargo bench-report @logs
+} else {
+    Write-Host "No logs/<binary>.log captures found, skipping"
+}
@@ -1,16 +1,21 @@
 //! # ADC Read Time Benchmark Function for RP2040
 //!
-//! Contains the benchmark logic for measuring ADC read times.
+//! Contains the benchmark logic for measuring ADC read times, one
+//! conversion at a time or free-running into DMA.
 //! Designed to be called from a central benchmark runner.
 
 #![no_std]
 // --- RTT Import ---
 use rtt_target::rprintln;
 // --- Other Necessary Imports ---
-use rp2040_hal::{self as hal, timer::Timer};
+use rp2040_hal as hal;
+use embedded_hal::delay::DelayNs;
 use embedded_hal_0_2::adc::OneShot;
 use hal::adc::Adc;
-use cortex_m::delay::Delay;
+
+use crate::bench::{Metric, Run, Samples};
+use crate::cycles::CycleCounter;
+use crate::dma;
 
 /// Number of iterations for the benchmark outer loop
 const NUM_ITERATIONS: usize = 100;
@@ -19,50 +24,61 @@ const NUM_READS: usize = 1000;
 
 // HAL version of the ADC benchmark
 pub fn benchmark_adc<ADCPIN>(
-    timer: &Timer,
+    cycles: &CycleCounter,
     adc: &mut Adc,
     adc_pin: &mut ADCPIN, // Generic AdcPin type
-    delay: &mut Delay,
+    delay: &mut impl DelayNs,
 ) where
     ADCPIN: embedded_hal_0_2::adc::Channel<Adc, ID = u8>,
 {
-    rprintln!("task,method,iteration,reads,avg_time_us");
+    let run = Run {
+        task: "adc",
+        method: "hal_read",
+        params: &[("reads", NUM_READS as u32)],
+        clock_hz: cycles.system_clock_hz(),
+    };
 
+    let mut samples = Samples::new("cycles");
     for iteration in 0..NUM_ITERATIONS {
-        let mut total_time: u64 = 0;
+        let mut total_cycles: u64 = 0;
 
         for _ in 0..NUM_READS {
-            let start = timer.get_counter();
+            let start = cycles.now();
             let _value: u16 = adc.read(adc_pin).unwrap();
-            let end = timer.get_counter();
-
-            let duration = end.checked_duration_since(start).map(|d| d.to_micros()).unwrap_or(0);
-            total_time += duration;
+            total_cycles += cycles.since(start);
         }
 
-        let avg_time = if NUM_READS > 0 {
-            total_time / (NUM_READS as u64)
+        let avg_cycles = if NUM_READS > 0 {
+            total_cycles / (NUM_READS as u64)
         } else {
             0
         };
 
-        rprintln!(
-            "adc,hal_read,{},{},{}",
-            iteration, NUM_READS, avg_time
-        );
+        samples.record(avg_cycles as u32);
+        run.sample(iteration as u32, &[
+            Metric::new("avg_time", cycles.to_micros(avg_cycles), "us"),
+            Metric::new("avg_time", avg_cycles, "cycles"),
+            Metric::new("cpu_time", total_cycles, "cycles"),
+        ]);
 
         delay.delay_ms(100);
     }
+    run.summary(&samples);
     rprintln!("ADC benchmark (HAL) finished.");
 }
 
 // Bare metal version of the ADC benchmark
 pub fn benchmark_adc_raw(
-    timer: &Timer,
+    cycles: &CycleCounter,
     adc_input: u8, // ADC input number (0-3)
-    delay: &mut Delay,
+    delay: &mut impl DelayNs,
 ) {
-    rprintln!("task,method,iteration,reads,avg_time_us");
+    let run = Run {
+        task: "adc",
+        method: "raw_read",
+        params: &[("reads", NUM_READS as u32)],
+        clock_hz: cycles.system_clock_hz(),
+    };
     
     // Get direct pointer to ADC
     let adc = unsafe { &*hal::pac::ADC::ptr() };
@@ -80,11 +96,12 @@ pub fn benchmark_adc_raw(
          .start_once().clear_bit() } // Don't start yet
     });
     
+    let mut samples = Samples::new("cycles");
     for iteration in 0..NUM_ITERATIONS {
-        let mut total_time: u64 = 0;
+        let mut total_cycles: u64 = 0;
 
         for _ in 0..NUM_READS {
-            let start = timer.get_counter();
+            let start = cycles.now();
             
             // Start conversion
             adc.cs().modify(|_, w| w.start_once().set_bit());
@@ -95,27 +112,109 @@ pub fn benchmark_adc_raw(
             // Read result
             let _value = adc.result().read().bits();
             
-            let end = timer.get_counter();
-            let duration = end.checked_duration_since(start).map(|d| d.to_micros()).unwrap_or(0);
-            total_time += duration;
+            total_cycles += cycles.since(start);
         }
 
-        let avg_time = if NUM_READS > 0 {
-            total_time / (NUM_READS as u64)
+        let avg_cycles = if NUM_READS > 0 {
+            total_cycles / (NUM_READS as u64)
         } else {
             0
         };
 
-        rprintln!(
-            "adc,raw_read,{},{},{}",
-            iteration, NUM_READS, avg_time
-        );
+        samples.record(avg_cycles as u32);
+        run.sample(iteration as u32, &[
+            Metric::new("avg_time", cycles.to_micros(avg_cycles), "us"),
+            Metric::new("avg_time", avg_cycles, "cycles"),
+            Metric::new("cpu_time", total_cycles, "cycles"),
+        ]);
 
         delay.delay_ms(100);
     }
     
+    run.summary(&samples);
+
     // Disable ADC
     adc.cs().modify(|_, w| w.en().clear_bit());
     
     rprintln!("ADC benchmark (Raw) finished.");
-}
\ No newline at end of file
+}
+
+// DMA version of the ADC benchmark: the ADC free-runs into its FIFO and
+// DMA, paced by the ADC's DREQ, copies each result out
+pub fn benchmark_adc_dma(
+    cycles: &CycleCounter,
+    adc_input: u8, // ADC input number (0-3)
+    delay: &mut impl DelayNs,
+) {
+    let run = Run {
+        task: "adc",
+        method: "dma_read",
+        params: &[("reads", NUM_READS as u32)],
+        clock_hz: cycles.system_clock_hz(),
+    };
+
+    // Get direct pointer to ADC
+    let adc = unsafe { &*hal::pac::ADC::ptr() };
+    let resets = unsafe { &*hal::pac::RESETS::ptr() };
+
+    // Reset ADC
+    resets.reset().modify(|_, w| w.adc().set_bit());
+    resets.reset().modify(|_, w| w.adc().clear_bit());
+    while resets.reset_done().read().adc().bit_is_clear() {}
+
+    adc.cs().write(|w| unsafe { w.en().set_bit().ainsel().bits(adc_input) });
+    while adc.cs().read().ready().bit_is_clear() {}
+    // FIFO on, a DREQ per result
+    adc.fcs().write(|w| unsafe { w.en().set_bit().dreq_en().set_bit().thresh().bits(1) });
+    dma::reset();
+
+    let mut results = [0u16; NUM_READS];
+    let mut samples = Samples::new("cycles");
+    for iteration in 0..NUM_ITERATIONS {
+        let start = cycles.now();
+
+        dma::start(&dma::Transfer {
+            read: adc.fifo().as_ptr() as u32,
+            write: results.as_mut_ptr() as u32,
+            count: NUM_READS as u32,
+            size: dma::Size::HalfWord,
+            incr_read: false,
+            incr_write: true,
+            read_ring_bits: 0,
+            treq: dma::TREQ_ADC,
+        });
+        adc.cs().modify(|_, w| w.start_many().set_bit());
+        // The CPU's part ends once the ADC and the channel are going
+        let cpu_cycles = cycles.since(start);
+        dma::wait();
+
+        let total_cycles = cycles.since(start);
+
+        // Stop free-running and drop the conversions after the last one
+        adc.cs().modify(|_, w| w.start_many().clear_bit());
+        while adc.cs().read().ready().bit_is_clear() {}
+        while adc.fcs().read().level().bits() > 0 {
+            let _ = adc.fifo().read();
+        }
+
+        let avg_cycles = total_cycles / NUM_READS as u64;
+
+        samples.record(avg_cycles as u32);
+        run.sample(iteration as u32, &[
+            Metric::new("avg_time", cycles.to_micros(avg_cycles), "us"),
+            Metric::new("avg_time", avg_cycles, "cycles"),
+            Metric::new("cpu_time", cpu_cycles, "cycles"),
+        ]);
+
+        delay.delay_ms(100);
+    }
+
+    run.summary(&samples);
+
+    // Disable the FIFO, then the ADC
+    dma::stop();
+    adc.fcs().write(|w| unsafe { w.bits(0) });
+    adc.cs().modify(|_, w| w.en().clear_bit());
+
+    rprintln!("ADC benchmark (DMA) finished.");
+}
@@ -0,0 +1,78 @@
+//! # Async Helpers
+//!
+//! Small adapters that let blocking RP2040 peripherals take part in the
+//! async receiver. They are written for a polling executor such as
+//! `embassy_futures::block_on`: pending futures wake themselves so the
+//! executor polls them again on its next pass.
+
+use core::future::poll_fn;
+use core::task::Poll;
+
+use embedded_hal_0_2::serial;
+use rp2040_hal::timer::Timer;
+
+use crate::Duration;
+
+/// Returns `Pending` once, letting other futures in a `join` or `select` run
+pub async fn yield_now() {
+    let mut yielded = false;
+    poll_fn(|cx| {
+        if yielded {
+            Poll::Ready(())
+        } else {
+            yielded = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    })
+    .await
+}
+
+/// Waits for the next byte on a UART without blocking other futures
+pub async fn read_byte<R: serial::Read<u8>>(uart: &mut R) -> Result<u8, R::Error> {
+    poll_fn(|cx| match uart.read() {
+        Ok(byte) => Poll::Ready(Ok(byte)),
+        Err(nb::Error::Other(e)) => Poll::Ready(Err(e)),
+        Err(nb::Error::WouldBlock) => {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    })
+    .await
+}
+
+/// Async delay on the 1 MHz RP2040 timer.
+///
+/// Yields until the deadline passes instead of spinning, so a delay in
+/// one task never holds up another.
+#[derive(Clone, Copy)]
+pub struct TimerDelay {
+    timer: Timer,
+}
+
+impl TimerDelay {
+    pub fn new(timer: Timer) -> Self {
+        Self { timer }
+    }
+
+    async fn wait(&mut self, duration: Duration) {
+        let deadline = self.timer.get_counter() + duration;
+        while self.timer.get_counter() < deadline {
+            yield_now().await;
+        }
+    }
+}
+
+impl embedded_hal_async::delay::DelayNs for TimerDelay {
+    async fn delay_ns(&mut self, ns: u32) {
+        self.wait(Duration::micros(u64::from(ns.div_ceil(1000)))).await;
+    }
+
+    async fn delay_us(&mut self, us: u32) {
+        self.wait(Duration::micros(u64::from(us))).await;
+    }
+
+    async fn delay_ms(&mut self, ms: u32) {
+        self.wait(Duration::millis(u64::from(ms))).await;
+    }
+}
@@ -0,0 +1,452 @@
+//! # Benchmark Records and Statistics
+//!
+//! Shared by the benchmark modules. Every result goes out over RTT as one
+//! JSON object per line, so tooling can pick them out of the menu and
+//! progress text. Each line carries the schema version, the firmware
+//! version and the system clock, then the [`Run`]'s task, method and
+//! settings. A `sample` record holds one iteration's [`Metric`]s:
+//!
+//! ```text
+//! {"schema":1,"firmware":"0.1.0","clock_hz":125000000,"kind":"sample","task":"gpio","method":"toggle_raw",
+//!  "params":{"toggles":1000},"iteration":0,"metrics":[{"name":"total_time","value":64,"unit":"us"},
+//!  {"name":"total_time","value":9212,"unit":"cycles"}]}
+//! ```
+//!
+//! (shown wrapped). The run also records one value per iteration into
+//! [`Samples`], which keeps a streaming histogram instead of the samples
+//! themselves, and ends with a `summary` record of them:
+//!
+//! ```text
+//! {"schema":1,...,"kind":"summary","task":"gpio","method":"toggle_raw","params":{"toggles":1000},
+//!  "unit":"cycles","samples":100,"min":8002,"p50":8003,"p90":8003,"p99":8011,"max":9212,
+//!  "mean":8015.20,"stddev":120.37,"outlier_threshold":8378,"outliers":1,"worst":[{"iteration":0,"value":9212}]}
+//! ```
+//!
+//! Percentiles come from the histogram: exact below 16, within 1/16 of
+//! the true value above. Outliers are samples far above the upper
+//! quartile, typically an interrupt or a flash cache miss landing in
+//! the timed section. The worst few are listed with their iteration.
+
+use core::fmt;
+
+use rtt_target::rprintln;
+
+/// Values below this have a bucket each; above it, each power of two is
+/// split into this many buckets
+const SUB_BUCKETS: usize = 16;
+const SUB_BITS: u32 = SUB_BUCKETS.trailing_zeros();
+const BUCKETS: usize = SUB_BUCKETS + (32 - SUB_BITS as usize) * SUB_BUCKETS;
+
+/// How many of the largest samples are kept with their iteration
+pub const WORST_KEPT: usize = 4;
+
+/// Version of the record layout, bumped when a field changes meaning
+pub const SCHEMA_VERSION: u32 = 1;
+/// The firmware's crate version, in every record
+pub const FIRMWARE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+fn bucket(value: u32) -> usize {
+    if value < SUB_BUCKETS as u32 {
+        return value as usize;
+    }
+    let exp = 31 - value.leading_zeros();
+    let sub = (value >> (exp - SUB_BITS)) as usize & (SUB_BUCKETS - 1);
+    SUB_BUCKETS + (exp - SUB_BITS) as usize * SUB_BUCKETS + sub
+}
+
+/// Smallest value that falls in `bucket`
+fn bucket_floor(bucket: usize) -> u32 {
+    if bucket < SUB_BUCKETS {
+        return bucket as u32;
+    }
+    let exp = ((bucket - SUB_BUCKETS) / SUB_BUCKETS) as u32 + SUB_BITS;
+    let sub = (bucket - SUB_BUCKETS) % SUB_BUCKETS;
+    ((SUB_BUCKETS + sub) as u32) << (exp - SUB_BITS)
+}
+
+/// Samples of one benchmark run, as a histogram plus running sums
+pub struct Samples {
+    unit: &'static str,
+    histogram: [u32; BUCKETS],
+    count: u32,
+    min: u32,
+    max: u32,
+    sum: u64,
+    sum_squares: u128,
+    /// Largest first, as (iteration, value)
+    worst: [Option<(u32, u32)>; WORST_KEPT],
+}
+
+impl Samples {
+    /// `unit` is printed with the summary, e.g. `"us"`
+    pub const fn new(unit: &'static str) -> Self {
+        Self {
+            unit,
+            histogram: [0; BUCKETS],
+            count: 0,
+            min: u32::MAX,
+            max: 0,
+            sum: 0,
+            sum_squares: 0,
+            worst: [None; WORST_KEPT],
+        }
+    }
+
+    /// Adds the next iteration's sample
+    pub fn record(&mut self, value: u32) {
+        let iteration = self.count;
+        self.histogram[bucket(value)] += 1;
+        self.count += 1;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.sum += u64::from(value);
+        self.sum_squares += u128::from(value) * u128::from(value);
+
+        if let Some(slot) = self.worst.iter().position(|w| w.is_none_or(|(_, v)| value > v)) {
+            self.worst.copy_within(slot..WORST_KEPT - 1, slot + 1);
+            self.worst[slot] = Some((iteration, value));
+        }
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// Smallest recorded value with at least `percent`% of the samples at
+    /// or below it, to histogram precision
+    pub fn percentile(&self, percent: u32) -> Option<u32> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = (u64::from(self.count) * u64::from(percent)).div_ceil(100).max(1);
+        let mut seen = 0u64;
+        let bucket = self.histogram.iter().position(|&n| {
+            seen += u64::from(n);
+            seen >= rank
+        })?;
+        Some(bucket_floor(bucket).clamp(self.min, self.max))
+    }
+
+    /// Values above this are outliers: three times the interquartile range
+    /// above the upper quartile. The range is taken as at least 1/64 of the
+    /// upper quartile, so a run of near-identical cycle counts does not
+    /// flag every sample a few cycles above the rest.
+    pub fn outlier_threshold(&self) -> Option<u32> {
+        let q1 = self.percentile(25)?;
+        let q3 = self.percentile(75)?;
+        let spread = (q3 - q1).max(q3 / 64).max(1);
+        Some(q3.saturating_add(spread.saturating_mul(3)))
+    }
+
+    pub fn summary(&self) -> Option<Summary> {
+        let threshold = self.outlier_threshold()?;
+        let n = u128::from(self.count);
+        let sum = u128::from(self.sum);
+        // Variance in hundredths squared, so its root is in hundredths
+        let variance_x10000 = (self.sum_squares * n - sum * sum) * 10_000 / (n * n);
+        let outliers = self
+            .histogram
+            .iter()
+            .enumerate()
+            .filter(|&(bucket, _)| bucket_floor(bucket) > threshold)
+            .map(|(_, &n)| n)
+            .sum();
+        let mut worst = self.worst;
+        for w in worst.iter_mut() {
+            *w = w.filter(|&(_, value)| value > threshold);
+        }
+
+        Some(Summary {
+            unit: self.unit,
+            count: self.count,
+            min: self.min,
+            max: self.max,
+            p50: self.percentile(50)?,
+            p90: self.percentile(90)?,
+            p99: self.percentile(99)?,
+            mean_x100: (sum * 100 / n) as u64,
+            stddev_x100: variance_x10000.isqrt() as u64,
+            outlier_threshold: threshold,
+            outliers,
+            worst_outliers: worst,
+        })
+    }
+}
+
+/// Statistics of a finished run
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Summary {
+    pub unit: &'static str,
+    pub count: u32,
+    pub min: u32,
+    pub max: u32,
+    pub p50: u32,
+    pub p90: u32,
+    pub p99: u32,
+    /// Mean in hundredths of the unit
+    pub mean_x100: u64,
+    /// Population standard deviation in hundredths of the unit
+    pub stddev_x100: u64,
+    pub outlier_threshold: u32,
+    /// Samples above `outlier_threshold`, to histogram precision
+    pub outliers: u32,
+    /// The largest outliers as (iteration, value), largest first
+    pub worst_outliers: [Option<(u32, u32)>; WORST_KEPT],
+}
+
+/// The summary's fields of a `summary` record, without the braces
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "\"unit\":\"{}\",\"samples\":{},\"min\":{},\"p50\":{},\"p90\":{},\"p99\":{},\"max\":{},\
+             \"mean\":{}.{:02},\"stddev\":{}.{:02},\"outlier_threshold\":{},\"outliers\":{},\"worst\":[",
+            self.unit,
+            self.count,
+            self.min,
+            self.p50,
+            self.p90,
+            self.p99,
+            self.max,
+            self.mean_x100 / 100,
+            self.mean_x100 % 100,
+            self.stddev_x100 / 100,
+            self.stddev_x100 % 100,
+            self.outlier_threshold,
+            self.outliers
+        )?;
+        for (i, (iteration, value)) in self.worst_outliers.iter().flatten().enumerate() {
+            let separator = if i == 0 { "" } else { "," };
+            write!(f, "{}{{\"iteration\":{},\"value\":{}}}", separator, iteration, value)?;
+        }
+        f.write_str("]")
+    }
+}
+
+/// A measured value
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Value {
+    Int(u64),
+    Real(f32),
+}
+
+impl From<u32> for Value {
+    fn from(value: u32) -> Self {
+        Value::Int(u64::from(value))
+    }
+}
+
+impl From<u64> for Value {
+    fn from(value: u64) -> Self {
+        Value::Int(value)
+    }
+}
+
+impl From<f32> for Value {
+    fn from(value: f32) -> Self {
+        Value::Real(value)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Value::Int(value) => write!(f, "{}", value),
+            Value::Real(value) if value.is_finite() => write!(f, "{:.3}", value),
+            // JSON has no NaN or infinity
+            Value::Real(_) => f.write_str("null"),
+        }
+    }
+}
+
+/// One measurement of an iteration, e.g. `total_time` in `us`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Metric {
+    pub name: &'static str,
+    pub value: Value,
+    pub unit: &'static str,
+}
+
+impl Metric {
+    pub fn new(name: &'static str, value: impl Into<Value>, unit: &'static str) -> Self {
+        Self { name, value: value.into(), unit }
+    }
+}
+
+/// What every record of one benchmark run shares. The run prints its
+/// records over RTT as JSON lines, described in the module docs.
+pub struct Run<'a> {
+    pub task: &'a str,
+    pub method: &'a str,
+    /// Settings of the run, e.g. `("bytes", 250)` for a UART message size
+    pub params: &'a [(&'a str, u32)],
+    /// System clock the cycle counts are in
+    pub clock_hz: u32,
+}
+
+impl Run<'_> {
+    /// Prints a `sample` record with an iteration's metrics
+    pub fn sample(&self, iteration: u32, metrics: &[Metric]) {
+        rprintln!("{}", SampleRecord { run: self, iteration, metrics });
+    }
+
+    /// Prints a `summary` record of the run's samples
+    pub fn summary(&self, samples: &Samples) {
+        match samples.summary() {
+            Some(summary) => rprintln!("{}{}}}", Head { run: self, kind: "summary" }, summary),
+            None => rprintln!("{}\"samples\":0}}", Head { run: self, kind: "summary" }),
+        }
+    }
+}
+
+/// A record's opening brace and shared fields, up to a trailing comma
+struct Head<'a> {
+    run: &'a Run<'a>,
+    kind: &'static str,
+}
+
+impl fmt::Display for Head<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{{\"schema\":{},\"firmware\":\"{}\",\"clock_hz\":{},\"kind\":\"{}\",\"task\":\"{}\",\"method\":\"{}\",\"params\":{{",
+            SCHEMA_VERSION, FIRMWARE_VERSION, self.run.clock_hz, self.kind, self.run.task, self.run.method
+        )?;
+        for (i, (name, value)) in self.run.params.iter().enumerate() {
+            let separator = if i == 0 { "" } else { "," };
+            write!(f, "{}\"{}\":{}", separator, name, value)?;
+        }
+        f.write_str("},")
+    }
+}
+
+struct SampleRecord<'a> {
+    run: &'a Run<'a>,
+    iteration: u32,
+    metrics: &'a [Metric],
+}
+
+impl fmt::Display for SampleRecord<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\"iteration\":{},\"metrics\":[", Head { run: self.run, kind: "sample" }, self.iteration)?;
+        for (i, metric) in self.metrics.iter().enumerate() {
+            let separator = if i == 0 { "" } else { "," };
+            write!(
+                f,
+                "{}{{\"name\":\"{}\",\"value\":{},\"unit\":\"{}\"}}",
+                separator, metric.name, metric.value, metric.unit
+            )?;
+        }
+        f.write_str("]}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::string::ToString;
+
+    fn samples(values: &[u32]) -> Samples {
+        let mut samples = Samples::new("us");
+        for &value in values {
+            samples.record(value);
+        }
+        samples
+    }
+
+    #[test]
+    fn buckets_cover_every_value_in_order() {
+        for value in (0..5000).chain([u32::MAX / 2, u32::MAX - 1, u32::MAX]) {
+            let b = bucket(value);
+            assert!(b < BUCKETS);
+            assert!(bucket_floor(b) <= value, "{}", value);
+            if b + 1 < BUCKETS {
+                assert!(bucket_floor(b + 1) > value, "{}", value);
+            }
+        }
+        assert_eq!(bucket(u32::MAX), BUCKETS - 1);
+    }
+
+    #[test]
+    fn percentiles_are_exact_for_small_values() {
+        let values: std::vec::Vec<u32> = (1..=10).collect();
+        let samples = samples(&values);
+        assert_eq!(samples.percentile(50), Some(5));
+        assert_eq!(samples.percentile(90), Some(9));
+        assert_eq!(samples.percentile(99), Some(10));
+        assert_eq!(samples.percentile(0), Some(1));
+    }
+
+    #[test]
+    fn percentiles_stay_within_a_sixteenth() {
+        let values: std::vec::Vec<u32> = (0..1000).map(|i| 10_000 + i * 7).collect();
+        let samples = samples(&values);
+        let p50 = samples.percentile(50).unwrap();
+        let exact = values[499];
+        assert!(p50 <= exact && exact - p50 <= exact / 16, "{} vs {}", p50, exact);
+    }
+
+    #[test]
+    fn summary_has_mean_and_stddev_in_hundredths() {
+        let summary = samples(&[2, 4, 4, 4, 5, 5, 7, 9]).summary().unwrap();
+        assert_eq!(summary.mean_x100, 500);
+        assert_eq!(summary.stddev_x100, 200);
+        assert_eq!((summary.min, summary.max), (2, 9));
+        assert_eq!(
+            summary.to_string(),
+            "\"unit\":\"us\",\"samples\":8,\"min\":2,\"p50\":4,\"p90\":9,\"p99\":9,\"max\":9,\
+             \"mean\":5.00,\"stddev\":2.00,\"outlier_threshold\":8,\"outliers\":1,\"worst\":[{\"iteration\":7,\"value\":9}]"
+        );
+    }
+
+    #[test]
+    fn cache_miss_on_first_iteration_is_flagged() {
+        let mut values = [11u32; 100];
+        values[0] = 31;
+        values[57] = 12;
+        values[88] = 25;
+        let summary = samples(&values).summary().unwrap();
+        assert_eq!(summary.outlier_threshold, 14);
+        assert_eq!(summary.outliers, 2);
+        assert_eq!(summary.worst_outliers, [Some((0, 31)), Some((88, 25)), None, None]);
+        assert!(summary.to_string().ends_with(
+            "\"outliers\":2,\"worst\":[{\"iteration\":0,\"value\":31},{\"iteration\":88,\"value\":25}]"
+        ));
+    }
+
+    #[test]
+    fn only_the_worst_outliers_are_kept() {
+        let values: std::vec::Vec<u32> = (0..100).map(|i| if i % 10 == 0 { 500 + i } else { 20 }).collect();
+        let summary = samples(&values).summary().unwrap();
+        assert_eq!(summary.outlier_threshold, 23);
+        assert_eq!(summary.outliers, 10);
+        assert_eq!(summary.worst_outliers, [Some((90, 590)), Some((80, 580)), Some((70, 570)), Some((60, 560))]);
+    }
+
+    #[test]
+    fn sample_records_are_json_lines() {
+        let run = Run { task: "uart", method: "tx_raw", params: &[("bytes", 250), ("baud", 115_200)], clock_hz: 125_000_000 };
+        let metrics = [
+            Metric::new("time", 21_701u64, "us"),
+            Metric::new("avg_byte", 86.804f32, "us"),
+            Metric::new("ratio", f32::NAN, "x"),
+        ];
+        let record = SampleRecord { run: &run, iteration: 3, metrics: &metrics }.to_string();
+        assert_eq!(
+            record,
+            std::format!(
+                "{{\"schema\":1,\"firmware\":\"{}\",\"clock_hz\":125000000,\"kind\":\"sample\",\"task\":\"uart\",\
+                 \"method\":\"tx_raw\",\"params\":{{\"bytes\":250,\"baud\":115200}},\"iteration\":3,\"metrics\":[\
+                 {{\"name\":\"time\",\"value\":21701,\"unit\":\"us\"}},{{\"name\":\"avg_byte\",\"value\":86.804,\"unit\":\"us\"}},\
+                 {{\"name\":\"ratio\",\"value\":null,\"unit\":\"x\"}}]}}",
+                FIRMWARE_VERSION
+            )
+        );
+        assert!(!record.contains('\n'));
+    }
+
+    #[test]
+    fn no_samples_no_summary() {
+        assert_eq!(Samples::new("us").summary(), None);
+    }
+}
@@ -2,44 +2,403 @@
 #![no_main]
 
 // External crate imports
-use cortex_m::delay::Delay;
 use cortex_m::peripheral::Peripherals as CorePeripherals;
+use cortex_m_rt::exception;
 use panic_halt as _;
 use rp2040_hal::{
-    clocks::{init_clocks_and_plls, Clock}, fugit::RateExtU32, gpio::FunctionUart, pac::{self, Peripherals}, sio, timer::Timer, uart::{DataBits, StopBits, UartConfig}, Sio, Watchdog
+    clocks::{init_clocks_and_plls, Clock}, fugit::{HertzU32, RateExtU32}, gpio::{bank0::{Gpio0, Gpio1, Gpio16, Gpio17, Gpio2, Gpio25, Gpio27}, FunctionNull, FunctionPio0, FunctionUart, Pin, PinState, PullDown}, pac::{self, Peripherals}, timer::Timer, uart::{DataBits, StopBits, UartConfig}, Sio, Watchdog
 };
 use rp_pico::{Pins, XOSC_CRYSTAL_FREQ};
 
 // --- RTT Import ---
-use rtt_target::{rtt_init_print, rprintln};
+use rtt_target::{rtt_init_default, rprintln, set_print_channel, ChannelMode, DownChannel};
 
 // Import modules from the parent crate
 extern crate morse_rsdk;
 use morse_rsdk::adc;
+use morse_rsdk::cycles::{self, CycleCounter};
 use morse_rsdk::gpio;
-use morse_rsdk::interrupt;
+use morse_rsdk::interrupt::{self, Stimulus};
+use morse_rsdk::pio_gpio;
 use morse_rsdk::pwm;
+use morse_rsdk::stack::{self, StackArea};
 use morse_rsdk::uart;
+use morse_rsdk::uart_loopback;
 
-// Define the benchmark mode as a constant
-// 1 = GPIO (HAL)
-// 2 = GPIO (Raw)
-// 3 = PWM (HAL)
-// 4 = PWM (Raw)
-// 5 = ADC (HAL)
-// 6 = ADC (Raw)
-// 7 = Interrupt (HAL)
-// 8 = UART (HAL)
-// 9 = UART (Raw)
-const BENCHMARK_MODE: u8 = 8;
+/// One entry of the menu
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Benchmark {
+    GpioHal,
+    GpioRaw,
+    GpioDma,
+    GpioPio,
+    PwmHal,
+    PwmRaw,
+    AdcHal,
+    AdcRaw,
+    AdcDma,
+    Interrupt,
+    UartHal,
+    UartRaw,
+    UartDma,
+    UartLoopbackHal,
+    UartLoopbackRaw,
+    KeyingSio,
+    KeyingPio,
+}
+
+impl Benchmark {
+    /// In menu order
+    const ALL: [Benchmark; 17] = [
+        Benchmark::GpioHal,
+        Benchmark::GpioRaw,
+        Benchmark::GpioDma,
+        Benchmark::GpioPio,
+        Benchmark::PwmHal,
+        Benchmark::PwmRaw,
+        Benchmark::AdcHal,
+        Benchmark::AdcRaw,
+        Benchmark::AdcDma,
+        Benchmark::Interrupt,
+        Benchmark::UartHal,
+        Benchmark::UartRaw,
+        Benchmark::UartDma,
+        Benchmark::UartLoopbackHal,
+        Benchmark::UartLoopbackRaw,
+        Benchmark::KeyingSio,
+        Benchmark::KeyingPio,
+    ];
+
+    /// Menu key: '1' to '9', then letters, skipping `RUN_ALL_KEY`
+    fn key(self) -> char {
+        MENU_KEYS[self as usize] as char
+    }
+
+    fn from_key(key: char) -> Option<Self> {
+        Self::ALL.iter().copied().find(|b| b.key() == key)
+    }
+
+    /// Method of the benchmark's `stack` record
+    fn slug(self) -> &'static str {
+        match self {
+            Benchmark::GpioHal => "gpio_hal",
+            Benchmark::GpioRaw => "gpio_raw",
+            Benchmark::GpioDma => "gpio_dma",
+            Benchmark::GpioPio => "gpio_pio",
+            Benchmark::PwmHal => "pwm_hal",
+            Benchmark::PwmRaw => "pwm_raw",
+            Benchmark::AdcHal => "adc_hal",
+            Benchmark::AdcRaw => "adc_raw",
+            Benchmark::AdcDma => "adc_dma",
+            Benchmark::Interrupt => "interrupt_hal",
+            Benchmark::UartHal => "uart_hal",
+            Benchmark::UartRaw => "uart_raw",
+            Benchmark::UartDma => "uart_dma",
+            Benchmark::UartLoopbackHal => "uart_loopback_hal",
+            Benchmark::UartLoopbackRaw => "uart_loopback_raw",
+            Benchmark::KeyingSio => "keying_sio",
+            Benchmark::KeyingPio => "keying_pio",
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Benchmark::GpioHal => "GPIO Toggle Benchmark (HAL)",
+            Benchmark::GpioRaw => "GPIO Toggle Benchmark (Raw)",
+            Benchmark::GpioDma => "GPIO Toggle Benchmark (DMA)",
+            Benchmark::GpioPio => "GPIO Toggle Benchmark (PIO)",
+            Benchmark::PwmHal => "PWM Benchmark (HAL)",
+            Benchmark::PwmRaw => "PWM Benchmark (Raw)",
+            Benchmark::AdcHal => "ADC Benchmark (HAL)",
+            Benchmark::AdcRaw => "ADC Benchmark (Raw)",
+            Benchmark::AdcDma => "ADC Benchmark (DMA)",
+            Benchmark::Interrupt => "Interrupt Benchmark (HAL)",
+            Benchmark::UartHal => "UART Benchmark (HAL)",
+            Benchmark::UartRaw => "UART Benchmark (Raw)",
+            Benchmark::UartDma => "UART Benchmark (DMA)",
+            Benchmark::UartLoopbackHal => "UART Loopback Benchmark (HAL)",
+            Benchmark::UartLoopbackRaw => "UART Loopback Benchmark (Raw)",
+            Benchmark::KeyingSio => "Morse Keying Benchmark (SIO)",
+            Benchmark::KeyingPio => "Morse Keying Benchmark (PIO)",
+        }
+    }
+}
+
+/// Menu key for running every benchmark
+const RUN_ALL_KEY: char = 'a';
+/// Keys of the benchmarks, in menu order
+const MENU_KEYS: &[u8] = b"123456789bcdefghijklmnopqrstuvwxyz";
+
+/// The interrupt benchmark makes its edges on GPIO16 with the pad's input
+/// override. Set to true to drive them from GPIO17 wired to GPIO16 instead.
+const INTERRUPT_STIMULUS_JUMPER: bool = false;
+
+type ReleasedPin<I> = Pin<I, FunctionNull, PullDown>;
+
+/// Peripherals and pins the benchmarks use, held in their reset state
+/// between runs. Each run takes what it needs, brings it out of reset and
+/// gives it back.
+struct Board {
+    cycles: CycleCounter,
+    /// Delays between iterations; SysTick belongs to `cycles`
+    delay: Timer,
+    resets: pac::RESETS,
+    system_clock_freq: HertzU32,
+    peripheral_clock_freq: HertzU32,
+    pwm: Option<pac::PWM>,
+    adc: Option<pac::ADC>,
+    uart0: Option<pac::UART0>,
+    pio0: Option<pac::PIO0>,
+    gpio0: Option<ReleasedPin<Gpio0>>,
+    gpio1: Option<ReleasedPin<Gpio1>>,
+    gpio2: Option<ReleasedPin<Gpio2>>,
+    gpio16: Option<ReleasedPin<Gpio16>>,
+    gpio17: Option<ReleasedPin<Gpio17>>,
+    gpio27: Option<ReleasedPin<Gpio27>>,
+    led: Option<ReleasedPin<Gpio25>>,
+}
+
+impl Board {
+    fn run(&mut self, benchmark: Benchmark) {
+        rprintln!("Running {}...", benchmark.name());
+        // Repainted so the high-water mark is this benchmark's
+        let mut stack = StackArea::main();
+        stack.paint_unused();
+        let cycles = &self.cycles;
+        match benchmark {
+            Benchmark::GpioHal => { // GPIO using HAL abstractions
+                let mut toggle_pin = self.gpio2.take().unwrap().into_push_pull_output();
+                gpio::benchmark_gpio_toggle(cycles, &mut toggle_pin, &mut self.delay);
+                self.gpio2 = Some(toggle_pin.into_function());
+            }
+            Benchmark::GpioRaw => { // GPIO using raw register access
+                gpio::benchmark_gpio_toggle_raw(cycles, 2, &mut self.delay); // Using GPIO2
+            }
+            Benchmark::GpioDma => { // GPIO driven by DMA through IO_BANK0
+                gpio::benchmark_gpio_toggle_dma(cycles, 2, &mut self.delay); // Using GPIO2
+            }
+            Benchmark::GpioPio => { // GPIO driven by a PIO state machine
+                let toggle_pin = self.gpio2.take().unwrap().into_function::<FunctionPio0>();
+                let pio0 = pio_gpio::benchmark_gpio_toggle_pio(
+                    cycles,
+                    self.pio0.take().unwrap(),
+                    &toggle_pin,
+                    &mut self.resets,
+                    &mut self.delay,
+                );
+                self.pio0 = Some(pio0);
+                self.gpio2 = Some(toggle_pin.into_function());
+            }
+            Benchmark::PwmHal => { // PWM using HAL
+                let led_pin = self.led.take().unwrap().into_push_pull_output();
+                let (pwm, led_pin) = pwm::benchmark_pwm(
+                    cycles,
+                    led_pin,
+                    self.pwm.take().unwrap(),
+                    &mut self.resets,
+                    &mut self.delay,
+                    self.system_clock_freq,
+                );
+                self.pwm = Some(pwm);
+                self.led = Some(led_pin.into_function());
+            }
+            Benchmark::PwmRaw => { // PWM using raw register access
+                pwm::benchmark_pwm_raw(
+                    cycles,
+                    25, // LED pin (GPIO25)
+                    &mut self.delay,
+                    self.system_clock_freq,
+                );
+            }
+            Benchmark::AdcHal => { // ADC using HAL
+                let mut adc_hal = rp2040_hal::adc::Adc::new(self.adc.take().unwrap(), &mut self.resets);
+                let adc_pin_gpio = self.gpio27.take().unwrap().into_floating_input();
+                let mut adc_pin = rp2040_hal::adc::AdcPin::new(adc_pin_gpio).unwrap();
+                adc::benchmark_adc(
+                    cycles,
+                    &mut adc_hal,
+                    &mut adc_pin,
+                    &mut self.delay,
+                );
+                self.adc = Some(adc_hal.free());
+                self.gpio27 = Some(adc_pin.release().into_pull_down_disabled().into_function());
+            }
+            Benchmark::AdcRaw => { // ADC using raw register access
+                adc::benchmark_adc_raw(
+                    cycles,
+                    0, // ADC input 0 (GPIO27 = ADC1)
+                    &mut self.delay,
+                );
+            }
+            Benchmark::AdcDma => { // ADC free-running into DMA
+                adc::benchmark_adc_dma(
+                    cycles,
+                    0, // ADC input 0 (GPIO27 = ADC1)
+                    &mut self.delay,
+                );
+            }
+            Benchmark::Interrupt => { // Interrupt using HAL
+                let button_pin = self.gpio16.take().unwrap().into_pull_up_input();
+                let led_pin = self.led.take().unwrap().into_push_pull_output();
+                let mut stimulus = if INTERRUPT_STIMULUS_JUMPER {
+                    Stimulus::Jumper(self.gpio17.take().unwrap().into_push_pull_output_in_state(PinState::High))
+                } else {
+                    Stimulus::PadOverride
+                };
+                let (button_pin, led_pin) = interrupt::benchmark_interrupt(
+                    cycles,
+                    button_pin,
+                    led_pin,
+                    &mut stimulus,
+                    &mut self.delay,
+                );
+                if let Stimulus::Jumper(stimulus_pin) = stimulus {
+                    self.gpio17 = Some(stimulus_pin.into_function());
+                }
+                self.gpio16 = Some(button_pin.into_pull_down_input().into_function());
+                self.led = Some(led_pin.into_function());
+            }
+            Benchmark::UartHal => { // UART using HAL
+                let uart_pins = (
+                    self.gpio0.take().unwrap().into_function::<FunctionUart>(), // TX
+                    self.gpio1.take().unwrap().into_function::<FunctionUart>(), // RX
+                );
+                let mut uart = rp2040_hal::uart::UartPeripheral::new(self.uart0.take().unwrap(), uart_pins, &mut self.resets)
+                    .enable(
+                        UartConfig::new(115_200u32.Hz(), DataBits::Eight, None, StopBits::One),
+                        self.peripheral_clock_freq,
+                    )
+                    .unwrap();
+                uart::benchmark_uart(
+                    &mut uart, 
+                    cycles,
+                    &mut self.delay,
+                );
+                let (uart0, (tx, rx)) = uart.disable().free();
+                self.uart0 = Some(uart0);
+                self.gpio0 = Some(tx.into_function());
+                self.gpio1 = Some(rx.into_function());
+            }
+            Benchmark::UartRaw => { // UART using raw register access
+                uart::benchmark_uart_raw(
+                    cycles,
+                    &mut self.delay,
+                    115_200, // Baud rate
+                    self.system_clock_freq.to_Hz(),
+                );
+            }
+            Benchmark::UartDma => { // UART TX fed by DMA
+                uart::benchmark_uart_dma(
+                    cycles,
+                    &mut self.delay,
+                    115_200, // Baud rate
+                    self.system_clock_freq.to_Hz(),
+                );
+            }
+            Benchmark::UartLoopbackHal => { // UART receive path using HAL, in loopback
+                let uart_pins = (
+                    self.gpio0.take().unwrap().into_function::<FunctionUart>(), // TX
+                    self.gpio1.take().unwrap().into_function::<FunctionUart>(), // RX
+                );
+                let uart = rp2040_hal::uart::UartPeripheral::new(self.uart0.take().unwrap(), uart_pins, &mut self.resets);
+                let uart = uart_loopback::benchmark_uart_loopback(
+                    uart,
+                    self.peripheral_clock_freq,
+                    cycles,
+                    &mut self.delay,
+                );
+                let (uart0, (tx, rx)) = uart.free();
+                self.uart0 = Some(uart0);
+                self.gpio0 = Some(tx.into_function());
+                self.gpio1 = Some(rx.into_function());
+            }
+            Benchmark::UartLoopbackRaw => { // UART receive path using raw register access, in loopback
+                uart_loopback::benchmark_uart_loopback_raw(
+                    cycles,
+                    &mut self.delay,
+                    self.peripheral_clock_freq.to_Hz(),
+                );
+            }
+            Benchmark::KeyingSio => { // Sidetone and keying from the CPU, as the transmitter does
+                let mut key_pin = self.gpio2.take().unwrap().into_push_pull_output();
+                pio_gpio::benchmark_keying_sio(cycles, &mut key_pin, &mut self.delay);
+                self.gpio2 = Some(key_pin.into_function());
+            }
+            Benchmark::KeyingPio => { // Sidetone and keying from a PIO state machine
+                let key_pin = self.gpio2.take().unwrap().into_function::<FunctionPio0>();
+                let pio0 = pio_gpio::benchmark_keying_pio(
+                    cycles,
+                    self.pio0.take().unwrap(),
+                    &key_pin,
+                    &mut self.resets,
+                    &mut self.delay,
+                );
+                self.pio0 = Some(pio0);
+                self.gpio2 = Some(key_pin.into_function());
+            }
+        }
+        self.hold_in_reset();
+        stack::report(&stack, benchmark.slug(), self.system_clock_freq.to_Hz(), 0);
+        rprintln!("{} finished.", benchmark.name());
+    }
+
+    /// Puts the blocks the runs take back into reset, so no run sees
+    /// another's configuration whether it uses the HAL or registers
+    fn hold_in_reset(&mut self) {
+        self.resets
+            .reset()
+            .modify(|_, w| w.pwm().set_bit().adc().set_bit().uart0().set_bit().pio0().set_bit());
+    }
+
+    /// Every benchmark in menu order
+    fn run_all(&mut self) {
+        for benchmark in Benchmark::ALL {
+            self.run(benchmark);
+        }
+        rprintln!("Run all finished: {} benchmarks.", Benchmark::ALL.len());
+    }
+}
+
+fn print_menu() {
+    rprintln!("");
+    rprintln!("Benchmarks:");
+    for benchmark in Benchmark::ALL {
+        rprintln!("  {}  {}", benchmark.key(), benchmark.name());
+    }
+    rprintln!("  {}  Run all", RUN_ALL_KEY);
+    rprintln!("Choose:");
+}
+
+#[exception]
+fn SysTick() {
+    cycles::on_systick();
+}
+
+/// Waits for the next key typed into the RTT terminal
+fn read_key(input: &mut DownChannel) -> char {
+    let mut byte = [0u8; 1];
+    loop {
+        if input.read(&mut byte) == 1 && !byte[0].is_ascii_whitespace() {
+            return byte[0] as char;
+        }
+    }
+}
 
 #[rp2040_hal::entry]
 fn main() -> ! {
-    // --- Initialize RTT ---
-    rtt_init_print!();
+    // --- Paint the free stack for the high-water marks ---
+    StackArea::main().paint_unused();
+
+    // --- Initialize RTT: results up, menu keys down ---
+    let mut channels = rtt_init_default!();
+    // Wait for the host rather than drop results; an unattended run prints
+    // thousands of lines. Only the untimed printing blocks.
+    channels.up.0.set_mode(ChannelMode::BlockIfFull);
+    set_print_channel(channels.up.0);
+    let mut input = channels.down.0;
     
     // --- Initial Setup ---
-    let mut pac = Peripherals::take().unwrap();
+    let pac = Peripherals::take().unwrap();
     let core = CorePeripherals::take().unwrap();
     let mut watchdog = Watchdog::new(pac.WATCHDOG);
     let mut resets = pac.RESETS;
@@ -64,8 +423,9 @@ fn main() -> ! {
     let system_clock_freq = clocks.system_clock.freq();
     let peripheral_clock_freq = clocks.peripheral_clock.freq();
 
-    // --- 5. Initialize Delay ---
-    let mut delay = Delay::new(core.SYST, system_clock_freq.to_Hz());
+    // --- 5. Count cycles on SysTick, delay on the TIMER ---
+    let cycles = CycleCounter::start(core.SYST, system_clock_freq);
+    let delay = timer;
 
     // --- Initialize SIO and Pins ---
     let sio = Sio::new(pac.SIO);
@@ -76,111 +436,40 @@ fn main() -> ! {
         &mut resets,
     );
 
+    let mut board = Board {
+        cycles,
+        delay,
+        resets,
+        system_clock_freq,
+        peripheral_clock_freq,
+        pwm: Some(pac.PWM),
+        adc: Some(pac.ADC),
+        uart0: Some(pac.UART0),
+        pio0: Some(pac.PIO0),
+        gpio0: Some(pins.gpio0),
+        gpio1: Some(pins.gpio1),
+        gpio2: Some(pins.gpio2),
+        gpio16: Some(pins.gpio16),
+        gpio17: Some(pins.gpio17),
+        gpio27: Some(pins.gpio27),
+        led: Some(pins.led),
+    };
+    board.hold_in_reset();
+
     // --- Print Header using RTT ---
     rprintln!("Raspberry Pi Pico Benchmark Suite");
-    rprintln!("Benchmark Mode: {}", BENCHMARK_MODE);
     rprintln!("---------------------------------");
 
     // --- Run Benchmarks ---
-    match BENCHMARK_MODE {
-        1 => { // GPIO using HAL abstractions
-            rprintln!("Running GPIO Toggle Benchmark (HAL)...");
-            let mut toggle_pin = pins.gpio2.into_push_pull_output();
-            gpio::benchmark_gpio_toggle(&timer, &mut toggle_pin, &mut delay);
-        }
-        2 => { // GPIO using raw register access
-            rprintln!("Running GPIO Toggle Benchmark (Raw)...");
-            gpio::benchmark_gpio_toggle_raw(&timer, 2, &mut delay); // Using GPIO2
-        }
-        3 => { // PWM using HAL
-            rprintln!("Running PWM Benchmark (HAL)...");
-            let led_pin = pins.led.into_push_pull_output();
-            pwm::benchmark_pwm(
-                &timer,
-                led_pin,
-                pac.PWM,
-                &mut resets,
-                &mut delay,
-                system_clock_freq,
-            );
-        }
-        4 => { // PWM using raw register access
-            rprintln!("Running PWM Benchmark (Raw)...");
-            pwm::benchmark_pwm_raw(
-                &timer,
-                25, // LED pin (GPIO25)
-                &mut delay,
-                system_clock_freq,
-            );
-        }
-        5 => { // ADC using HAL
-            rprintln!("Running ADC Benchmark (HAL)...");
-            let mut adc_hal = rp2040_hal::adc::Adc::new(pac.ADC, &mut resets);
-            let adc_pin_gpio = pins.gpio27.into_floating_input();
-            let mut adc_pin = rp2040_hal::adc::AdcPin::new(adc_pin_gpio).unwrap();
-            adc::benchmark_adc(
-                &timer,
-                &mut adc_hal,
-                &mut adc_pin,
-                &mut delay,
-            );
-        }
-        6 => { // ADC using raw register access
-            rprintln!("Running ADC Benchmark (Raw)...");
-            adc::benchmark_adc_raw(
-                &timer,
-                0, // ADC input 0 (GPIO27 = ADC1)
-                &mut delay,
-            );
-        }
-        7 => { // Interrupt using HAL
-            rprintln!("Running Interrupt Benchmark (HAL)...");
-            let button_pin = pins.gpio16.into_pull_up_input();
-            let led_pin = pins.led.into_push_pull_output();
-            interrupt::benchmark_interrupt(
-                &timer,
-                button_pin,
-                led_pin,
-                &mut delay,
-                system_clock_freq,
-            );
-        }
-        8 => { // UART using HAL
-            rprintln!("Running UART Benchmark (HAL)...");
-            let uart_pins = (
-                pins.gpio0.into_function::<FunctionUart>(), // TX
-                pins.gpio1.into_function::<FunctionUart>(), // RX
-            );
-            let mut uart = rp2040_hal::uart::UartPeripheral::new(pac.UART0, uart_pins, &mut resets)
-                .enable(
-                    UartConfig::new(115_200u32.Hz(), DataBits::Eight, None, StopBits::One),
-                    peripheral_clock_freq,
-                )
-                .unwrap();
-            uart::benchmark_uart(
-                &mut uart, 
-                &timer,
-                &mut delay,
-            );
-        }
-        9 => { // UART using raw register access
-            rprintln!("Running UART Benchmark (Raw)...");
-            uart::benchmark_uart_raw(
-                &timer,
-                &mut delay,
-                115_200, // Baud rate
-                system_clock_freq.to_Hz(),
-            );
-        }
-        _ => {
-            rprintln!("Invalid benchmark mode selected");
-        }
-    }
-
-    rprintln!("Selected benchmark finished.");
-
-    // Loop forever instead of exiting
     loop {
-        cortex_m::asm::wfi(); // Wait for interrupt
+        print_menu();
+        let key = read_key(&mut input);
+        if key == RUN_ALL_KEY {
+            board.run_all();
+        } else if let Some(benchmark) = Benchmark::from_key(key) {
+            board.run(benchmark);
+        } else {
+            rprintln!("No benchmark '{}'", key);
+        }
     }
-}
\ No newline at end of file
+}
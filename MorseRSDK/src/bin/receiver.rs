@@ -8,409 +8,463 @@ pub static BOOT2: [u8; 256] = rp2040_boot2::BOOT_LOADER_GENERIC_03H;
 use panic_halt as _;
 use core::fmt::Write;
 use core::result::Result::{Ok, Err};
-use core::iter::Iterator;
-use core::convert::Into;
 use core::write;
+use core::cell::RefCell;
+use critical_section::Mutex;
 
 use rp2040_hal::{
-    gpio::{bank0::Gpio25, Pin, FunctionSio, SioOutput, PullDown, FunctionI2c},
-    pac,
+    gpio::{bank0::{Gpio0, Gpio1, Gpio25}, Pin, FunctionSio, FunctionUart, SioOutput, PullDown, FunctionI2c},
+    pac::{self, interrupt},
     timer::Timer,
     clocks::{Clock, init_clocks_and_plls},
     watchdog::Watchdog,
+    multicore::{Multicore, Stack},
+    sio::SioFifo,
     Sio,
     gpio::Pins,
-    uart::{UartPeripheral, UartConfig, DataBits, Parity, StopBits},
+    uart::{UartPeripheral, UartConfig, DataBits, Parity, StopBits, Reader, ReadErrorType},
     i2c::I2C,
 };
 use rp_pico::XOSC_CRYSTAL_FREQ;
 use rp2040_hal::entry;
 use cortex_m::delay::Delay;
 use heapless::String;
-use nb::block;
+use heapless::spsc::{Consumer, Producer, Queue};
 use embedded_hal::digital::OutputPin;
-use embedded_hal_0_2::serial;
-use embedded_hal_0_2::blocking::i2c;
+use embedded_hal_0_2::serial::Read as _;
+use rtt_target::rtt_init_print;
 use rp2040_hal::fugit::RateExtU32;
 
-use morse_rsdk::{
-    BAUD_RATE,
-    MORSE_CODE, LCD_ADDRESS, LCD_BACKLIGHT, LCD_EN_BIT, LCD_RS_BIT, LCD_CLEARDISPLAY,
-    LCD_RETURNHOME, LCD_ENTRYMODESET, LCD_DISPLAYCONTROL, LCD_FUNCTIONSET,
-    LCD_SETDDRAMADDR, LCD_ENTRYLEFT, LCD_ENTRYSHIFTDECREMENT,
-    LCD_DISPLAYON, LCD_CURSOROFF, LCD_BLINKOFF, LCD_4BITMODE, LCD_2LINE, LCD_5X8_DOTS,
-    LCD_CHAR_WIDTH, MAX_MESSAGE_LENGTH, MAX_MORSE_LENGTH
+use morse_rsdk::{Duration, Instant, OLED_ADDRESS, LCD_CHAR_WIDTH, MAX_MESSAGE_LENGTH};
+use morse_rsdk::bench::{Metric, Run};
+use morse_rsdk::keyer::LineEditor;
+use morse_rsdk::shell::Shell;
+use morse_rsdk::stack::{StackArea, Watermark};
+use morse_rsdk::storage::{FlashConfigStore, Rp2040Flash};
+use morse_rsdk::usb::UsbBlockingWriter;
+use morse_rsdk::intercore::{CoreMessage, LatencyStats};
+use morse_rsdk::decoder::{wpm_from_dot_interval, Decoder, DecoderEvent, Trigger, CHAR_TIMEOUT, WORD_TIMEOUT};
+use morse_rsdk::message::MessageRing;
+use morse_rsdk::message_log::{MessageLog, LOG_IDLE};
+use morse_rsdk::display::{
+    Display, GpioSegments, Lcd1602, LinkStats, RttConsole, SerialConsole, SevenSegment, Ssd1306,
+    UsbConsole,
 };
 
-// Timing constants
-const INTER_CHAR_GAP: u32 = 1000; // 1 second for inter-character gap
-const WORD_GAP: u32 = 2000; // 2 seconds for word gap
-
-pub struct Receiver<UART, I2C> 
-where
-    UART: serial::Write<u8> + serial::Read<u8>,
-    I2C: i2c::Write,
-{
-    uart: UART,
-    i2c: I2C,
-    led_pin: Pin<Gpio25, FunctionSio<SioOutput>, PullDown>,
+/// Bytes waiting between `UART0_IRQ` and `Receiver::run`. Holds 255,
+/// about 22 ms of a saturated link at 115200 baud.
+const RX_RING_SIZE: usize = 256;
+/// How often core0 looks for new stack high-water marks on either core
+const STACK_CHECK_INTERVAL: Duration = Duration::secs(1);
+
+// Output selection - any combination of these is shown at once.
+// The UART0 console is always on. The LCD and the OLED share I2C0
+// (GPIO4/5), so at most one of them can be fitted.
+const USE_LCD: bool = true;
+const USE_OLED: bool = false;
+const USE_SEVEN_SEGMENT: bool = false;
+const USE_RTT: bool = false;
+const USE_USB: bool = true;
+const _: () = assert!(!(USE_LCD && USE_OLED), "the LCD and the OLED share I2C0");
+
+/// Core1: decodes what core0 captured and shows it. Every flash write
+/// happens here, so only core0 has to be parked for them.
+pub struct Receiver<D: Display> {
+    display: D,
     timer: Timer,
-    delay: Delay,
-    display_message: String<{MAX_MESSAGE_LENGTH}>,
-    message_index: usize,
-    actual_lcd_address: u8,
-    lcd_available: bool,
+    display_message: MessageRing<{MAX_MESSAGE_LENGTH}>,
+    stats: LinkStats,
+    overruns: u32,
+    latency: LatencyStats,
+    /// Decoded words kept in flash, written once the link is quiet and
+    /// listed by the `dump` command
+    log: Option<MessageLog<Rp2040Flash>>,
+    shell: Option<UsbShell>,
+    clock_hz: u32,
 }
 
-impl<UART, I2C> Receiver<UART, I2C>
-where
-    UART: serial::Write<u8> + serial::Read<u8>,
-    I2C: i2c::Write,
-{
+impl<D: Display> Receiver<D> {
     pub fn new(
-        uart: UART,
-        i2c: I2C,
-        led_pin: Pin<Gpio25, FunctionSio<SioOutput>, PullDown>,
+        display: D,
         timer: Timer,
-        delay: Delay,
+        log: Option<MessageLog<Rp2040Flash>>,
+        shell: Option<UsbShell>,
+        clock_hz: u32,
     ) -> Self {
         Self {
-            uart,
-            i2c,
-            led_pin,
+            display,
             timer,
-            delay,
-            display_message: String::new(),
-            message_index: 0,
-            actual_lcd_address: LCD_ADDRESS,
-            lcd_available: false,
+            display_message: MessageRing::new(),
+            stats: LinkStats::default(),
+            overruns: 0,
+            latency: LatencyStats::default(),
+            log,
+            shell,
+            clock_hz,
         }
     }
 
     pub fn init(&mut self) {
-        // Test LED first
-        self.led_pin.set_high().unwrap();
-        self.delay.delay_ms(500);
-        self.led_pin.set_low().unwrap();
-        
         // Test UART
-        self.uart_log("UART test - if you see this, UART works");
-        
-        // Test I2C LCD
-        if self.lcd_init() {
-            self.uart_log("LCD initialized successfully");
-            self.lcd_command(LCD_CLEARDISPLAY);
-            self.lcd_print("Hello World!");
-        } else {
-            self.uart_log("LCD init failed");
-        }
+        self.log("UART test - if you see this, UART works");
+        self.display.show_status("Hello World!");
         
-        self.uart_log("System ready");
-    }
-
-    fn lcd_write_byte(&mut self, byte_value: u8) -> bool {
-        self.i2c.write(self.actual_lcd_address, &[byte_value]).is_ok()
+        self.log("System ready");
     }
 
-    fn lcd_pulse_enable(&mut self, data: u8) {
-        self.lcd_write_byte(data | LCD_EN_BIT | LCD_BACKLIGHT);
-        self.delay.delay_us(500);
-        self.lcd_write_byte((data & !LCD_EN_BIT) | LCD_BACKLIGHT);
-        self.delay.delay_us(100);
-    }
-
-    fn lcd_write_4bits(&mut self, value: u8) {
-        let data = (value & 0xF0) | LCD_BACKLIGHT;
-        self.lcd_pulse_enable(data);
-    }
-
-    fn lcd_command(&mut self, command: u8) {
-        let high_nibble = (command & 0xF0) | LCD_BACKLIGHT;
-        self.lcd_pulse_enable(high_nibble);
-        
-        let low_nibble = ((command << 4) & 0xF0) | LCD_BACKLIGHT;
-        self.lcd_pulse_enable(low_nibble);
+    /// `received` is when core0 captured the byte or tick that finished `letter`
+    fn add_to_message(&mut self, letter: char, received: Instant) {
+        self.display_message.push(letter);
+        self.display.show_char(letter);
+        let latency = self.timer.get_counter() - received;
+        self.latency.record(latency);
+        let run = Run { task: "symbol_to_screen", method: "receiver", params: &[], clock_hz: self.clock_hz };
+        run.sample(self.latency.count() - 1, &[Metric::new("latency", latency.to_micros() as u32, "us")]);
         
-        self.delay.delay_ms(2);
-    }
-
-    fn lcd_data(&mut self, data: u8) {
-        let high_nibble = (data & 0xF0) | LCD_RS_BIT | LCD_BACKLIGHT;
-        self.lcd_pulse_enable(high_nibble);
-        
-        let low_nibble = ((data << 4) & 0xF0) | LCD_RS_BIT | LCD_BACKLIGHT;
-        self.lcd_pulse_enable(low_nibble);
-        
-        self.delay.delay_us(100);
-    }
-
-    fn lcd_init(&mut self) -> bool {
-        self.uart_log("Initializing I2C for LCD...");
-        
-        self.delay.delay_ms(50);
-        self.lcd_write_byte(LCD_BACKLIGHT);
-        self.delay.delay_ms(50);
-        
-        self.lcd_write_4bits(0x30);
-        self.delay.delay_ms(5);
-        self.lcd_write_4bits(0x30);
-        self.delay.delay_ms(5);
-        self.lcd_write_4bits(0x30);
-        self.delay.delay_ms(5);
-        
-        self.lcd_write_4bits(0x20);
-        self.delay.delay_ms(5);
+        let logged = match self.log.as_mut() {
+            Some(log) => log.push(letter, received).is_ok(),
+            None => true,
+        };
+        if !logged {
+            self.log("Message log write failed");
+        }
         
-        self.lcd_command(LCD_FUNCTIONSET | LCD_4BITMODE | LCD_2LINE | LCD_5X8_DOTS);
-        self.lcd_command(LCD_DISPLAYCONTROL | LCD_DISPLAYON | LCD_CURSOROFF | LCD_BLINKOFF);
-        self.lcd_command(LCD_CLEARDISPLAY);
-        self.delay.delay_ms(5);
-        self.lcd_command(LCD_ENTRYMODESET | LCD_ENTRYLEFT | LCD_ENTRYSHIFTDECREMENT);
-        self.lcd_command(LCD_RETURNHOME);
-        self.delay.delay_ms(5);
+        let mut log_msg = String::<128>::new();
+        let _ = write!(log_msg, "LCD Display: {}", self.display_message.window(LCD_CHAR_WIDTH));
+        self.log(log_msg.as_str());
         
-        let test_msg = "LCD Ready";
-        for c in test_msg.chars() {
-            self.lcd_data(c as u8);
+        if letter == ' ' {
+            let mut log_msg = String::<128>::new();
+            let _ = write!(log_msg, "Symbol to screen: {}", self.latency);
+            self.log(log_msg.as_str());
         }
-        
-        self.lcd_available = true;
-        self.uart_log("LCD initialization complete");
-        true
-    }
-
-    fn lcd_clear(&mut self) {
-        if !self.lcd_available { return; }
-        self.lcd_command(LCD_CLEARDISPLAY);
-        self.delay.delay_ms(5);
     }
 
-    fn lcd_set_cursor(&mut self, col: u8, row: u8) {
-        if !self.lcd_available { return; }
-        
-        let row_offsets = [0x00, 0x40, 0x14, 0x54];
-        self.lcd_command(LCD_SETDDRAMADDR | (col + row_offsets[row as usize % 4]));
+    pub fn log(&mut self, message: &str) {
+        self.display.log(message);
     }
 
-    fn lcd_print(&mut self, text: &str) {
-        if !self.lcd_available { return; }
-        
-        for c in text.chars() {
-            self.lcd_data(c as u8);
+    /// Writes the queued words to flash
+    fn flush_log(&mut self) {
+        let Some(log) = self.log.as_mut() else {
+            return;
+        };
+        if log.pending() > 0 && log.flush().is_err() {
+            self.log("Message log write failed");
         }
     }
 
-    fn add_to_message(&mut self, letter: char) {
-        if self.message_index >= MAX_MESSAGE_LENGTH - 1 {
-            let mut temp = String::<{MAX_MESSAGE_LENGTH}>::new();
-            let _ = write!(&mut temp, "{}", &self.display_message[1..]);
-            self.display_message.clear();
-            let _ = self.display_message.push_str(temp.as_str());
-            self.message_index = MAX_MESSAGE_LENGTH - 2;
-        }
-        
-        let _ = self.display_message.push(letter);
-        self.message_index += 1;
-        self.update_lcd_display();
+    /// Runs any commands typed on the USB port since the last call
+    fn serve_shell(&mut self) {
+        let Some((shell, input)) = self.shell.as_mut() else {
+            return;
+        };
+        let log = &mut self.log;
+        morse_rsdk::usb::read_lines(input, |line| run_command(shell, log.as_mut(), line));
     }
 
-    fn update_lcd_display(&mut self) {
-        if !self.lcd_available { return; }
-        
-        let start_pos = if self.display_message.len() > LCD_CHAR_WIDTH {
-            self.display_message.len() - LCD_CHAR_WIDTH
-        } else {
-            0
-        };
-        
-        let display_text = {
-            let mut temp = String::<{LCD_CHAR_WIDTH}>::new();
-            let _ = write!(&mut temp, "{}", &self.display_message[start_pos..]);
-            temp
-        };
-        
-        self.lcd_clear();
-        self.lcd_set_cursor(0, 0);
-        self.lcd_print("Morse Receiver");
-        
-        self.lcd_set_cursor(0, 1);
-        self.lcd_print(display_text.as_str());
-        
-        let mut log_msg = String::<128>::new();
-        let _ = write!(log_msg, "LCD Display: {}", display_text);
-        self.uart_log(log_msg.as_str());
+    /// Counts a lost byte or a code that failed to decode
+    fn record_errors(&mut self, count: u32) {
+        self.stats.errors += count;
+        self.display.show_stats(self.stats);
     }
 
-    pub fn uart_log(&mut self, message: &str) {
-        for byte in message.as_bytes() {
-            let _ = block!(self.uart.write(*byte));
+    /// Updates the speed estimate from the time between two dots
+    fn update_wpm(&mut self, dot_interval: Duration) {
+        let Some(wpm) = wpm_from_dot_interval(dot_interval) else {
+            return;
+        };
+        if wpm != self.stats.wpm {
+            self.stats.wpm = wpm;
+            self.display.show_stats(self.stats);
         }
-        let _ = block!(self.uart.write(b'\r'));
-        let _ = block!(self.uart.write(b'\n'));
     }
 
-    fn decode_morse(&self, morse: &str) -> char {
-        if morse.is_empty() { return '?'; }
-        
-        for (i, code) in MORSE_CODE.iter().enumerate() {
-            if *code == morse {
-                return (b'A' + i as u8) as char;
-            }
+    fn display_letter(&mut self, letter: char, received: Instant) {
+        if letter == ' ' || letter.is_ascii_uppercase() {
+            self.add_to_message(letter, received);
+        } else {
+            self.log("Invalid letter detected");
         }
-        '?'
     }
 
-    fn display_letter(&mut self, letter: char) {
-        if letter == ' ' {
-            self.uart_log("Detected: SPACE");
-            self.add_to_message(' ');
-            return;
-        }
-        
-        if letter.is_ascii_uppercase() {
-            let mut message = String::<32>::new();
-            let _ = write!(&mut message, "Decoded: {}", letter);
-            self.uart_log(message.as_str());
-            self.add_to_message(letter);
-        } else {
-            self.uart_log("Invalid letter detected");
+    fn handle_event(&mut self, event: DecoderEvent, received: Instant) {
+        match event {
+            DecoderEvent::Signal { signal, dot_interval } => {
+                self.display.show_signal(signal);
+                if let Some(interval) = dot_interval {
+                    self.update_wpm(interval);
+                }
+                let mut message = String::<32>::new();
+                let _ = write!(&mut message, "Received signal: {}", signal);
+                self.log(message.as_str());
+            }
+            DecoderEvent::Letter { letter, code, trigger } => {
+                self.display_letter(letter, received);
+                let mut message = String::<64>::new();
+                match trigger {
+                    Trigger::Marker => {
+                        let _ = write!(&mut message, "Decoded character: {} ({})", letter, code);
+                    }
+                    Trigger::Timeout => {
+                        let _ = write!(&mut message, "Auto-decoded by timeout: {} ({})", letter, code);
+                    }
+                    Trigger::Text => return,
+                }
+                self.log(message.as_str());
+            }
+            DecoderEvent::Unknown { code, trigger } => {
+                let mut message = String::<64>::new();
+                match trigger {
+                    Trigger::Marker => {
+                        let _ = write!(&mut message, "Failed to decode: ({})", code);
+                    }
+                    Trigger::Timeout => {
+                        let _ = write!(&mut message, "Failed to auto-decode: ({})", code);
+                    }
+                    Trigger::Text => return,
+                }
+                self.log(message.as_str());
+                self.record_errors(1);
+            }
+            DecoderEvent::Space { trigger } => {
+                self.display_letter(' ', received);
+                match trigger {
+                    Trigger::Marker => self.log("Word gap detected - adding space"),
+                    Trigger::Timeout => self.log("Auto word gap - adding space"),
+                    Trigger::Text => {}
+                }
+            }
         }
     }
 
-    pub fn run(&mut self) {
-        let mut morse_buffer = [0u8; MAX_MORSE_LENGTH];
-        let mut buffer_index = 0;
-        let mut last_signal_time = 0u64;
-        let mut last_event_time = 0u64;
-        let mut in_character = false;
-        let mut in_word = false;
-        let mut space_added = false;
+    /// Decodes messages from core0 forever
+    pub fn run(&mut self, fifo: &mut SioFifo) -> ! {
+        let mut decoder = Decoder::new(CHAR_TIMEOUT, WORD_TIMEOUT);
         
-        self.uart_log("Starting Morse reception...");
+        self.log("Starting Morse reception...");
         
-        if self.lcd_available {
-            self.lcd_clear();
-            self.lcd_set_cursor(0, 0);
-            self.lcd_print("Morse Receiver");
-            self.lcd_set_cursor(0, 1);
-            self.lcd_print("Waiting...");
-        }
+        self.display.clear();
+        self.display.show_status("Waiting...");
         
+        let mut last_byte = Instant::from_ticks(0);
         loop {
-            let current_time = self.timer.get_counter().ticks();
-            
-            if let Ok(c) = block!(self.uart.read()) {
-                self.led_pin.set_high().unwrap();
-                self.delay.delay_ms(5);
-                self.led_pin.set_low().unwrap();
-                
-                if c == b'.' || c == b'-' {
-                    if !in_character {
-                        buffer_index = 0;
-                        morse_buffer = [0u8; MAX_MORSE_LENGTH];
-                        in_character = true;
-                        space_added = false;
+            let words = [fifo.read_blocking(), fifo.read_blocking()];
+            let Some(message) = CoreMessage::from_words(words) else {
+                self.log("Bad message from core0");
+                continue;
+            };
+            let at = message.at();
+            match message {
+                CoreMessage::Byte { byte, .. } => {
+                    last_byte = at;
+                    if let Some(event) = decoder.on_byte(byte, at) {
+                        self.handle_event(event, at);
                     }
-                    
-                    if buffer_index < MAX_MORSE_LENGTH - 1 {
-                        morse_buffer[buffer_index] = c;
-                        buffer_index += 1;
-                        
-                        let mut message = String::<32>::new();
-                        let _ = write!(&mut message, "Received signal: {}", c as char);
-                        self.uart_log(message.as_str());
-                    }
-                    
-                    last_signal_time = current_time;
-                    last_event_time = current_time;
-                    in_word = true;
                 }
-                else if c == b'C' || c == b'c' {
-                    if buffer_index > 0 && in_character {
-                        let morse_str = core::str::from_utf8(&morse_buffer[..buffer_index]).unwrap_or("");
-                        let decoded = self.decode_morse(morse_str);
-                        if decoded != '?' {
-                            self.display_letter(decoded);
-                            let mut message = String::<64>::new();
-                            let _ = write!(&mut message, "Decoded character: {} ({})", decoded, morse_str);
-                            self.uart_log(message.as_str());
-                        } else {
-                            let mut message = String::<64>::new();
-                            let _ = write!(&mut message, "Failed to decode: ({})", morse_str);
-                            self.uart_log(message.as_str());
-                        }
-                        
-                        morse_buffer = [0u8; MAX_MORSE_LENGTH];
-                        buffer_index = 0;
-                        in_character = false;
+                CoreMessage::Tick { .. } => {
+                    while let Some(event) = decoder.poll(at) {
+                        self.handle_event(event, at);
                     }
-                    last_event_time = current_time;
-                }
-                else if c == b'W' || c == b'w' {
-                    if in_word && !space_added {
-                        self.display_letter(' ');
-                        in_word = false;
-                        space_added = true;
-                        self.uart_log("Word gap detected - adding space");
+                    if at - last_byte > LOG_IDLE {
+                        self.flush_log();
                     }
-                    last_event_time = current_time;
+                    self.serve_shell();
                 }
-                else if c == b'H' && in_character {
-                    if buffer_index > 0 {
-                        let morse_str = core::str::from_utf8(&morse_buffer[..buffer_index]).unwrap_or("");
-                        let decoded = self.decode_morse(morse_str);
-                        if decoded != '?' {
-                            self.display_letter(decoded);
-                        }
-                        morse_buffer = [0u8; MAX_MORSE_LENGTH];
-                        buffer_index = 0;
-                        in_character = false;
-                    }
+                CoreMessage::Overrun { count, .. } => {
+                    self.overruns += u32::from(count);
+                    let mut message = String::<48>::new();
+                    let _ = write!(message, "Receive overrun, {} bytes lost", self.overruns);
+                    self.log(message.as_str());
+                    self.record_errors(count.into());
                 }
-                else if c == b'O' && in_word {
-                    if !space_added {
-                        self.display_letter(' ');
-                        space_added = true;
-                    }
-                    in_word = false;
+                CoreMessage::RxError { count, .. } => self.record_errors(count.into()),
+            }
+        }
+    }
+}
+
+/// Core0: moves bytes from the receive ring to core1 and keeps time
+pub struct Capture {
+    rx: Consumer<'static, u8, RX_RING_SIZE>,
+    rx_counts: RxCounts,
+    led_pin: Pin<Gpio25, FunctionSio<SioOutput>, PullDown>,
+    timer: Timer,
+    delay: Delay,
+    /// Core0's and core1's
+    stacks: [Watermark; 2],
+}
+
+impl Capture {
+    pub fn new(
+        rx: Consumer<'static, u8, RX_RING_SIZE>,
+        led_pin: Pin<Gpio25, FunctionSio<SioOutput>, PullDown>,
+        timer: Timer,
+        delay: Delay,
+        stacks: [Watermark; 2],
+    ) -> Self {
+        Self {
+            rx,
+            rx_counts: RxCounts::default(),
+            led_pin,
+            timer,
+            delay,
+            stacks,
+        }
+    }
+
+    pub fn init(&mut self) {
+        // Test LED
+        self.led_pin.set_high().unwrap();
+        self.delay.delay_ms(500);
+        self.led_pin.set_low().unwrap();
+    }
+
+    /// Sends lost-byte counts in chunks that fit a message
+    fn send_lost(fifo: &mut SioFifo, mut lost: u32, message: impl Fn(u8) -> CoreMessage) {
+        while lost > 0 {
+            let count = lost.min(u8::MAX.into()) as u8;
+            send(fifo, message(count));
+            lost -= u32::from(count);
+        }
+    }
+
+    /// Forwards whatever `UART0_IRQ` has captured, then a tick, every 5 ms
+    pub fn run(&mut self, fifo: &mut SioFifo) -> ! {
+        let mut last_stack_check = Instant::from_ticks(0);
+        loop {
+            // Never waits, so core1 gets ticks on an idle line too
+            while let Some(byte) = self.rx.dequeue() {
+                self.led_pin.set_high().unwrap();
+                send(fifo, CoreMessage::Byte { byte, at: self.timer.get_counter() });
+            }
+            
+            let counts = critical_section::with(|cs| {
+                LINK_RX.borrow(cs).borrow().as_ref().map(|link| link.counts)
+            }).unwrap_or_default();
+            let at = self.timer.get_counter();
+            Self::send_lost(fifo, counts.overruns.wrapping_sub(self.rx_counts.overruns), |count| {
+                CoreMessage::Overrun { count, at }
+            });
+            Self::send_lost(fifo, counts.errors.wrapping_sub(self.rx_counts.errors), |count| {
+                CoreMessage::RxError { count, at }
+            });
+            self.rx_counts = counts;
+            
+            send(fifo, CoreMessage::Tick { at: self.timer.get_counter() });
+            
+            if at - last_stack_check > STACK_CHECK_INTERVAL {
+                for stack in &mut self.stacks {
+                    stack.check();
                 }
+                last_stack_check = at;
             }
             
-            if in_character && (current_time - last_signal_time > INTER_CHAR_GAP.into()) {
-                if buffer_index > 0 {
-                    let morse_str = core::str::from_utf8(&morse_buffer[..buffer_index]).unwrap_or("");
-                    let decoded = self.decode_morse(morse_str);
-                    if decoded != '?' {
-                        self.display_letter(decoded);
-                        let mut message = String::<64>::new();
-                        let _ = write!(&mut message, "Auto-decoded by timeout: {} ({})", decoded, morse_str);
-                        self.uart_log(message.as_str());
-                    } else {
-                        let mut message = String::<64>::new();
-                        let _ = write!(&mut message, "Failed to auto-decode: ({})", morse_str);
-                        self.uart_log(message.as_str());
+            self.delay.delay_ms(5);
+            self.led_pin.set_low().unwrap();
+        }
+    }
+}
+
+/// Blocks while core1 is behind: the receive ring keeps filling meanwhile
+fn send(fifo: &mut SioFifo, message: CoreMessage) {
+    for word in message.to_words() {
+        fifo.write_blocking(word);
+    }
+}
+
+/// Core1's stack, in words
+const CORE1_STACK_WORDS: usize = 4096;
+static mut CORE1_STACK: Stack<CORE1_STACK_WORDS> = Stack::new();
+
+type LinkPins = (Pin<Gpio0, FunctionUart, PullDown>, Pin<Gpio1, FunctionUart, PullDown>);
+
+/// Bytes lost before reaching `Receiver::run`, as running totals
+#[derive(Clone, Copy, Default)]
+struct RxCounts {
+    /// The ring was full, or the UART's own FIFO overflowed first
+    overruns: u32,
+    /// Parity, framing and break errors
+    errors: u32,
+}
+
+/// UART0's receive side, owned by `UART0_IRQ`
+struct LinkRx {
+    uart: Reader<pac::UART0, LinkPins>,
+    ring: Producer<'static, u8, RX_RING_SIZE>,
+    counts: RxCounts,
+}
+static LINK_RX: Mutex<RefCell<Option<LinkRx>>> = Mutex::new(RefCell::new(None));
+
+/// Moves received bytes from the UART FIFO into the ring. Fires when the
+/// FIFO is half full or has held a byte for 32 bit periods.
+#[interrupt]
+fn UART0_IRQ() {
+    critical_section::with(|cs| {
+        let mut link = LINK_RX.borrow(cs).borrow_mut();
+        let Some(link) = link.as_mut() else { return };
+        loop {
+            match link.uart.read() {
+                Ok(byte) => {
+                    if link.ring.enqueue(byte).is_err() {
+                        link.counts.overruns += 1;
                     }
-                    
-                    morse_buffer = [0u8; MAX_MORSE_LENGTH];
-                    buffer_index = 0;
                 }
-                in_character = false;
+                Err(nb::Error::WouldBlock) => break,
+                Err(nb::Error::Other(ReadErrorType::Overrun)) => link.counts.overruns += 1,
+                Err(nb::Error::Other(_)) => link.counts.errors += 1,
             }
-            
-            if in_word && !space_added && (current_time - last_event_time > WORD_GAP.into()) {
-                self.display_letter(' ');
-                space_added = true;
-                in_word = false;
-                self.uart_log("Auto word gap - adding space");
+        }
+    });
+}
+
+/// Configuration shell on the USB port and the line being typed into it.
+/// `Receiver::run` serves it between core0's ticks, on core1 with the
+/// message log, as `save` writes to flash too.
+type UsbShell = (Shell<FlashConfigStore<Rp2040Flash>>, LineEditor<64>);
+
+/// `dump` lists the message log; everything else goes to the shell
+fn run_command(shell: &mut Shell<FlashConfigStore<Rp2040Flash>>, log: Option<&mut MessageLog<Rp2040Flash>>, line: &str) {
+    match line.trim() {
+        "dump" => {
+            let Some(log) = log else {
+                let _ = write!(UsbBlockingWriter, "No message log\r\n");
+                return;
+            };
+            match log.dump(&mut UsbBlockingWriter) {
+                Ok(count) => { let _ = write!(UsbBlockingWriter, "{} words logged\r\n", count); }
+                Err(e) => { let _ = write!(UsbBlockingWriter, "Log read failed: {:?}\r\n", e); }
             }
-            
-            self.delay.delay_ms(5);
+        }
+        "help" => {
+            shell.execute(line, &mut UsbBlockingWriter);
+            let _ = write!(UsbBlockingWriter, "dump lists the decoded words saved in flash\r\n");
+        }
+        _ => {
+            shell.execute(line, &mut UsbBlockingWriter);
         }
     }
 }
 
+#[interrupt]
+fn USBCTRL_IRQ() {
+    morse_rsdk::usb::poll();
+}
+
+/// Parks core0 in RAM while core1 writes to flash
+#[interrupt]
+fn SIO_IRQ_PROC0() {
+    morse_rsdk::lockout::on_fifo_irq();
+}
+
 #[entry]
 fn main() -> ! {
+    // Paint the free stack first, so the high-water mark covers start-up
+    let mut main_stack = StackArea::main();
+    main_stack.paint_unused();
+    // RTT carries the stack high-water marks, and the decoded text with USE_RTT
+    rtt_init_print!();
+
     let mut pac = pac::Peripherals::take().unwrap();
     let core = pac::CorePeripherals::take().unwrap();
     
@@ -425,7 +479,7 @@ fn main() -> ! {
         &mut watchdog,
     ).ok().unwrap();
     
-    let sio = Sio::new(pac.SIO);
+    let mut sio = Sio::new(pac.SIO);
     let pins = Pins::new(
         pac.IO_BANK0,
         pac.PADS_BANK0,
@@ -436,38 +490,154 @@ fn main() -> ! {
     let delay = cortex_m::delay::Delay::new(core.SYST, clocks.system_clock.freq().to_Hz());
     let timer = Timer::new(pac.TIMER, &mut pac.RESETS, &clocks);
     
+    // Saved settings from the end of flash, or the defaults.
+    // The receiver's settings are only read here, at start-up.
+    let shell = Shell::new(FlashConfigStore::new(Rp2040Flash));
+    let config = *shell.config();
+    
+    // Decoded words are appended to flash as well as shown
+    let log = MessageLog::new(Rp2040Flash).ok();
+    let log_boot = log.as_ref().map(MessageLog::boot);
+    
+    // USB CDC port for reading decoded text without a USB-UART adapter,
+    // and for the configuration shell
+    let usb_shell = USE_USB.then(|| (shell, LineEditor::new()));
+    let usb = if USE_USB {
+        morse_rsdk::usb::init(
+            pac.USBCTRL_REGS,
+            pac.USBCTRL_DPRAM,
+            clocks.usb_clock,
+            &mut pac.RESETS,
+            "Morse Receiver",
+        );
+        Some(UsbConsole)
+    } else {
+        None
+    };
+    
     let uart = UartPeripheral::new(
         pac.UART0,
         (pins.gpio0.into_function(), pins.gpio1.into_function()),
         &mut pac.RESETS,
     )
     .enable(
-        UartConfig::new(BAUD_RATE.Hz(), DataBits::Eight, core::prelude::v1::Some(Parity::Odd), StopBits::One),
+        UartConfig::new(config.baud_rate.Hz(), DataBits::Eight, core::prelude::v1::Some(Parity::Odd), StopBits::One),
         clocks.peripheral_clock.freq(),
     )
     .unwrap();
     
+    let (mut uart_rx, uart_tx) = uart.split();
+    let ring = cortex_m::singleton!(: Queue<u8, RX_RING_SIZE> = Queue::new()).unwrap();
+    let (producer, consumer) = ring.split();
+    uart_rx.enable_rx_interrupt();
+    critical_section::with(|cs| {
+        LINK_RX.borrow(cs).replace(Some(LinkRx { uart: uart_rx, ring: producer, counts: RxCounts::default() }));
+    });
+    unsafe {
+        pac::NVIC::unmask(pac::Interrupt::UART0_IRQ);
+    }
+    let mut console = SerialConsole::new(uart_tx);
+    
+    match log_boot {
+        Some(boot) => {
+            let mut message = String::<48>::new();
+            let _ = write!(message, "Message log ready, start-up #{}", boot);
+            console.log(message.as_str());
+        }
+        None => console.log("Message log unavailable"),
+    }
+    
     let i2c = I2C::i2c0(
         pac.I2C0,
         pins.gpio4.into_pull_up_input().into_function::<FunctionI2c>(),
         pins.gpio5.into_pull_up_input().into_function::<FunctionI2c>(),
-        100_000.Hz(),
+        // The PCF8574 behind the LCD is limited to 100 kHz
+        if USE_OLED { 400_000.Hz() } else { 100_000.Hz() },
         &mut pac.RESETS,
         clocks.system_clock.freq(),
     );
     
     let led_pin = pins.gpio25.into_push_pull_output();
     
-    let mut receiver = Receiver::new(
-        uart,
-        i2c,
-        led_pin,
-        timer,
-        delay,
-    );
+    let (lcd, oled) = if USE_LCD {
+        console.log("Initializing I2C for LCD...");
+        let mut lcd = Lcd1602::new(i2c, timer, config.lcd_address);
+        match lcd.init() {
+            Ok(()) => {
+                console.log("LCD initialized successfully");
+                (Some(lcd), None)
+            }
+            Err(_) => {
+                console.log("LCD init failed");
+                (None, None)
+            }
+        }
+    } else if USE_OLED {
+        console.log("Initializing I2C for OLED...");
+        let mut oled = Ssd1306::new(i2c, OLED_ADDRESS);
+        match oled.init() {
+            Ok(()) => {
+                console.log("OLED initialized successfully");
+                (None, Some(oled))
+            }
+            Err(_) => {
+                console.log("OLED init failed");
+                (None, None)
+            }
+        }
+    } else {
+        (None, None)
+    };
     
+    // Segments a-g on GPIO6-12. For a 74HC595 on GPIO18-20 use
+    // ShiftRegisterSegments::new(gpio18, gpio19, gpio20) instead.
+    let seven_segment = if USE_SEVEN_SEGMENT {
+        Some(SevenSegment::new(GpioSegments::new([
+            pins.gpio6.into_push_pull_output().into_dyn_pin(),
+            pins.gpio7.into_push_pull_output().into_dyn_pin(),
+            pins.gpio8.into_push_pull_output().into_dyn_pin(),
+            pins.gpio9.into_push_pull_output().into_dyn_pin(),
+            pins.gpio10.into_push_pull_output().into_dyn_pin(),
+            pins.gpio11.into_push_pull_output().into_dyn_pin(),
+            pins.gpio12.into_push_pull_output().into_dyn_pin(),
+        ])))
+    } else {
+        None
+    };
+    
+    let rtt = if USE_RTT { Some(RttConsole) } else { None };
+    
+    let display = console.and(usb).and(lcd).and(oled).and(seven_segment).and(rtt);
+    
+    let clock_hz = clocks.system_clock.freq().to_Hz();
+    let mut receiver = Receiver::new(display, timer, log, usb_shell, clock_hz);
     receiver.init();
-    receiver.run();
     
-    loop {}
+    // Safety: core1 is not running yet, and only runs on this array
+    let mut core1_stack = unsafe {
+        StackArea::new(core::ptr::addr_of_mut!(CORE1_STACK.mem).cast(), CORE1_STACK_WORDS)
+    };
+    core1_stack.paint();
+    let stacks = [
+        Watermark::new(main_stack, "receiver_core0", clock_hz),
+        Watermark::new(core1_stack, "receiver_core1", clock_hz),
+    ];
+    
+    // Decoding, displays and the message log on core1
+    let mut multicore = Multicore::new(&mut pac.PSM, &mut pac.PPB, &mut sio.fifo);
+    let core1 = &mut multicore.cores()[1];
+    core1
+        .spawn(unsafe { &mut *core::ptr::addr_of_mut!(CORE1_STACK.mem) }, move || {
+            let pac = unsafe { pac::Peripherals::steal() };
+            let mut sio = Sio::new(pac.SIO);
+            receiver.run(&mut sio.fifo)
+        })
+        .unwrap();
+    // Only now: spawning core1 used the FIFO this listens on
+    morse_rsdk::lockout::init_victim();
+    
+    // UART capture and timing on core0
+    let mut capture = Capture::new(consumer, led_pin, timer, delay, stacks);
+    capture.init();
+    capture.run(&mut sio.fifo)
 }
\ No newline at end of file
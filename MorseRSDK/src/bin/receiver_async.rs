@@ -0,0 +1,254 @@
+#![no_std]
+#![no_main]
+
+#[link_section = ".boot2"]
+#[used]
+pub static BOOT2: [u8; 256] = rp2040_boot2::BOOT_LOADER_GENERIC_03H;
+
+use panic_halt as _;
+use core::cell::RefCell;
+use core::fmt::Write;
+
+use rp2040_hal::{
+    gpio::{bank0::{Gpio4, Gpio5}, FunctionI2c, Pin, PullUp},
+    i2c::{Controller, I2C},
+    pac::{self, interrupt},
+    timer::Timer,
+    clocks::{Clock, init_clocks_and_plls},
+    watchdog::Watchdog,
+    Sio,
+    gpio::Pins,
+    uart::{UartPeripheral, UartConfig, DataBits, Parity, StopBits},
+};
+use rp_pico::XOSC_CRYSTAL_FREQ;
+use rp2040_hal::entry;
+use embassy_futures::{block_on, join::join, select::{select, Either}};
+use embedded_hal::digital::OutputPin;
+use embedded_hal_0_2::serial;
+use embedded_hal_async::delay::DelayNs;
+use heapless::{Deque, String};
+use rp2040_hal::fugit::RateExtU32;
+
+use morse_rsdk::{BAUD_RATE, LCD_ADDRESS};
+use morse_rsdk::async_io::{read_byte, yield_now, TimerDelay};
+use morse_rsdk::decoder::{Decoder, DecoderEvent, Trigger, CHAR_TIMEOUT, WORD_TIMEOUT};
+use morse_rsdk::display::{AsyncLcd1602, Display, LinkStats, SerialConsole};
+
+/// How often the receive task checks the gap timeouts while the line is idle
+const POLL_INTERVAL_MS: u32 = 5;
+
+/// Characters decoded but not yet drawn on the LCD
+type Pending = RefCell<Deque<char, 32>>;
+
+type LcdBus = I2C<
+    pac::I2C0,
+    (Pin<Gpio4, FunctionI2c, PullUp>, Pin<Gpio5, FunctionI2c, PullUp>),
+    Controller,
+>;
+
+#[interrupt]
+fn I2C0_IRQ() {
+    use rp2040_hal::async_utils::AsyncPeripheral;
+    LcdBus::on_interrupt();
+}
+
+/// Reads the link and decodes it. Decoded characters are logged on the
+/// console straight away and queued for the LCD task.
+async fn receive<UART, W, LED>(
+    mut uart: UART,
+    console: &mut SerialConsole<W>,
+    mut led: LED,
+    timer: Timer,
+    pending: &Pending,
+) -> !
+where
+    UART: serial::Read<u8>,
+    W: serial::Write<u8>,
+    LED: OutputPin,
+{
+    let mut delay = TimerDelay::new(timer);
+    let mut decoder = Decoder::new(CHAR_TIMEOUT, WORD_TIMEOUT);
+    let mut stats = LinkStats::default();
+
+    console.log("Starting Morse reception...");
+
+    loop {
+        let received = select(read_byte(&mut uart), delay.delay_ms(POLL_INTERVAL_MS)).await;
+        let current_time = timer.get_counter();
+
+        match received {
+            Either::First(Ok(c)) => {
+                if let Some(event) = decoder.on_byte(c, current_time) {
+                    handle_event(event, console, pending, &mut stats);
+                }
+
+                let _ = led.set_high();
+                delay.delay_ms(5).await;
+                let _ = led.set_low();
+            }
+            // Parity, framing, break or overrun
+            Either::First(Err(_)) => {
+                stats.errors += 1;
+                console.show_stats(stats);
+            }
+            Either::Second(()) => {}
+        }
+
+        while let Some(event) = decoder.poll(current_time) {
+            handle_event(event, console, pending, &mut stats);
+        }
+    }
+}
+
+fn handle_event<W: serial::Write<u8>>(
+    event: DecoderEvent,
+    console: &mut SerialConsole<W>,
+    pending: &Pending,
+    stats: &mut LinkStats,
+) {
+    let mut message = String::<64>::new();
+    match event {
+        DecoderEvent::Signal { signal, .. } => {
+            let _ = write!(message, "Received signal: {}", signal);
+        }
+        DecoderEvent::Letter { letter, code, trigger } => {
+            queue(pending, letter);
+            console.show_char(letter);
+            match trigger {
+                Trigger::Marker => { let _ = write!(message, "Decoded character: {} ({})", letter, code); }
+                Trigger::Timeout => { let _ = write!(message, "Auto-decoded by timeout: {} ({})", letter, code); }
+                Trigger::Text => {}
+            }
+        }
+        DecoderEvent::Unknown { code, trigger } => {
+            match trigger {
+                Trigger::Marker => { let _ = write!(message, "Failed to decode: ({})", code); }
+                Trigger::Timeout => { let _ = write!(message, "Failed to auto-decode: ({})", code); }
+                Trigger::Text => {}
+            }
+            stats.errors += 1;
+            console.show_stats(*stats);
+        }
+        DecoderEvent::Space { trigger } => {
+            queue(pending, ' ');
+            console.show_char(' ');
+            match trigger {
+                Trigger::Marker => { let _ = message.push_str("Word gap detected - adding space"); }
+                Trigger::Timeout => { let _ = message.push_str("Auto word gap - adding space"); }
+                Trigger::Text => {}
+            }
+        }
+    }
+    if !message.is_empty() {
+        console.log(message.as_str());
+    }
+}
+
+/// Queues a character for the LCD, dropping the oldest if the LCD has fallen behind
+fn queue(pending: &Pending, c: char) {
+    let mut pending = pending.borrow_mut();
+    if pending.is_full() {
+        pending.pop_front();
+    }
+    let _ = pending.push_back(c);
+}
+
+/// Draws queued characters. Each redraw awaits its I2C writes and enable
+/// pulses, so reception carries on while the LCD is busy.
+async fn update_lcd<D: DelayNs>(lcd: &mut Option<AsyncLcd1602<LcdBus, D>>, pending: &Pending) -> ! {
+    if let Some(lcd) = lcd {
+        lcd.clear().await;
+        lcd.show_status("Waiting...").await;
+    }
+
+    loop {
+        // Keep the borrow short: the receive task queues into the same deque
+        let next = pending.borrow_mut().pop_front();
+        match (next, lcd.as_mut()) {
+            (Some(c), Some(lcd)) => lcd.show_char(c).await,
+            _ => yield_now().await,
+        }
+    }
+}
+
+#[entry]
+fn main() -> ! {
+    let mut pac = pac::Peripherals::take().unwrap();
+
+    let mut watchdog = Watchdog::new(pac.WATCHDOG);
+    let clocks = init_clocks_and_plls(
+        XOSC_CRYSTAL_FREQ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    ).ok().unwrap();
+
+    let sio = Sio::new(pac.SIO);
+    let pins = Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+
+    let timer = Timer::new(pac.TIMER, &mut pac.RESETS, &clocks);
+
+    let uart = UartPeripheral::new(
+        pac.UART0,
+        (pins.gpio0.into_function(), pins.gpio1.into_function()),
+        &mut pac.RESETS,
+    )
+    .enable(
+        UartConfig::new(BAUD_RATE.Hz(), DataBits::Eight, Some(Parity::Odd), StopBits::One),
+        clocks.peripheral_clock.freq(),
+    )
+    .unwrap();
+
+    let (uart_rx, uart_tx) = uart.split();
+    let mut console = SerialConsole::new(uart_tx);
+
+    let i2c: LcdBus = I2C::i2c0(
+        pac.I2C0,
+        pins.gpio4.into_pull_up_input().into_function::<FunctionI2c>(),
+        pins.gpio5.into_pull_up_input().into_function::<FunctionI2c>(),
+        100_000.Hz(),
+        &mut pac.RESETS,
+        clocks.system_clock.freq(),
+    );
+
+    // The async I2C driver completes transfers from its interrupt
+    pac::NVIC::unpend(pac::Interrupt::I2C0_IRQ);
+    unsafe {
+        pac::NVIC::unmask(pac::Interrupt::I2C0_IRQ);
+    }
+
+    let led_pin = pins.gpio25.into_push_pull_output();
+    let pending: Pending = RefCell::new(Deque::new());
+
+    // Neither task returns, so this never does either
+    block_on(async {
+        console.log("Initializing I2C for LCD...");
+        let mut lcd = AsyncLcd1602::new(i2c, TimerDelay::new(timer), LCD_ADDRESS);
+        let mut lcd = match lcd.init().await {
+            Ok(()) => {
+                console.log("LCD initialized successfully");
+                Some(lcd)
+            }
+            Err(_) => {
+                console.log("LCD init failed");
+                None
+            }
+        };
+
+        console.log("System ready");
+        join(
+            receive(uart_rx, &mut console, led_pin, timer, &pending),
+            update_lcd(&mut lcd, &pending),
+        )
+        .await
+        .0
+    })
+}
@@ -0,0 +1,285 @@
+//! Embassy version of the receiver.
+//!
+//! Same link protocol, console output and LCD as `receiver.rs`, split into
+//! tasks on the Embassy executor:
+//!
+//! - `link_rx` drains UART0 and runs the decoder
+//! - `show` prints decoder events on the UART and USB consoles
+//! - `lcd` draws decoded characters over async I2C
+//! - `activity_led` blinks the LED for every received byte
+//!
+//! Settings are read from flash at start-up; change them with the
+//! blocking receiver's shell.
+
+#![no_std]
+#![no_main]
+
+#[link_section = ".boot2"]
+#[used]
+pub static BOOT2: [u8; 256] = rp2040_boot2::BOOT_LOADER_GENERIC_03H;
+
+use panic_halt as _;
+use core::fmt::Write;
+
+use rp2040_hal::{
+    gpio::{bank0::{Gpio0, Gpio1, Gpio4, Gpio5, Gpio25}, FunctionI2c, FunctionSio, FunctionUart, Pin, PullDown, PullUp, SioOutput},
+    i2c::{Controller, I2C},
+    pac::{self, interrupt},
+    timer::Timer,
+    clocks::{Clock, init_clocks_and_plls},
+    watchdog::Watchdog,
+    Sio,
+    gpio::Pins,
+    uart::{UartPeripheral, UartConfig, DataBits, Parity, StopBits, Reader, Writer},
+};
+use rp_pico::XOSC_CRYSTAL_FREQ;
+use rp2040_hal::entry;
+use embassy_executor::Executor;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_sync::signal::Signal;
+use embassy_time::{Delay, Duration, Instant, Ticker, Timer as EmbassyTimer};
+use embedded_hal::digital::OutputPin;
+use embedded_hal_0_2::serial::Read as _;
+use heapless::String;
+use rp2040_hal::fugit::RateExtU32;
+
+use morse_rsdk::config::ConfigStore;
+use morse_rsdk::decoder::{Decoder, DecoderEvent, Trigger, CHAR_TIMEOUT, WORD_TIMEOUT};
+use morse_rsdk::display::{AsyncLcd1602, Display, Fanout, LinkStats, SerialConsole, UsbConsole};
+use morse_rsdk::storage::{FlashConfigStore, Rp2040Flash};
+
+/// How often the UART FIFO is drained. 32 bytes take about 2.8 ms at 115200 baud.
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+type LinkPins = (Pin<Gpio0, FunctionUart, PullDown>, Pin<Gpio1, FunctionUart, PullDown>);
+type LinkRx = Reader<pac::UART0, LinkPins>;
+type Consoles = Fanout<SerialConsole<Writer<pac::UART0, LinkPins>>, Option<UsbConsole>>;
+type Led = Pin<Gpio25, FunctionSio<SioOutput>, PullDown>;
+type LcdBus = I2C<
+    pac::I2C0,
+    (Pin<Gpio4, FunctionI2c, PullUp>, Pin<Gpio5, FunctionI2c, PullUp>),
+    Controller,
+>;
+
+/// What the other tasks tell `show`
+enum Report {
+    Event(DecoderEvent),
+    UartError,
+    Log(&'static str),
+}
+
+/// Into `show`, which owns the consoles
+static REPORTS: Channel<CriticalSectionRawMutex, Report, 16> = Channel::new();
+/// Characters for the LCD. Dropped if the LCD falls behind or is missing.
+static LCD_CHARS: Channel<CriticalSectionRawMutex, char, 32> = Channel::new();
+/// A byte arrived on the link
+static ACTIVITY: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+#[embassy_executor::task]
+async fn link_rx(mut uart: LinkRx) {
+    let mut decoder = Decoder::new(CHAR_TIMEOUT, WORD_TIMEOUT);
+    let mut ticker = Ticker::every(POLL_INTERVAL);
+    loop {
+        ticker.next().await;
+        // Embassy ticks are TIMER microseconds, see `time_driver`
+        let now = morse_rsdk::Instant::from_ticks(Instant::now().as_ticks());
+        loop {
+            match uart.read() {
+                Ok(byte) => {
+                    ACTIVITY.signal(());
+                    if let Some(event) = decoder.on_byte(byte, now) {
+                        REPORTS.send(Report::Event(event)).await;
+                    }
+                }
+                Err(nb::Error::WouldBlock) => break,
+                Err(nb::Error::Other(_)) => REPORTS.send(Report::UartError).await,
+            }
+        }
+        while let Some(event) = decoder.poll(now) {
+            REPORTS.send(Report::Event(event)).await;
+        }
+    }
+}
+
+#[embassy_executor::task]
+async fn show(mut consoles: Consoles) {
+    let mut stats = LinkStats::default();
+    consoles.log("Starting Morse reception...");
+    consoles.show_status("Waiting...");
+    loop {
+        let event = match REPORTS.receive().await {
+            Report::Event(event) => event,
+            Report::UartError => {
+                stats.errors += 1;
+                consoles.show_stats(stats);
+                continue;
+            }
+            Report::Log(line) => {
+                consoles.log(line);
+                continue;
+            }
+        };
+
+        let mut message = String::<64>::new();
+        match event {
+            DecoderEvent::Signal { signal, .. } => {
+                consoles.show_signal(signal);
+                let _ = write!(message, "Received signal: {}", signal);
+            }
+            DecoderEvent::Letter { letter, code, trigger } => {
+                show_char(&mut consoles, letter);
+                match trigger {
+                    Trigger::Marker => { let _ = write!(message, "Decoded character: {} ({})", letter, code); }
+                    Trigger::Timeout => { let _ = write!(message, "Auto-decoded by timeout: {} ({})", letter, code); }
+                    Trigger::Text => {}
+                }
+            }
+            DecoderEvent::Unknown { code, trigger } => {
+                match trigger {
+                    Trigger::Marker => { let _ = write!(message, "Failed to decode: ({})", code); }
+                    Trigger::Timeout => { let _ = write!(message, "Failed to auto-decode: ({})", code); }
+                    Trigger::Text => {}
+                }
+                stats.errors += 1;
+                consoles.show_stats(stats);
+            }
+            DecoderEvent::Space { trigger } => {
+                show_char(&mut consoles, ' ');
+                match trigger {
+                    Trigger::Marker => { let _ = message.push_str("Word gap detected - adding space"); }
+                    Trigger::Timeout => { let _ = message.push_str("Auto word gap - adding space"); }
+                    Trigger::Text => {}
+                }
+            }
+        }
+        if !message.is_empty() {
+            consoles.log(message.as_str());
+        }
+    }
+}
+
+fn show_char(consoles: &mut Consoles, c: char) {
+    consoles.show_char(c);
+    let _ = LCD_CHARS.try_send(c);
+}
+
+#[embassy_executor::task]
+async fn lcd(mut lcd: AsyncLcd1602<LcdBus, Delay>) {
+    if lcd.init().await.is_err() {
+        REPORTS.send(Report::Log("LCD init failed")).await;
+        return;
+    }
+    REPORTS.send(Report::Log("LCD initialized successfully")).await;
+    lcd.clear().await;
+    lcd.show_status("Waiting...").await;
+    loop {
+        let c = LCD_CHARS.receive().await;
+        lcd.show_char(c).await;
+    }
+}
+
+#[embassy_executor::task]
+async fn activity_led(mut led: Led) {
+    loop {
+        ACTIVITY.wait().await;
+        let _ = led.set_high();
+        EmbassyTimer::after_millis(5).await;
+        let _ = led.set_low();
+    }
+}
+
+#[interrupt]
+fn TIMER_IRQ_0() {
+    morse_rsdk::time_driver::on_interrupt();
+}
+
+#[interrupt]
+fn USBCTRL_IRQ() {
+    morse_rsdk::usb::poll();
+}
+
+#[interrupt]
+fn I2C0_IRQ() {
+    use rp2040_hal::async_utils::AsyncPeripheral;
+    LcdBus::on_interrupt();
+}
+
+#[entry]
+fn main() -> ! {
+    let mut pac = pac::Peripherals::take().unwrap();
+
+    let mut watchdog = Watchdog::new(pac.WATCHDOG);
+    let clocks = init_clocks_and_plls(
+        XOSC_CRYSTAL_FREQ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    ).ok().unwrap();
+
+    let sio = Sio::new(pac.SIO);
+    let pins = Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+
+    let mut timer = Timer::new(pac.TIMER, &mut pac.RESETS, &clocks);
+    let alarm = timer.alarm_0().unwrap();
+    morse_rsdk::time_driver::init(timer, alarm);
+
+    // Saved settings from the end of flash, or the defaults
+    let config = FlashConfigStore::new(Rp2040Flash).load().unwrap_or_default();
+
+    morse_rsdk::usb::init(
+        pac.USBCTRL_REGS,
+        pac.USBCTRL_DPRAM,
+        clocks.usb_clock,
+        &mut pac.RESETS,
+        "Morse Receiver",
+    );
+
+    let uart = UartPeripheral::new(
+        pac.UART0,
+        (pins.gpio0.into_function(), pins.gpio1.into_function()),
+        &mut pac.RESETS,
+    )
+    .enable(
+        UartConfig::new(config.baud_rate.Hz(), DataBits::Eight, Some(Parity::Odd), StopBits::One),
+        clocks.peripheral_clock.freq(),
+    )
+    .unwrap();
+    let (uart_rx, uart_tx) = uart.split();
+    let mut consoles = SerialConsole::new(uart_tx).and(Some(UsbConsole));
+
+    let i2c: LcdBus = I2C::i2c0(
+        pac.I2C0,
+        pins.gpio4.into_pull_up_input().into_function::<FunctionI2c>(),
+        pins.gpio5.into_pull_up_input().into_function::<FunctionI2c>(),
+        100_000.Hz(),
+        &mut pac.RESETS,
+        clocks.system_clock.freq(),
+    );
+
+    // The async I2C driver completes transfers from its interrupt
+    pac::NVIC::unpend(pac::Interrupt::I2C0_IRQ);
+    unsafe {
+        pac::NVIC::unmask(pac::Interrupt::I2C0_IRQ);
+    }
+
+    let led = pins.gpio25.into_push_pull_output();
+
+    let executor = cortex_m::singleton!(: Executor = Executor::new()).unwrap();
+    executor.run(|spawner| {
+        consoles.log("Initializing I2C for LCD...");
+        consoles.log("System ready");
+        spawner.must_spawn(show(consoles));
+        spawner.must_spawn(lcd(AsyncLcd1602::new(i2c, Delay, config.lcd_address)));
+        spawner.must_spawn(activity_led(led));
+        spawner.must_spawn(link_rx(uart_rx));
+    })
+}
@@ -0,0 +1,407 @@
+//! RTIC version of the receiver.
+//!
+//! Same link protocol, consoles, LCD, shell and message log as
+//! `receiver.rs`, driven by interrupts and RTIC tasks instead of a
+//! superloop:
+//!
+//! - `uart_rx` runs on the UART0 RX interrupt and feeds the decoder
+//! - `timeouts` checks the character and word gaps on a timer
+//! - `show` prints decoder output and queues words for the message log
+//! - `save_log` writes the queued words to flash once the link is quiet
+//! - `update_lcd` draws on the LCD at the lowest priority, so UART
+//!   interrupts preempt its blocking I2C writes
+//! - `usb` services the USB port and its shell
+//!
+//! Higher numbers preempt lower ones: UART 3, timeouts and LED 2,
+//! everything else 1.
+
+#![no_std]
+#![no_main]
+
+#[link_section = ".boot2"]
+#[used]
+pub static BOOT2: [u8; 256] = rp2040_boot2::BOOT_LOADER_GENERIC_03H;
+
+use panic_halt as _;
+use rtic_monotonics::rp2040::prelude::*;
+
+rp2040_timer_monotonic!(Mono);
+
+#[rtic::app(device = rp2040_hal::pac, dispatchers = [TIMER_IRQ_1, TIMER_IRQ_2])]
+mod app {
+    use super::Mono;
+    use core::fmt::Write;
+
+    use rp2040_hal::{
+        gpio::{bank0::{Gpio0, Gpio1, Gpio4, Gpio5, Gpio25}, FunctionI2c, FunctionSio, FunctionUart, Pin, PullDown, PullUp, SioOutput},
+        i2c::{Controller, I2C},
+        pac,
+        clocks::{Clock, init_clocks_and_plls},
+        watchdog::Watchdog,
+        Sio,
+        gpio::Pins,
+        uart::{UartPeripheral, UartConfig, DataBits, Parity, StopBits, Reader, Writer},
+    };
+    use rp_pico::XOSC_CRYSTAL_FREQ;
+    use embedded_hal::digital::OutputPin;
+    use embedded_hal_0_2::serial::Read as _;
+    use heapless::String;
+    use rp2040_hal::fugit::RateExtU32;
+    use rtic_monotonics::rp2040::prelude::*;
+    use rtic_sync::channel::{Receiver, Sender};
+    use rtic_sync::make_channel;
+
+    use morse_rsdk::LCD_CHAR_WIDTH;
+    use morse_rsdk::decoder::{wpm_from_dot_interval, Decoder, DecoderEvent, Trigger, CHAR_TIMEOUT, WORD_TIMEOUT};
+    use morse_rsdk::display::{Display, Fanout, Lcd1602, LinkStats, SerialConsole, UsbConsole};
+    use morse_rsdk::keyer::LineEditor;
+    use morse_rsdk::message::MessageRing;
+    use morse_rsdk::message_log::{MessageLog, LOG_IDLE};
+    use morse_rsdk::shell::Shell;
+    use morse_rsdk::storage::{FlashConfigStore, Rp2040Flash};
+    use morse_rsdk::usb::UsbBlockingWriter;
+    use morse_rsdk::{Instant, MAX_MESSAGE_LENGTH};
+
+    /// How often `timeouts` checks the gaps
+    const TIMEOUT_CHECK_MS: u64 = 5;
+    /// How often `save_log` looks for a quiet link
+    const LOG_CHECK_MS: u64 = 500;
+
+    const REPORT_CAPACITY: usize = 16;
+    const LCD_CAPACITY: usize = 32;
+
+    type LinkPins = (Pin<Gpio0, FunctionUart, PullDown>, Pin<Gpio1, FunctionUart, PullDown>);
+    type Consoles = Fanout<SerialConsole<Writer<pac::UART0, LinkPins>>, UsbConsole>;
+    type LcdBus = I2C<
+        pac::I2C0,
+        (Pin<Gpio4, FunctionI2c, PullUp>, Pin<Gpio5, FunctionI2c, PullUp>),
+        Controller,
+    >;
+    type Lcd = Lcd1602<LcdBus, Mono>;
+
+    /// What the interrupt-level tasks tell `show`
+    pub enum Report {
+        Event(DecoderEvent),
+        UartError,
+    }
+
+    #[shared]
+    struct Shared {
+        decoder: Decoder,
+        log: Option<MessageLog<Rp2040Flash>>,
+        /// When `uart_rx` last read a byte
+        last_byte: Instant,
+    }
+
+    #[local]
+    struct Local {
+        uart_rx: Reader<pac::UART0, LinkPins>,
+        reports: Sender<'static, Report, REPORT_CAPACITY>,
+        consoles: Consoles,
+        lcd: Option<Lcd>,
+        led: Pin<Gpio25, FunctionSio<SioOutput>, PullDown>,
+        shell: Shell<FlashConfigStore<Rp2040Flash>>,
+        input: LineEditor<64>,
+    }
+
+    #[init]
+    fn init(cx: init::Context) -> (Shared, Local) {
+        let mut pac = cx.device;
+
+        let mut watchdog = Watchdog::new(pac.WATCHDOG);
+        let clocks = init_clocks_and_plls(
+            XOSC_CRYSTAL_FREQ,
+            pac.XOSC,
+            pac.CLOCKS,
+            pac.PLL_SYS,
+            pac.PLL_USB,
+            &mut pac.RESETS,
+            &mut watchdog,
+        ).ok().unwrap();
+
+        Mono::start(pac.TIMER, &pac.RESETS);
+
+        let sio = Sio::new(pac.SIO);
+        let pins = Pins::new(
+            pac.IO_BANK0,
+            pac.PADS_BANK0,
+            sio.gpio_bank0,
+            &mut pac.RESETS,
+        );
+
+        // Saved settings from the end of flash, or the defaults.
+        // The receiver's settings are only read here, at start-up.
+        let shell = Shell::new(FlashConfigStore::new(Rp2040Flash));
+        let config = *shell.config();
+        let log = MessageLog::new(Rp2040Flash).ok();
+
+        morse_rsdk::usb::init(
+            pac.USBCTRL_REGS,
+            pac.USBCTRL_DPRAM,
+            clocks.usb_clock,
+            &mut pac.RESETS,
+            "Morse Receiver",
+        );
+
+        let uart = UartPeripheral::new(
+            pac.UART0,
+            (pins.gpio0.into_function(), pins.gpio1.into_function()),
+            &mut pac.RESETS,
+        )
+        .enable(
+            UartConfig::new(config.baud_rate.Hz(), DataBits::Eight, Some(Parity::Odd), StopBits::One),
+            clocks.peripheral_clock.freq(),
+        )
+        .unwrap();
+        let (mut uart_rx, uart_tx) = uart.split();
+        uart_rx.enable_rx_interrupt();
+        let mut consoles = SerialConsole::new(uart_tx).and(UsbConsole);
+
+        match log.as_ref() {
+            Some(log) => {
+                let mut message = String::<48>::new();
+                let _ = write!(message, "Message log ready, start-up #{}", log.boot());
+                consoles.log(message.as_str());
+            }
+            None => consoles.log("Message log unavailable"),
+        }
+
+        let i2c = I2C::i2c0(
+            pac.I2C0,
+            pins.gpio4.into_pull_up_input().into_function::<FunctionI2c>(),
+            pins.gpio5.into_pull_up_input().into_function::<FunctionI2c>(),
+            100_000.Hz(),
+            &mut pac.RESETS,
+            clocks.system_clock.freq(),
+        );
+
+        consoles.log("Initializing I2C for LCD...");
+        let mut lcd = Lcd1602::new(i2c, Mono, config.lcd_address);
+        let lcd = match lcd.init() {
+            Ok(()) => {
+                consoles.log("LCD initialized successfully");
+                Some(lcd)
+            }
+            Err(_) => {
+                consoles.log("LCD init failed");
+                None
+            }
+        };
+
+        consoles.log("System ready");
+        consoles.log("Starting Morse reception...");
+        consoles.clear();
+        consoles.show_status("Waiting...");
+
+        let (reports, report_rx) = make_channel!(Report, REPORT_CAPACITY);
+        let (lcd_tx, lcd_rx) = make_channel!(char, LCD_CAPACITY);
+        timeouts::spawn(reports.clone()).ok();
+        show::spawn(report_rx, lcd_tx).ok();
+        update_lcd::spawn(lcd_rx).ok();
+        save_log::spawn().ok();
+
+        (
+            Shared {
+                decoder: Decoder::new(CHAR_TIMEOUT, WORD_TIMEOUT),
+                log,
+                last_byte: Instant::from_ticks(0),
+            },
+            Local {
+                uart_rx,
+                reports,
+                consoles,
+                lcd,
+                led: pins.gpio25.into_push_pull_output(),
+                shell,
+                input: LineEditor::new(),
+            },
+        )
+    }
+
+    /// Drains the RX FIFO. Fires when the FIFO fills past its threshold or
+    /// holds bytes that have waited 32 bit periods.
+    #[task(binds = UART0_IRQ, priority = 3, shared = [decoder, last_byte], local = [uart_rx, reports])]
+    fn uart_rx(mut cx: uart_rx::Context) {
+        loop {
+            let report = match cx.local.uart_rx.read() {
+                Ok(byte) => {
+                    let _ = blink::spawn();
+                    let now = Mono::now();
+                    cx.shared.last_byte.lock(|last_byte| *last_byte = now);
+                    match cx.shared.decoder.lock(|decoder| decoder.on_byte(byte, now)) {
+                        Some(event) => Report::Event(event),
+                        None => continue,
+                    }
+                }
+                Err(nb::Error::WouldBlock) => break,
+                Err(nb::Error::Other(_)) => Report::UartError,
+            };
+            // Dropped if `show` has fallen 16 reports behind
+            let _ = cx.local.reports.try_send(report);
+        }
+    }
+
+    /// Character and word timeouts. The time is read under the lock so a
+    /// byte decoded in between can never look like it came later.
+    #[task(priority = 2, shared = [decoder])]
+    async fn timeouts(mut cx: timeouts::Context, mut reports: Sender<'static, Report, REPORT_CAPACITY>) {
+        loop {
+            Mono::delay(TIMEOUT_CHECK_MS.millis()).await;
+            while let Some(event) = cx.shared.decoder.lock(|decoder| decoder.poll(Mono::now())) {
+                let _ = reports.try_send(Report::Event(event));
+            }
+        }
+    }
+
+    #[task(priority = 2, local = [led])]
+    async fn blink(cx: blink::Context) {
+        let _ = cx.local.led.set_high();
+        Mono::delay(5.millis()).await;
+        let _ = cx.local.led.set_low();
+    }
+
+    #[task(priority = 1, shared = [log], local = [consoles])]
+    async fn show(
+        mut cx: show::Context,
+        mut reports: Receiver<'static, Report, REPORT_CAPACITY>,
+        mut lcd_chars: Sender<'static, char, LCD_CAPACITY>,
+    ) {
+        let consoles = cx.local.consoles;
+        let mut stats = LinkStats::default();
+        let mut message_ring = MessageRing::<MAX_MESSAGE_LENGTH>::new();
+
+        while let Ok(report) = reports.recv().await {
+            let event = match report {
+                Report::Event(event) => event,
+                Report::UartError => {
+                    stats.errors += 1;
+                    consoles.show_stats(stats);
+                    continue;
+                }
+            };
+
+            let mut message = String::<64>::new();
+            let letter = match event {
+                DecoderEvent::Signal { signal, dot_interval } => {
+                    consoles.show_signal(signal);
+                    if let Some(wpm) = dot_interval.and_then(wpm_from_dot_interval) {
+                        if wpm != stats.wpm {
+                            stats.wpm = wpm;
+                            consoles.show_stats(stats);
+                        }
+                    }
+                    let _ = write!(message, "Received signal: {}", signal);
+                    None
+                }
+                DecoderEvent::Letter { letter, code, trigger } => {
+                    match trigger {
+                        Trigger::Marker => { let _ = write!(message, "Decoded character: {} ({})", letter, code); }
+                        Trigger::Timeout => { let _ = write!(message, "Auto-decoded by timeout: {} ({})", letter, code); }
+                        Trigger::Text => {}
+                    }
+                    Some(letter)
+                }
+                DecoderEvent::Unknown { code, trigger } => {
+                    match trigger {
+                        Trigger::Marker => { let _ = write!(message, "Failed to decode: ({})", code); }
+                        Trigger::Timeout => { let _ = write!(message, "Failed to auto-decode: ({})", code); }
+                        Trigger::Text => {}
+                    }
+                    if !message.is_empty() {
+                        consoles.log(message.as_str());
+                        message.clear();
+                    }
+                    stats.errors += 1;
+                    consoles.show_stats(stats);
+                    None
+                }
+                DecoderEvent::Space { trigger } => {
+                    match trigger {
+                        Trigger::Marker => { let _ = message.push_str("Word gap detected - adding space"); }
+                        Trigger::Timeout => { let _ = message.push_str("Auto word gap - adding space"); }
+                        Trigger::Text => {}
+                    }
+                    Some(' ')
+                }
+            };
+
+            // Same order as `Receiver::handle_event`: show the character, then log
+            match letter {
+                Some(c) if c == ' ' || c.is_ascii_uppercase() => {
+                    message_ring.push(c);
+                    consoles.show_char(c);
+                    let _ = lcd_chars.try_send(c);
+
+                    let now = Mono::now();
+                    let logged = cx.shared.log.lock(|log| match log.as_mut() {
+                        Some(log) => log.push(c, now).is_ok(),
+                        None => true,
+                    });
+                    if !logged {
+                        consoles.log("Message log write failed");
+                    }
+
+                    let mut line = String::<128>::new();
+                    let _ = write!(line, "LCD Display: {}", message_ring.window(LCD_CHAR_WIDTH));
+                    consoles.log(line.as_str());
+                }
+                Some(_) => consoles.log("Invalid letter detected"),
+                None => {}
+            }
+            if !message.is_empty() {
+                consoles.log(message.as_str());
+            }
+        }
+    }
+
+    /// Writes the words `show` queued once nothing has arrived for
+    /// `LOG_IDLE`, as a flash write holds off every interrupt
+    #[task(priority = 1, shared = [log, last_byte])]
+    async fn save_log(mut cx: save_log::Context) {
+        loop {
+            Mono::delay(LOG_CHECK_MS.millis()).await;
+            let quiet = cx.shared.last_byte.lock(|last_byte| Mono::now() - *last_byte > LOG_IDLE);
+            if quiet {
+                cx.shared.log.lock(|log| {
+                    if let Some(log) = log.as_mut().filter(|log| log.pending() > 0) {
+                        // A failed write only loses the words; `dump` shows what is left
+                        let _ = log.flush();
+                    }
+                });
+            }
+        }
+    }
+
+    #[task(priority = 1, local = [lcd])]
+    async fn update_lcd(cx: update_lcd::Context, mut chars: Receiver<'static, char, LCD_CAPACITY>) {
+        while let Ok(c) = chars.recv().await {
+            if let Some(lcd) = cx.local.lcd.as_mut() {
+                lcd.show_char(c);
+            }
+        }
+    }
+
+    /// Configuration shell and `dump`, as on the blocking receiver
+    #[task(binds = USBCTRL_IRQ, priority = 1, shared = [log], local = [shell, input])]
+    fn usb(mut cx: usb::Context) {
+        morse_rsdk::usb::poll();
+        let shell = cx.local.shell;
+        let input = cx.local.input;
+        cx.shared.log.lock(|log| {
+            morse_rsdk::usb::read_lines(input, |line| match line.trim() {
+                "dump" => match log.as_mut().map(|log| log.dump(&mut UsbBlockingWriter)) {
+                    Some(Ok(count)) => { let _ = write!(UsbBlockingWriter, "{} words logged\r\n", count); }
+                    Some(Err(e)) => { let _ = write!(UsbBlockingWriter, "Log read failed: {:?}\r\n", e); }
+                    None => { let _ = write!(UsbBlockingWriter, "No message log\r\n"); }
+                },
+                "help" => {
+                    shell.execute(line, &mut UsbBlockingWriter);
+                    let _ = write!(UsbBlockingWriter, "dump lists the decoded words saved in flash\r\n");
+                }
+                _ => {
+                    shell.execute(line, &mut UsbBlockingWriter);
+                }
+            });
+        });
+    }
+}
@@ -9,43 +9,154 @@ pub static BOOT2: [u8; 256] = rp2040_boot2::BOOT_LOADER_GENERIC_03H;
 use panic_halt as _;
 use cortex_m_semihosting::hprintln;
 
+use core::fmt::Write;
+
 use rp2040_hal::{
     gpio::{bank0::{Gpio16, Gpio21, Gpio25}, Pin, FunctionSio, SioOutput, SioInput, PullUp, PullDown},
-    pac,
+    pac::{self, interrupt},
     timer::Timer,
     clocks::{Clock, init_clocks_and_plls},
     watchdog::Watchdog,
     Sio,
     gpio::Pins,
+    uart::{UartPeripheral, UartConfig, DataBits, Parity, StopBits},
 };
 use rp2040_hal::entry;
+use rp2040_hal::fugit::RateExtU32;
 use rp_pico::XOSC_CRYSTAL_FREQ;
 use cortex_m::delay::Delay;
 use embedded_hal::digital::{InputPin, OutputPin};
-use morse_rsdk::{DEBOUNCE_TIME_MS, DOT_FREQ, DASH_FREQ, SYNC_PATTERN, DOT_THRESHOLD_MS, DASH_THRESHOLD_MS};
+use embedded_hal_0_2::serial;
+use heapless::String;
+use nb::block;
+use rtt_target::rtt_init_print;
+use morse_rsdk::{Duration, Instant, INTER_CHAR_GAP, SYNC_PATTERN, WORD_GAP};
+use morse_rsdk::keyer::{parse_line, Command, Element, KeyQueue, LineEditor, Speed};
+use morse_rsdk::shell::Shell;
+use morse_rsdk::stack::{StackArea, Watermark};
+use morse_rsdk::storage::{FlashConfigStore, Rp2040Flash};
+use morse_rsdk::usb::UsbBlockingWriter;
+
+/// Longest line accepted from the USB port
+const MAX_INPUT_LINE: usize = 64;
+/// Elements waiting to be keyed, roughly 100 characters of text
+const KEY_QUEUE_LENGTH: usize = 512;
+/// How often the main loop looks for a new stack high-water mark
+const STACK_CHECK_INTERVAL: Duration = Duration::secs(1);
 
-pub struct Transmitter {
+pub struct Transmitter<LINK>
+where
+    LINK: serial::Write<u8>,
+{
     button_pin: Pin<Gpio16, FunctionSio<SioInput>, PullUp>,
     speaker_pin: Pin<Gpio21, FunctionSio<SioOutput>, PullDown>,
     led_pin: Pin<Gpio25, FunctionSio<SioOutput>, PullDown>,
+    link: LINK,
     timer: Timer,
     delay: Delay,
+    shell: Shell<FlashConfigStore<Rp2040Flash>>,
+    input: LineEditor<MAX_INPUT_LINE>,
+    queue: KeyQueue<KEY_QUEUE_LENGTH>,
 }
 
-impl Transmitter {
+impl<LINK> Transmitter<LINK>
+where
+    LINK: serial::Write<u8>,
+{
     pub fn new(
         button_pin: Pin<Gpio16, FunctionSio<SioInput>, PullUp>,
         speaker_pin: Pin<Gpio21, FunctionSio<SioOutput>, PullDown>,
         led_pin: Pin<Gpio25, FunctionSio<SioOutput>, PullDown>,
+        link: LINK,
         timer: Timer,
         delay: Delay,
+        shell: Shell<FlashConfigStore<Rp2040Flash>>,
     ) -> Self {
         Self {
             button_pin,
             speaker_pin,
             led_pin,
+            link,
             timer,
             delay,
+            shell,
+            input: LineEditor::new(),
+            queue: KeyQueue::new(),
+        }
+    }
+
+    /// Sends one line to the receiver over UART0, like `uart_log` in the C transmitter
+    fn send_link(&mut self, line: &str) {
+        for byte in line.as_bytes() {
+            let _ = block!(self.link.write(*byte));
+        }
+        let _ = block!(self.link.write(b'\r'));
+        let _ = block!(self.link.write(b'\n'));
+    }
+
+    /// Writes a reply line to the USB terminal
+    fn reply(&mut self, line: &str) {
+        morse_rsdk::usb::write_all(line.as_bytes());
+        morse_rsdk::usb::write_all(b"\r\n");
+    }
+
+    /// Collects typed input from USB and acts on complete lines
+    fn service_usb(&mut self) {
+        let mut input = core::mem::take(&mut self.input);
+        morse_rsdk::usb::read_lines(&mut input, |line| self.handle_line(line));
+        self.input = input;
+    }
+
+    fn handle_line(&mut self, line: &str) {
+        let mut message = String::<96>::new();
+        match parse_line(line) {
+            Command::Send(text) => match self.queue.push_text(text) {
+                Ok(0) => { let _ = write!(message, "Queued: {}", text); }
+                Ok(skipped) => { let _ = write!(message, "Queued: {} ({} unsupported characters skipped)", text, skipped); }
+                Err(_) => { let _ = message.push_str("Queue full, line dropped"); }
+            },
+            Command::Wpm(wpm) => {
+                if let Some(speed) = Speed::from_wpm(wpm) {
+                    self.shell.config_mut().unit_ms = speed.unit_ms();
+                }
+                let _ = write!(message, "Speed: {} WPM", self.speed().wpm());
+            }
+            Command::Abort => {
+                self.queue.clear();
+                let _ = message.push_str("Aborted");
+            }
+            Command::Help => {
+                let _ = message.push_str("Type text to send. /wpm N sets speed, /abort clears the queue,\r\n/show, /get, /set, /save and /reset change settings");
+            }
+            Command::Shell(command) => {
+                // Settings are read from the shell's config as they are used
+                self.shell.execute(command, &mut UsbBlockingWriter);
+                return;
+            }
+            Command::Invalid(line) => { let _ = write!(message, "Bad argument: {}", line); }
+            Command::Empty => return,
+        }
+        self.reply(message.as_str());
+    }
+
+    fn speed(&self) -> Speed {
+        self.shell.config().speed()
+    }
+
+    /// Keys the next queued element, if any
+    fn key_next(&mut self) {
+        match self.queue.pop() {
+            Some(Element::Dot) => self.transmit_dot(),
+            Some(Element::Dash) => self.transmit_dash(),
+            Some(gap @ Element::CharGap) => {
+                self.send_link("CHAR GAP");
+                self.transmit_gap(self.speed().off(gap));
+            }
+            Some(gap @ Element::WordGap) => {
+                self.send_link("WORD GAP");
+                self.transmit_gap(self.speed().off(gap));
+            }
+            None => {}
         }
     }
 
@@ -53,9 +164,9 @@ impl Transmitter {
         hprintln!("Transmitter initialized");
     }
 
-    pub fn generate_tone(&mut self, freq_hz: u32, duration_ms: u32) {
+    pub fn generate_tone(&mut self, freq_hz: u32, duration: Duration) {
         let period_us = 1_000_000 / freq_hz;
-        let cycles = (duration_ms * 1000) / period_us;
+        let cycles = duration.to_micros() as u32 / period_us;
 
         for _ in 0..cycles {
             self.speaker_pin.set_high().unwrap();
@@ -67,22 +178,24 @@ impl Transmitter {
 
     pub fn transmit_dot(&mut self) {
         self.led_pin.set_high().unwrap();
-        self.generate_tone(DOT_FREQ, 250);
+        self.generate_tone(self.shell.config().dot_freq, self.speed().on(Element::Dot));
         self.led_pin.set_low().unwrap();
         hprintln!(".");
-        self.transmit_gap(250);
+        self.send_link(".");
+        self.transmit_gap(self.speed().off(Element::Dot));
     }
 
     pub fn transmit_dash(&mut self) {
         self.led_pin.set_high().unwrap();
-        self.generate_tone(DASH_FREQ, 750);
+        self.generate_tone(self.shell.config().dash_freq, self.speed().on(Element::Dash));
         self.led_pin.set_low().unwrap();
         hprintln!("-");
-        self.transmit_gap(250);
+        self.send_link("-");
+        self.transmit_gap(self.speed().off(Element::Dash));
     }
 
-    pub fn transmit_gap(&mut self, duration_ms: u32) {
-        self.delay.delay_ms(duration_ms);
+    pub fn transmit_gap(&mut self, duration: Duration) {
+        self.delay.delay_us(duration.to_micros() as u32);
     }
 
     pub fn transmit_sync(&mut self) {
@@ -97,10 +210,12 @@ impl Transmitter {
         hprintln!("Sync pattern transmitted");
     }
 
-    pub fn transmit_morse_input(&mut self) {
-        let mut press_start = 0u64;
-        let mut last_release_time = 0u64;
-        let mut last_log_time = 0u64;
+    /// The main loop. `stack` is reported whenever its high-water mark grows.
+    pub fn transmit_morse_input(&mut self, mut stack: Watermark) {
+        let mut press_start = Instant::from_ticks(0);
+        let mut last_release_time = Instant::from_ticks(0);
+        let mut last_log_time = Instant::from_ticks(0);
+        let mut last_stack_check = Instant::from_ticks(0);
         let mut button_was_pressed = false;
         let mut in_word = false;
 
@@ -110,56 +225,74 @@ impl Transmitter {
         self.transmit_sync();
 
         loop {
-            let current_ticks = self.timer.get_counter().ticks();
+            let now = self.timer.get_counter();
             let button_state = self.button_pin.is_low().unwrap();
+            let config = *self.shell.config();
+            let debounce = Duration::millis(config.debounce_ms.into());
 
             if button_state && !button_was_pressed {
-                let current_ms = current_ticks / 1000;
-                let last_release_ms = last_release_time / 1000;
-                
-                if current_ms - last_release_ms > DEBOUNCE_TIME_MS.into() {
-                    press_start = current_ticks;
+                if now - last_release_time > debounce {
+                    press_start = now;
                     button_was_pressed = true;
                 }
             }
             else if !button_state && button_was_pressed {
-                let current_ms = current_ticks / 1000;
-                let press_start_ms = press_start / 1000;
+                let press_duration = now - press_start;
                 
-                if current_ms - press_start_ms > DEBOUNCE_TIME_MS.into() {
-                    let press_duration_ms = current_ms - press_start_ms;
-
-                    if press_duration_ms <= DOT_THRESHOLD_MS.into() {
+                if press_duration > debounce {
+                    if press_duration <= Duration::millis(config.dot_threshold_ms.into()) {
                         self.transmit_dot();
-                    } else if press_duration_ms <= DASH_THRESHOLD_MS.into() {
+                    } else if press_duration <= Duration::millis(config.dash_threshold_ms.into()) {
                         self.transmit_dash();
                     }
-                    last_release_time = current_ticks;
+                    last_release_time = now;
                     in_word = true;
                 }
                 button_was_pressed = false;
             }
             else if !button_state && in_word {
-                let current_ms = current_ticks / 1000;
-                let last_release_ms = last_release_time / 1000;
-                let gap_duration_ms = current_ms - last_release_ms;
+                let gap_duration = now - last_release_time;
 
-                if gap_duration_ms > 1750 {
+                if gap_duration > WORD_GAP {
                     hprintln!("WORD GAP");
+                    self.send_link("WORD GAP");
                     in_word = false;
-                } else if gap_duration_ms > 750 && current_ms - (last_log_time / 1000) > 750 {
+                } else if gap_duration > INTER_CHAR_GAP && now - last_log_time > INTER_CHAR_GAP {
                     hprintln!("CHAR GAP");
-                    last_log_time = current_ticks;
+                    self.send_link("CHAR GAP");
+                    last_log_time = now;
                 }
             }
 
+            // Typed text is keyed only while the straight key is idle
+            self.service_usb();
+            if !button_state && !button_was_pressed {
+                self.key_next();
+            }
+
+            if now - last_stack_check > STACK_CHECK_INTERVAL {
+                stack.check();
+                last_stack_check = now;
+            }
+
             self.delay.delay_ms(10);
         }
     }
 }
 
+#[interrupt]
+fn USBCTRL_IRQ() {
+    morse_rsdk::usb::poll();
+}
+
 #[entry]
 fn main() -> ! {
+    // Paint the free stack first, so the high-water mark covers start-up
+    let mut main_stack = StackArea::main();
+    main_stack.paint_unused();
+    // Stack high-water marks go out over RTT
+    rtt_init_print!();
+
     let mut pac = pac::Peripherals::take().unwrap();
     let core = pac::CorePeripherals::take().unwrap();
     
@@ -185,20 +318,48 @@ fn main() -> ! {
     let delay = cortex_m::delay::Delay::new(core.SYST, clocks.system_clock.freq().to_Hz());
     let timer = Timer::new(pac.TIMER, &mut pac.RESETS, &clocks);
     
+    // Saved settings from the end of flash, or the defaults
+    let shell = Shell::new(FlashConfigStore::new(Rp2040Flash));
+    
+    // Link to the receiver, same wiring and settings as the receiver's UART0
+    let uart = UartPeripheral::new(
+        pac.UART0,
+        (pins.gpio0.into_function(), pins.gpio1.into_function()),
+        &mut pac.RESETS,
+    )
+    .enable(
+        UartConfig::new(shell.config().baud_rate.Hz(), DataBits::Eight, Some(Parity::Odd), StopBits::One),
+        clocks.peripheral_clock.freq(),
+    )
+    .unwrap();
+    let (_, link) = uart.split();
+    
+    // USB CDC port for typing messages to key out
+    morse_rsdk::usb::init(
+        pac.USBCTRL_REGS,
+        pac.USBCTRL_DPRAM,
+        clocks.usb_clock,
+        &mut pac.RESETS,
+        "Morse Transmitter",
+    );
+    
     let button_pin = pins.gpio16.into_pull_up_input();
     let speaker_pin = pins.gpio21.into_push_pull_output();
     let led_pin = pins.gpio25.into_push_pull_output();
     
+    let stack = Watermark::new(main_stack, "transmitter_main", clocks.system_clock.freq().to_Hz());
     let mut transmitter = Transmitter::new(
         button_pin,
         speaker_pin,
         led_pin,
+        link,
         timer,
         delay,
+        shell,
     );
     
     transmitter.init();
-    transmitter.transmit_morse_input();
+    transmitter.transmit_morse_input(stack);
     
     loop {}
 }
\ No newline at end of file
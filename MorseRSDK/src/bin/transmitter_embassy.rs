@@ -0,0 +1,335 @@
+//! Embassy version of the transmitter.
+//!
+//! Same wiring, link protocol and USB commands as `transmitter.rs`, but
+//! split into tasks on the Embassy executor instead of one superloop:
+//!
+//! - `straight_key` samples the key and classifies presses
+//! - `usb_input` reads typed lines, queues text and runs the shell
+//! - `typed_text` feeds queued text to the sidetone while the key is idle
+//! - `sidetone` plays each element on the speaker and LED
+//! - `link` writes protocol lines to the receiver over UART0
+//!
+//! Elements and link lines travel over channels, so no task waits on
+//! another's delays.
+
+#![no_std]
+#![no_main]
+
+#[link_section = ".boot2"]
+#[used]
+pub static BOOT2: [u8; 256] = rp2040_boot2::BOOT_LOADER_GENERIC_03H;
+
+use panic_halt as _;
+use core::cell::{Cell, RefCell};
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use rp2040_hal::{
+    gpio::{bank0::{Gpio0, Gpio1, Gpio16, Gpio21, Gpio25}, FunctionUart, Pin, FunctionSio, SioOutput, SioInput, PullUp, PullDown},
+    pac::{self, interrupt},
+    timer::Timer,
+    clocks::{Clock, init_clocks_and_plls},
+    watchdog::Watchdog,
+    Sio,
+    gpio::Pins,
+    uart::{UartPeripheral, UartConfig, DataBits, Parity, StopBits, Writer},
+};
+use rp2040_hal::entry;
+use rp2040_hal::fugit::RateExtU32;
+use rp_pico::XOSC_CRYSTAL_FREQ;
+use embassy_executor::{Executor, Spawner};
+use embassy_futures::yield_now;
+use embassy_sync::blocking_mutex::{raw::CriticalSectionRawMutex, Mutex};
+use embassy_sync::channel::Channel;
+use embassy_time::{Duration, Instant, Ticker, Timer as EmbassyTimer};
+use embedded_hal::digital::{InputPin, OutputPin};
+use embedded_hal_0_2::serial::Write as _;
+use heapless::String;
+use morse_rsdk::SYNC_PATTERN;
+use morse_rsdk::config::Config;
+use morse_rsdk::keyer::{parse_line, Command, Element, KeyQueue, LineEditor, Speed};
+use morse_rsdk::shell::Shell;
+use morse_rsdk::storage::{FlashConfigStore, Rp2040Flash};
+use morse_rsdk::usb::UsbBlockingWriter;
+
+/// Longest line accepted from the USB port
+const MAX_INPUT_LINE: usize = 64;
+/// Elements waiting to be keyed, roughly 100 characters of text
+const KEY_QUEUE_LENGTH: usize = 512;
+/// How often the key and the USB port are checked, as in the superloop
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// The link gaps, as Embassy durations. Embassy ticks are TIMER microseconds.
+const WORD_GAP: Duration = Duration::from_micros(morse_rsdk::WORD_GAP.to_micros());
+const INTER_CHAR_GAP: Duration = Duration::from_micros(morse_rsdk::INTER_CHAR_GAP.to_micros());
+
+type Button = Pin<Gpio16, FunctionSio<SioInput>, PullUp>;
+type Speaker = Pin<Gpio21, FunctionSio<SioOutput>, PullDown>;
+type Led = Pin<Gpio25, FunctionSio<SioOutput>, PullDown>;
+type Link = Writer<pac::UART0, (Pin<Gpio0, FunctionUart, PullDown>, Pin<Gpio1, FunctionUart, PullDown>)>;
+
+/// Dots and dashes to sound, and gaps to wait out, in order
+static ELEMENTS: Channel<CriticalSectionRawMutex, Element, 16> = Channel::new();
+/// Protocol lines for the receiver, see `send_link` in `transmitter.rs`
+static LINK: Channel<CriticalSectionRawMutex, &'static str, 16> = Channel::new();
+/// Typed text waiting for the straight key to go idle
+static TYPED: Mutex<CriticalSectionRawMutex, RefCell<KeyQueue<KEY_QUEUE_LENGTH>>> =
+    Mutex::new(RefCell::new(KeyQueue::new()));
+/// Live settings, owned by the shell in `usb_input` and read by the others
+static CONFIG: Mutex<CriticalSectionRawMutex, Cell<Config>> = Mutex::new(Cell::new(Config::new()));
+/// Set while the straight key is pressed or a press is being timed
+static KEY_BUSY: AtomicBool = AtomicBool::new(false);
+
+fn config() -> Config {
+    CONFIG.lock(|c| c.get())
+}
+
+#[embassy_executor::task]
+async fn straight_key(mut button: Button) {
+    let mut press_start = Instant::from_ticks(0);
+    let mut last_release = Instant::from_ticks(0);
+    let mut last_char_gap = Instant::from_ticks(0);
+    let mut button_was_pressed = false;
+    let mut in_word = false;
+    let mut ticker = Ticker::every(POLL_INTERVAL);
+
+    loop {
+        ticker.next().await;
+        let now = Instant::now();
+        let pressed = button.is_low().unwrap_or(false);
+        let config = config();
+        let debounce = Duration::from_millis(config.debounce_ms.into());
+
+        if pressed && !button_was_pressed {
+            if now - last_release > debounce {
+                press_start = now;
+                button_was_pressed = true;
+            }
+        } else if !pressed && button_was_pressed {
+            let held = now - press_start;
+            if held > debounce {
+                if held.as_millis() <= config.dot_threshold_ms.into() {
+                    ELEMENTS.send(Element::Dot).await;
+                } else if held.as_millis() <= config.dash_threshold_ms.into() {
+                    ELEMENTS.send(Element::Dash).await;
+                }
+                last_release = now;
+                in_word = true;
+            }
+            button_was_pressed = false;
+        } else if !pressed && in_word {
+            // Gaps between presses are only reported, never sounded
+            let gap = now - last_release;
+            if gap > WORD_GAP {
+                LINK.send("WORD GAP").await;
+                in_word = false;
+            } else if gap > INTER_CHAR_GAP && now - last_char_gap > INTER_CHAR_GAP {
+                LINK.send("CHAR GAP").await;
+                last_char_gap = now;
+            }
+        }
+
+        KEY_BUSY.store(pressed || button_was_pressed, Ordering::Relaxed);
+    }
+}
+
+#[embassy_executor::task]
+async fn usb_input(mut shell: Shell<FlashConfigStore<Rp2040Flash>>) {
+    let mut input = LineEditor::<MAX_INPUT_LINE>::new();
+    let mut ticker = Ticker::every(POLL_INTERVAL);
+    loop {
+        ticker.next().await;
+        morse_rsdk::usb::read_lines(&mut input, |line| handle_line(&mut shell, line));
+        CONFIG.lock(|c| c.set(*shell.config()));
+    }
+}
+
+fn handle_line(shell: &mut Shell<FlashConfigStore<Rp2040Flash>>, line: &str) {
+    let mut message = String::<96>::new();
+    match parse_line(line) {
+        Command::Send(text) => match TYPED.lock(|q| q.borrow_mut().push_text(text)) {
+            Ok(0) => { let _ = write!(message, "Queued: {}", text); }
+            Ok(skipped) => { let _ = write!(message, "Queued: {} ({} unsupported characters skipped)", text, skipped); }
+            Err(_) => { let _ = message.push_str("Queue full, line dropped"); }
+        },
+        Command::Wpm(wpm) => {
+            if let Some(speed) = Speed::from_wpm(wpm) {
+                shell.config_mut().unit_ms = speed.unit_ms();
+            }
+            let _ = write!(message, "Speed: {} WPM", shell.config().speed().wpm());
+        }
+        Command::Abort => {
+            TYPED.lock(|q| q.borrow_mut().clear());
+            let _ = message.push_str("Aborted");
+        }
+        Command::Help => {
+            let _ = message.push_str("Type text to send. /wpm N sets speed, /abort clears the queue,\r\n/show, /get, /set, /save and /reset change settings");
+        }
+        Command::Shell(command) => {
+            shell.execute(command, &mut UsbBlockingWriter);
+            return;
+        }
+        Command::Invalid(line) => { let _ = write!(message, "Bad argument: {}", line); }
+        Command::Empty => return,
+    }
+    morse_rsdk::usb::write_all(message.as_bytes());
+    morse_rsdk::usb::write_all(b"\r\n");
+}
+
+#[embassy_executor::task]
+async fn typed_text() {
+    let mut ticker = Ticker::every(POLL_INTERVAL);
+    loop {
+        let next = if KEY_BUSY.load(Ordering::Relaxed) {
+            None
+        } else {
+            TYPED.lock(|q| q.borrow_mut().pop())
+        };
+        match next {
+            Some(element) => ELEMENTS.send(element).await,
+            None => ticker.next().await,
+        }
+    }
+}
+
+#[embassy_executor::task]
+async fn sidetone(mut speaker: Speaker, mut led: Led) {
+    loop {
+        let element = ELEMENTS.receive().await;
+        let config = config();
+        let speed = config.speed();
+        match element {
+            Element::Dot | Element::Dash => {
+                let (freq, line) = match element {
+                    Element::Dot => (config.dot_freq, "."),
+                    _ => (config.dash_freq, "-"),
+                };
+                let _ = led.set_high();
+                tone(&mut speaker, freq, speed.on(element)).await;
+                let _ = led.set_low();
+                LINK.send(line).await;
+            }
+            Element::CharGap => LINK.send("CHAR GAP").await,
+            Element::WordGap => LINK.send("WORD GAP").await,
+        }
+        EmbassyTimer::after_micros(speed.off(element).to_micros()).await;
+    }
+}
+
+/// Square wave on the speaker. Each half period is an executor timer, so
+/// other tasks run between edges.
+async fn tone(speaker: &mut Speaker, freq_hz: u32, duration: morse_rsdk::Duration) {
+    let period_us = 1_000_000 / freq_hz;
+    let cycles = duration.to_micros() as u32 / period_us;
+    let half = Duration::from_micros((period_us / 2).into());
+    for _ in 0..cycles {
+        let _ = speaker.set_high();
+        EmbassyTimer::after(half).await;
+        let _ = speaker.set_low();
+        EmbassyTimer::after(half).await;
+    }
+}
+
+#[embassy_executor::task]
+async fn link(mut uart: Link) {
+    loop {
+        let line = LINK.receive().await;
+        for &byte in line.as_bytes().iter().chain(b"\r\n") {
+            // The FIFO drains in well under a millisecond
+            while uart.write(byte).is_err() {
+                yield_now().await;
+            }
+        }
+    }
+}
+
+/// Keys the sync pattern once at start-up, like `transmit_sync`
+async fn send_sync() {
+    for c in SYNC_PATTERN.chars() {
+        match c {
+            '.' => ELEMENTS.send(Element::Dot).await,
+            '-' => ELEMENTS.send(Element::Dash).await,
+            _ => {}
+        }
+    }
+}
+
+#[embassy_executor::task]
+async fn start(spawner: Spawner, button: Button) {
+    send_sync().await;
+    spawner.must_spawn(straight_key(button));
+    spawner.must_spawn(typed_text());
+}
+
+#[interrupt]
+fn TIMER_IRQ_0() {
+    morse_rsdk::time_driver::on_interrupt();
+}
+
+#[interrupt]
+fn USBCTRL_IRQ() {
+    morse_rsdk::usb::poll();
+}
+
+#[entry]
+fn main() -> ! {
+    let mut pac = pac::Peripherals::take().unwrap();
+
+    let mut watchdog = Watchdog::new(pac.WATCHDOG);
+    let clocks = init_clocks_and_plls(
+        XOSC_CRYSTAL_FREQ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    ).ok().unwrap();
+
+    let sio = Sio::new(pac.SIO);
+    let pins = Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+
+    let mut timer = Timer::new(pac.TIMER, &mut pac.RESETS, &clocks);
+    let alarm = timer.alarm_0().unwrap();
+    morse_rsdk::time_driver::init(timer, alarm);
+
+    // Saved settings from the end of flash, or the defaults
+    let shell = Shell::new(FlashConfigStore::new(Rp2040Flash));
+    CONFIG.lock(|c| c.set(*shell.config()));
+
+    let uart = UartPeripheral::new(
+        pac.UART0,
+        (pins.gpio0.into_function(), pins.gpio1.into_function()),
+        &mut pac.RESETS,
+    )
+    .enable(
+        UartConfig::new(shell.config().baud_rate.Hz(), DataBits::Eight, Some(Parity::Odd), StopBits::One),
+        clocks.peripheral_clock.freq(),
+    )
+    .unwrap();
+    let (_, uart_tx) = uart.split();
+
+    morse_rsdk::usb::init(
+        pac.USBCTRL_REGS,
+        pac.USBCTRL_DPRAM,
+        clocks.usb_clock,
+        &mut pac.RESETS,
+        "Morse Transmitter",
+    );
+
+    let button = pins.gpio16.into_pull_up_input();
+    let speaker = pins.gpio21.into_push_pull_output();
+    let led = pins.gpio25.into_push_pull_output();
+
+    let executor = cortex_m::singleton!(: Executor = Executor::new()).unwrap();
+    executor.run(|spawner| {
+        spawner.must_spawn(link(uart_tx));
+        spawner.must_spawn(sidetone(speaker, led));
+        spawner.must_spawn(usb_input(shell));
+        spawner.must_spawn(start(spawner, button));
+    })
+}
@@ -0,0 +1,304 @@
+//! # Runtime Configuration
+//!
+//! Settings that used to be compile-time constants, gathered in one typed
+//! [`Config`]. Defaults come from the constants in the crate root. Every
+//! setting is listed in [`FIELDS`] so the [`shell`](crate::shell) can get
+//! and set it by name.
+
+use heapless::Vec;
+
+use crate::keyer::Speed;
+
+/// All tunable settings
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Config {
+    /// UART0 link speed. Applied at start-up.
+    pub baud_rate: u32,
+    /// Sidetone for dots, in Hz
+    pub dot_freq: u32,
+    /// Sidetone for dashes, in Hz
+    pub dash_freq: u32,
+    /// Longest straight-key press read as a dot
+    pub dot_threshold_ms: u32,
+    /// Longest straight-key press read as a dash
+    pub dash_threshold_ms: u32,
+    /// Straight-key debounce time
+    pub debounce_ms: u32,
+    /// Length of one Morse unit when keying typed text
+    pub unit_ms: u32,
+    /// I2C address of the LCD backpack. Applied at start-up.
+    pub lcd_address: u8,
+}
+
+impl Config {
+    pub const fn new() -> Self {
+        Self {
+            baud_rate: crate::BAUD_RATE,
+            dot_freq: crate::DOT_FREQ,
+            dash_freq: crate::DASH_FREQ,
+            dot_threshold_ms: crate::DOT_THRESHOLD_MS,
+            dash_threshold_ms: crate::DASH_THRESHOLD_MS,
+            debounce_ms: crate::DEBOUNCE_TIME_MS as u32,
+            unit_ms: crate::DOT_DURATION.to_millis() as u32,
+            lcd_address: crate::LCD_ADDRESS,
+        }
+    }
+
+    /// Keying speed for typed text
+    pub fn speed(&self) -> Speed {
+        Speed::from_unit_ms(self.unit_ms)
+    }
+
+    /// Every field in [`FIELDS`] order as a little-endian `u32`
+    pub fn to_bytes(&self) -> Vec<u8, { FIELDS.len() * 4 }> {
+        let mut bytes = Vec::new();
+        for f in FIELDS {
+            // Cannot fail: sized for every field
+            let _ = bytes.extend_from_slice(&f.get(self).to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Inverse of [`to_bytes`](Self::to_bytes). Fields missing from the
+    /// end of `bytes` or out of range keep their defaults.
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let mut config = Self::new();
+        for (f, chunk) in FIELDS.iter().zip(bytes.chunks_exact(4)) {
+            let value = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+            let _ = f.set_value(&mut config, value);
+        }
+        config
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Why a value was rejected by [`Field::set`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    /// No field has this name
+    UnknownField,
+    /// Not a number (decimal, or hex with `0x`)
+    NotANumber,
+    /// Outside the field's range
+    OutOfRange { min: u32, max: u32 },
+}
+
+/// One named setting
+pub struct Field {
+    pub name: &'static str,
+    pub unit: &'static str,
+    pub min: u32,
+    pub max: u32,
+    /// Only takes effect after a restart
+    pub needs_restart: bool,
+    /// Shown in hex, e.g. I2C addresses
+    pub hex: bool,
+    get: fn(&Config) -> u32,
+    set: fn(&mut Config, u32),
+}
+
+impl Field {
+    pub fn get(&self, config: &Config) -> u32 {
+        (self.get)(config)
+    }
+
+    /// Parses and range-checks `value`, then stores it
+    pub fn set(&self, config: &mut Config, value: &str) -> Result<(), ConfigError> {
+        let value = parse_number(value).ok_or(ConfigError::NotANumber)?;
+        self.set_value(config, value)
+    }
+
+    /// Range-checks `value`, then stores it
+    pub fn set_value(&self, config: &mut Config, value: u32) -> Result<(), ConfigError> {
+        if !(self.min..=self.max).contains(&value) {
+            return Err(ConfigError::OutOfRange { min: self.min, max: self.max });
+        }
+        (self.set)(config, value);
+        Ok(())
+    }
+}
+
+/// Every setting, in the order `show` lists them
+pub const FIELDS: &[Field] = &[
+    Field {
+        name: "baud_rate",
+        unit: "baud",
+        min: 1200,
+        max: 921_600,
+        needs_restart: true,
+        hex: false,
+        get: |c| c.baud_rate,
+        set: |c, v| c.baud_rate = v,
+    },
+    Field {
+        name: "dot_freq",
+        unit: "Hz",
+        min: 100,
+        max: 5000,
+        needs_restart: false,
+        hex: false,
+        get: |c| c.dot_freq,
+        set: |c, v| c.dot_freq = v,
+    },
+    Field {
+        name: "dash_freq",
+        unit: "Hz",
+        min: 100,
+        max: 5000,
+        needs_restart: false,
+        hex: false,
+        get: |c| c.dash_freq,
+        set: |c, v| c.dash_freq = v,
+    },
+    Field {
+        name: "dot_threshold_ms",
+        unit: "ms",
+        min: 10,
+        max: 2000,
+        needs_restart: false,
+        hex: false,
+        get: |c| c.dot_threshold_ms,
+        set: |c, v| c.dot_threshold_ms = v,
+    },
+    Field {
+        name: "dash_threshold_ms",
+        unit: "ms",
+        min: 10,
+        max: 5000,
+        needs_restart: false,
+        hex: false,
+        get: |c| c.dash_threshold_ms,
+        set: |c, v| c.dash_threshold_ms = v,
+    },
+    Field {
+        name: "debounce_ms",
+        unit: "ms",
+        min: 0,
+        max: 500,
+        needs_restart: false,
+        hex: false,
+        get: |c| c.debounce_ms,
+        set: |c, v| c.debounce_ms = v,
+    },
+    Field {
+        name: "unit_ms",
+        unit: "ms",
+        min: 1200 / crate::keyer::MAX_WPM,
+        max: 1200 / crate::keyer::MIN_WPM,
+        needs_restart: false,
+        hex: false,
+        get: |c| c.unit_ms,
+        set: |c, v| c.unit_ms = v,
+    },
+    Field {
+        name: "lcd_address",
+        unit: "",
+        min: 0x08,
+        max: 0x77,
+        needs_restart: true,
+        hex: true,
+        get: |c| c.lcd_address as u32,
+        set: |c, v| c.lcd_address = v as u8,
+    },
+];
+
+/// Looks up a field by name
+pub fn field(name: &str) -> Result<&'static Field, ConfigError> {
+    FIELDS
+        .iter()
+        .find(|f| f.name == name)
+        .ok_or(ConfigError::UnknownField)
+}
+
+fn parse_number(text: &str) -> Option<u32> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+/// Where `save` writes the configuration
+pub trait ConfigStore {
+    type Error: core::fmt::Debug;
+
+    /// The saved configuration, if there is a valid one
+    fn load(&mut self) -> Option<Config>;
+
+    fn save(&mut self, config: &Config) -> Result<(), Self::Error>;
+}
+
+/// No persistent storage: nothing loads and `save` always fails
+pub struct NoStore;
+
+/// Returned by [`NoStore::save`]
+#[derive(Debug)]
+pub struct NoStorage;
+
+impl ConfigStore for NoStore {
+    type Error = NoStorage;
+
+    fn load(&mut self) -> Option<Config> {
+        None
+    }
+
+    fn save(&mut self, _config: &Config) -> Result<(), NoStorage> {
+        Err(NoStorage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_match_constants() {
+        let config = Config::default();
+        assert_eq!(config.baud_rate, crate::BAUD_RATE);
+        assert_eq!(config.lcd_address, crate::LCD_ADDRESS);
+        assert_eq!(config.speed(), Speed::default());
+    }
+
+    #[test]
+    fn every_default_is_in_range() {
+        let config = Config::default();
+        for f in FIELDS {
+            let value = f.get(&config);
+            assert!((f.min..=f.max).contains(&value), "{} = {}", f.name, value);
+        }
+    }
+
+    #[test]
+    fn set_parses_and_checks_range() {
+        let mut config = Config::default();
+        let lcd = field("lcd_address").unwrap();
+        assert_eq!(lcd.set(&mut config, "0x3f"), Ok(()));
+        assert_eq!(config.lcd_address, 0x3F);
+        assert_eq!(lcd.set(&mut config, "0x80"), Err(ConfigError::OutOfRange { min: 0x08, max: 0x77 }));
+        assert_eq!(lcd.set(&mut config, "addr"), Err(ConfigError::NotANumber));
+        assert_eq!(config.lcd_address, 0x3F);
+
+        assert_eq!(field("dot_freq").unwrap().set(&mut config, "700"), Ok(()));
+        assert_eq!(config.dot_freq, 700);
+        assert!(field("volume").is_err());
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let config = Config { dash_freq: 450, lcd_address: 0x3F, ..Config::default() };
+        assert_eq!(Config::from_bytes(&config.to_bytes()), config);
+    }
+
+    #[test]
+    fn short_or_bad_bytes_keep_defaults() {
+        let mut bytes = Config::default().to_bytes();
+        bytes[4..8].copy_from_slice(&1u32.to_le_bytes()); // dot_freq below range
+        bytes.truncate(12);
+        let config = Config::from_bytes(&bytes);
+        assert_eq!(config, Config::default());
+    }
+}
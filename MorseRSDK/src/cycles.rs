@@ -0,0 +1,148 @@
+//! # Cycle Counter
+//!
+//! The TIMER ticks once a microsecond, too coarse for a GPIO toggle or a
+//! PWM setup that take a few dozen cycles. [`CycleCounter`] runs SysTick
+//! from the system clock (8 ns a cycle at 125 MHz) and counts its wraps
+//! from the SysTick exception, giving a 64-bit count of cycles.
+//!
+//! SysTick counts down from `2^24 - 1`, so it wraps every 134 ms at
+//! 125 MHz. The binary must forward its SysTick exception to
+//! [`on_systick`]. A wrap whose exception is still pending, e.g. inside a
+//! critical section, is counted too, but only one: keep interrupts masked
+//! for less than a wrap.
+//!
+//! The counter owns SYST, so `cortex_m::delay::Delay` cannot run next to
+//! it; delay with the TIMER instead.
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use cortex_m::peripheral::{syst::SystClkSource, SCB, SYST};
+use rp2040_hal::fugit::HertzU32;
+
+/// SysTick counts from this down to 0
+const RELOAD: u32 = 0x00FF_FFFF;
+/// Cycles in one SysTick wrap
+const PERIOD: u64 = RELOAD as u64 + 1;
+
+/// SysTick wraps since [`CycleCounter::start`]. Only [`on_systick`]
+/// writes it, so a load and a store are enough on the M0+.
+static WRAPS: AtomicU32 = AtomicU32::new(0);
+
+/// Call from the binary's SysTick exception handler
+pub fn on_systick() {
+    WRAPS.store(WRAPS.load(Ordering::Relaxed).wrapping_add(1), Ordering::Release);
+}
+
+/// Cycles since the counter started. Safe from interrupt handlers, which
+/// cannot borrow the [`CycleCounter`]; meaningless before it starts.
+pub fn now() -> u64 {
+    loop {
+        let pending = SCB::is_pendst_pending();
+        let wraps = WRAPS.load(Ordering::Acquire);
+        let current = SYST::get_current();
+        // A wrap or its exception in between: the three reads disagree
+        if SCB::is_pendst_pending() == pending && WRAPS.load(Ordering::Acquire) == wraps {
+            return count(wraps, pending, current);
+        }
+    }
+}
+
+/// Cycles from `wraps` counted wraps, one more if its exception is
+/// pending, and the SysTick current value
+fn count(wraps: u32, pending: bool, current: u32) -> u64 {
+    (u64::from(wraps) + u64::from(pending)) * PERIOD + u64::from(RELOAD - (current & RELOAD))
+}
+
+/// A free-running cycle count on SysTick
+pub struct CycleCounter {
+    syst: SYST,
+    system_clock_hz: u32,
+    /// Cycles two back-to-back reads take, taken off every [`since`](Self::since)
+    overhead: u64,
+}
+
+impl CycleCounter {
+    /// Starts SysTick from the system clock with its exception enabled
+    pub fn start(mut syst: SYST, system_clock: HertzU32) -> Self {
+        syst.disable_counter();
+        syst.set_clock_source(SystClkSource::Core);
+        syst.set_reload(RELOAD);
+        syst.clear_current();
+        WRAPS.store(0, Ordering::Release);
+        syst.enable_interrupt();
+        syst.enable_counter();
+
+        let mut counter = Self { syst, system_clock_hz: system_clock.to_Hz(), overhead: 0 };
+        counter.overhead = (0..8)
+            .map(|_| {
+                let start = now();
+                now() - start
+            })
+            .min()
+            .unwrap_or(0);
+        counter
+    }
+
+    pub fn now(&self) -> u64 {
+        now()
+    }
+
+    /// Cycles from `start` to now, less the cost of reading the counter
+    pub fn since(&self, start: u64) -> u64 {
+        (now() - start).saturating_sub(self.overhead)
+    }
+
+    /// Cycles two back-to-back reads take, for spans whose end is read
+    /// elsewhere, e.g. in an interrupt handler
+    pub fn overhead(&self) -> u64 {
+        self.overhead
+    }
+
+    /// Whole microseconds in `cycles`
+    pub fn to_micros(&self, cycles: u64) -> u64 {
+        cycles * 1_000_000 / u64::from(self.system_clock_hz)
+    }
+
+    pub fn system_clock_hz(&self) -> u32 {
+        self.system_clock_hz
+    }
+
+    /// Cycles in a microsecond, 125 at the default system clock
+    pub fn cycles_per_micro(&self) -> u32 {
+        self.system_clock_hz / 1_000_000
+    }
+
+    /// Stops SysTick and gives it back
+    pub fn free(mut self) -> SYST {
+        self.syst.disable_interrupt();
+        self.syst.disable_counter();
+        self.syst
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_up_as_systick_counts_down() {
+        assert_eq!(count(0, false, RELOAD), 0);
+        assert_eq!(count(0, false, RELOAD - 125), 125);
+        assert_eq!(count(0, false, 0), RELOAD as u64);
+    }
+
+    #[test]
+    fn wraps_carry_into_the_high_bits() {
+        assert_eq!(count(1, false, RELOAD), PERIOD);
+        assert_eq!(count(3, false, RELOAD - 1), 3 * PERIOD + 1);
+        assert_eq!(count(u32::MAX, false, 0), u64::from(u32::MAX) * PERIOD + RELOAD as u64);
+    }
+
+    #[test]
+    fn pending_wrap_counts_before_its_exception_runs() {
+        // Just wrapped with interrupts masked: current is back near RELOAD
+        let before = count(4, false, 2);
+        let after = count(4, true, RELOAD - 3);
+        assert_eq!(after - before, 6);
+    }
+}
@@ -0,0 +1,323 @@
+//! # Morse Decoder
+//!
+//! The receiver's decoding state machine, shared by the blocking and async
+//! receivers. Feed it bytes from the link with [`Decoder::on_byte`] and call
+//! [`Decoder::poll`] regularly so characters and word gaps time out.
+//!
+//! Times are [`Instant`]s of the 1 MHz TIMER counter.
+
+use heapless::String;
+
+use crate::{Duration, Instant, MAX_MORSE_LENGTH, MORSE_CODE};
+
+/// Silence after a dot or dash that finishes the character. A little
+/// longer than the transmitter's [`INTER_CHAR_GAP`](crate::INTER_CHAR_GAP).
+pub const CHAR_TIMEOUT: Duration = Duration::millis(1000);
+/// Silence that finishes the word. A little longer than the
+/// transmitter's [`WORD_GAP`](crate::WORD_GAP).
+pub const WORD_TIMEOUT: Duration = Duration::millis(2000);
+
+/// What made the decoder finish a character or word
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Trigger {
+    /// A `C` or `W` gap marker from the transmitter
+    Marker,
+    /// The `H` of "CHAR GAP" or the `O` of "WORD GAP"
+    Text,
+    /// No signal arrived within the gap time
+    Timeout,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DecoderEvent {
+    /// A dot or dash was added to the current character.
+    /// `dot_interval` is the time since the previous dot when this dot
+    /// directly follows one in the same character.
+    Signal { signal: char, dot_interval: Option<Duration> },
+    /// A complete character was decoded
+    Letter { letter: char, code: String<MAX_MORSE_LENGTH>, trigger: Trigger },
+    /// The collected code is not a letter
+    Unknown { code: String<MAX_MORSE_LENGTH>, trigger: Trigger },
+    /// A word gap
+    Space { trigger: Trigger },
+}
+
+/// Sending speed from the time between two dots of one character.
+/// A dot plus the gap after it is two units, and PARIS timing gives
+/// WPM = 1200 / unit_ms.
+pub fn wpm_from_dot_interval(dot_interval: Duration) -> Option<u32> {
+    let unit_us = dot_interval.to_micros() / 2;
+    (unit_us > 0).then(|| (1_200_000 / unit_us) as u32)
+}
+
+/// Looks up a dot/dash code, e.g. `".-"` -> `'A'`
+pub fn decode_morse(morse: &str) -> Option<char> {
+    if morse.is_empty() {
+        return None;
+    }
+    MORSE_CODE
+        .iter()
+        .position(|code| *code == morse)
+        .map(|i| (b'A' + i as u8) as char)
+}
+
+pub struct Decoder {
+    code: String<MAX_MORSE_LENGTH>,
+    char_gap: Duration,
+    word_gap: Duration,
+    last_signal_time: Instant,
+    last_event_time: Instant,
+    last_symbol: u8,
+    in_character: bool,
+    in_word: bool,
+    space_added: bool,
+}
+
+impl Decoder {
+    pub const fn new(char_gap: Duration, word_gap: Duration) -> Self {
+        Self {
+            code: String::new(),
+            char_gap,
+            word_gap,
+            last_signal_time: Instant::from_ticks(0),
+            last_event_time: Instant::from_ticks(0),
+            last_symbol: 0,
+            in_character: false,
+            in_word: false,
+            space_added: false,
+        }
+    }
+
+    /// Handles one byte from the link
+    pub fn on_byte(&mut self, byte: u8, now: Instant) -> Option<DecoderEvent> {
+        match byte {
+            b'.' | b'-' => self.on_signal(byte, now),
+            b'C' | b'c' => {
+                let event = if self.in_character && !self.code.is_empty() {
+                    Some(self.finish_character(Trigger::Marker))
+                } else {
+                    None
+                };
+                self.last_event_time = now;
+                event
+            }
+            b'W' | b'w' => {
+                let event = if self.in_word && !self.space_added {
+                    Some(self.finish_word(Trigger::Marker))
+                } else {
+                    None
+                };
+                self.last_event_time = now;
+                event
+            }
+            b'H' if self.in_character => {
+                if self.code.is_empty() {
+                    None
+                } else {
+                    Some(self.finish_character(Trigger::Text))
+                }
+            }
+            b'O' if self.in_word => {
+                let event = if self.space_added {
+                    None
+                } else {
+                    Some(self.finish_word(Trigger::Text))
+                };
+                self.in_word = false;
+                event
+            }
+            _ => None,
+        }
+    }
+
+    /// Checks the character and word timeouts. Call until it returns `None`.
+    /// A `now` before the last byte never times out.
+    pub fn poll(&mut self, now: Instant) -> Option<DecoderEvent> {
+        if self.in_character && Self::expired(now, self.last_signal_time, self.char_gap) {
+            if self.code.is_empty() {
+                self.in_character = false;
+            } else {
+                return Some(self.finish_character(Trigger::Timeout));
+            }
+        }
+
+        if self.in_word && !self.space_added && Self::expired(now, self.last_event_time, self.word_gap) {
+            return Some(self.finish_word(Trigger::Timeout));
+        }
+
+        None
+    }
+
+    fn expired(now: Instant, since: Instant, gap: Duration) -> bool {
+        now.checked_duration_since(since).is_some_and(|elapsed| elapsed > gap)
+    }
+
+    /// True while dots and dashes are being collected
+    pub fn in_character(&self) -> bool {
+        self.in_character
+    }
+
+    fn on_signal(&mut self, signal: u8, now: Instant) -> Option<DecoderEvent> {
+        let dot_interval = if signal == b'.' && self.last_symbol == b'.' && self.in_character {
+            now.checked_duration_since(self.last_signal_time)
+        } else {
+            None
+        };
+        self.last_symbol = signal;
+
+        if !self.in_character {
+            self.code.clear();
+            self.in_character = true;
+            self.space_added = false;
+        }
+
+        let event = if self.code.len() < MAX_MORSE_LENGTH - 1 {
+            let _ = self.code.push(signal as char);
+            Some(DecoderEvent::Signal { signal: signal as char, dot_interval })
+        } else {
+            None
+        };
+
+        self.last_signal_time = now;
+        self.last_event_time = now;
+        self.in_word = true;
+        event
+    }
+
+    fn finish_character(&mut self, trigger: Trigger) -> DecoderEvent {
+        let code = core::mem::take(&mut self.code);
+        self.in_character = false;
+        match decode_morse(code.as_str()) {
+            Some(letter) => DecoderEvent::Letter { letter, code, trigger },
+            None => DecoderEvent::Unknown { code, trigger },
+        }
+    }
+
+    fn finish_word(&mut self, trigger: Trigger) -> DecoderEvent {
+        self.in_word = false;
+        self.space_added = true;
+        DecoderEvent::Space { trigger }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    fn ms(ms: u64) -> Instant {
+        Instant::from_ticks(0) + Duration::millis(ms)
+    }
+
+    fn receiver_decoder() -> Decoder {
+        Decoder::new(CHAR_TIMEOUT, WORD_TIMEOUT)
+    }
+
+    fn feed(decoder: &mut Decoder, bytes: &[u8], now: Instant) -> Vec<DecoderEvent> {
+        bytes.iter().filter_map(|&b| decoder.on_byte(b, now)).collect()
+    }
+
+    fn letter(letter: char, code: &str, trigger: Trigger) -> DecoderEvent {
+        DecoderEvent::Letter { letter, code: code.try_into().unwrap(), trigger }
+    }
+
+    #[test]
+    fn decodes_alphabet() {
+        assert_eq!(decode_morse(".-"), Some('A'));
+        assert_eq!(decode_morse("--.."), Some('Z'));
+        assert_eq!(decode_morse(""), None);
+        assert_eq!(decode_morse("......."), None);
+    }
+
+    #[test]
+    fn marker_finishes_character_and_word() {
+        let mut decoder = receiver_decoder();
+        let events = feed(&mut decoder, b"...C---CW", ms(0));
+        assert_eq!(events.len(), 9);
+        assert_eq!(events[3], letter('S', "...", Trigger::Marker));
+        assert_eq!(events[7], letter('O', "---", Trigger::Marker));
+        assert_eq!(events[8], DecoderEvent::Space { trigger: Trigger::Marker });
+    }
+
+    #[test]
+    fn unknown_code_is_reported() {
+        let mut decoder = receiver_decoder();
+        let events = feed(&mut decoder, b"......C", ms(0));
+        assert_eq!(
+            events.last(),
+            Some(&DecoderEvent::Unknown { code: "......".try_into().unwrap(), trigger: Trigger::Marker })
+        );
+    }
+
+    #[test]
+    fn timeouts_finish_character_then_word() {
+        let mut decoder = receiver_decoder();
+        let start = ms(100);
+        feed(&mut decoder, b".-", start);
+        assert_eq!(decoder.poll(start + CHAR_TIMEOUT), None);
+        assert_eq!(
+            decoder.poll(start + CHAR_TIMEOUT + Duration::micros(1)),
+            Some(letter('A', ".-", Trigger::Timeout))
+        );
+        assert_eq!(decoder.poll(start + WORD_TIMEOUT), None);
+        assert_eq!(
+            decoder.poll(start + WORD_TIMEOUT + Duration::micros(1)),
+            Some(DecoderEvent::Space { trigger: Trigger::Timeout })
+        );
+        assert_eq!(decoder.poll(ms(9999)), None);
+    }
+
+    /// The gaps were once compared as milliseconds against microsecond
+    /// ticks, which finished every character 1 ms after its last signal
+    #[test]
+    fn timeouts_are_seconds_not_milliseconds() {
+        assert_eq!(CHAR_TIMEOUT.to_millis(), 1000);
+        assert_eq!(WORD_TIMEOUT.to_millis(), 2000);
+
+        let mut decoder = receiver_decoder();
+        feed(&mut decoder, b".", ms(0));
+        // Well past the old 1 ms and 2 ms, well inside a slow character
+        assert_eq!(decoder.poll(ms(5)), None);
+        assert_eq!(decoder.poll(ms(500)), None);
+        feed(&mut decoder, b"-", ms(900));
+        assert_eq!(decoder.poll(ms(1800)), None);
+        assert_eq!(decoder.poll(ms(1901)), Some(letter('A', ".-", Trigger::Timeout)));
+    }
+
+    #[test]
+    fn time_before_the_last_byte_never_times_out() {
+        let mut decoder = receiver_decoder();
+        feed(&mut decoder, b"...", ms(5000));
+        assert_eq!(decoder.poll(ms(0)), None);
+        assert_eq!(decoder.poll(ms(4999)), None);
+        assert!(decoder.in_character());
+    }
+
+    #[test]
+    fn dot_interval_only_between_dots() {
+        let mut decoder = receiver_decoder();
+        let intervals: Vec<Option<Duration>> = [(b'.', 0), (b'.', 500), (b'-', 1000), (b'.', 2000)]
+            .iter()
+            .filter_map(|&(b, t)| decoder.on_byte(b, ms(t)))
+            .map(|e| match e {
+                DecoderEvent::Signal { dot_interval, .. } => dot_interval,
+                _ => panic!("unexpected {:?}", e),
+            })
+            .collect();
+        assert_eq!(intervals, [None, Some(Duration::millis(500)), None, None]);
+    }
+
+    #[test]
+    fn wpm_follows_paris_timing() {
+        assert_eq!(wpm_from_dot_interval(Duration::millis(120)), Some(20));
+        assert_eq!(wpm_from_dot_interval(Duration::millis(500)), Some(4));
+        assert_eq!(wpm_from_dot_interval(Duration::micros(1)), None);
+    }
+
+    #[test]
+    fn overlong_code_is_capped() {
+        let mut decoder = receiver_decoder();
+        let events = feed(&mut decoder, &[b'.'; MAX_MORSE_LENGTH + 4], ms(0));
+        assert_eq!(events.len(), MAX_MORSE_LENGTH - 1);
+    }
+}
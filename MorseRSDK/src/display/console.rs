@@ -0,0 +1,117 @@
+//! # Text Console Backends
+//!
+//! Line-oriented displays for a serial port, the USB CDC port and RTT. All
+//! of them print the receiver's diagnostic log as well as decoded
+//! characters and status.
+
+use core::fmt::Write;
+use embedded_hal_0_2::serial;
+use heapless::String;
+use nb::block;
+
+use super::Display;
+
+/// Formats the line a console prints for a decoded character
+fn char_line(c: char) -> String<32> {
+    let mut line = String::new();
+    if c == ' ' {
+        let _ = line.push_str("Detected: SPACE");
+    } else {
+        let _ = write!(line, "Decoded: {}", c);
+    }
+    line
+}
+
+/// Display on a serial port, one CRLF-terminated line per event
+pub struct SerialConsole<W> {
+    uart: W,
+}
+
+impl<W: serial::Write<u8>> SerialConsole<W> {
+    pub fn new(uart: W) -> Self {
+        Self { uart }
+    }
+
+    pub fn release(self) -> W {
+        self.uart
+    }
+
+    fn write_line(&mut self, text: &str) {
+        for byte in text.as_bytes() {
+            let _ = block!(self.uart.write(*byte));
+        }
+        let _ = block!(self.uart.write(b'\r'));
+        let _ = block!(self.uart.write(b'\n'));
+    }
+}
+
+impl<W: serial::Write<u8>> Display for SerialConsole<W> {
+    fn show_char(&mut self, c: char) {
+        self.write_line(char_line(c).as_str());
+    }
+
+    fn show_status(&mut self, status: &str) {
+        let mut line = String::<64>::new();
+        let _ = write!(line, "Status: {}", status);
+        self.write_line(line.as_str());
+    }
+
+    fn clear(&mut self) {
+        self.write_line("");
+    }
+
+    fn log(&mut self, message: &str) {
+        self.write_line(message);
+    }
+}
+
+/// Display on the RTT up-channel. `rtt_init_print!` must have been called.
+pub struct RttConsole;
+
+impl Display for RttConsole {
+    fn show_char(&mut self, c: char) {
+        rtt_target::rprintln!("{}", char_line(c));
+    }
+
+    fn show_status(&mut self, status: &str) {
+        rtt_target::rprintln!("Status: {}", status);
+    }
+
+    fn clear(&mut self) {
+        rtt_target::rprintln!();
+    }
+
+    fn log(&mut self, message: &str) {
+        rtt_target::rprintln!("{}", message);
+    }
+}
+
+/// Display on the USB CDC-ACM port. [`crate::usb::init`] must have been called.
+pub struct UsbConsole;
+
+impl UsbConsole {
+    fn write_line(&mut self, text: &str) {
+        crate::usb::write(text.as_bytes());
+        crate::usb::write(b"\r\n");
+    }
+}
+
+impl Display for UsbConsole {
+    fn show_char(&mut self, c: char) {
+        self.write_line(char_line(c).as_str());
+    }
+
+    fn show_status(&mut self, status: &str) {
+        let mut line = String::<64>::new();
+        let _ = write!(line, "Status: {}", status);
+        self.write_line(line.as_str());
+    }
+
+    fn clear(&mut self) {
+        self.write_line("");
+    }
+
+    fn log(&mut self, message: &str) {
+        self.write_line(message);
+    }
+}
@@ -0,0 +1,71 @@
+//! # 5x7 Bitmap Font
+//!
+//! Column-major glyphs (bit 0 = top row) for the characters the receiver
+//! can show: space, A-Z, 0-9 and a little punctuation.
+
+/// Width of a glyph in pixels
+pub const GLYPH_WIDTH: usize = 5;
+/// Horizontal advance including the blank spacing column
+pub const GLYPH_ADVANCE: usize = GLYPH_WIDTH + 1;
+/// Height of a glyph in pixels
+pub const GLYPH_HEIGHT: usize = 7;
+
+const LETTERS: [[u8; GLYPH_WIDTH]; 26] = [
+    [0x7E, 0x11, 0x11, 0x11, 0x7E], // A
+    [0x7F, 0x49, 0x49, 0x49, 0x36], // B
+    [0x3E, 0x41, 0x41, 0x41, 0x22], // C
+    [0x7F, 0x41, 0x41, 0x22, 0x1C], // D
+    [0x7F, 0x49, 0x49, 0x49, 0x41], // E
+    [0x7F, 0x09, 0x09, 0x09, 0x01], // F
+    [0x3E, 0x41, 0x49, 0x49, 0x7A], // G
+    [0x7F, 0x08, 0x08, 0x08, 0x7F], // H
+    [0x00, 0x41, 0x7F, 0x41, 0x00], // I
+    [0x20, 0x40, 0x41, 0x3F, 0x01], // J
+    [0x7F, 0x08, 0x14, 0x22, 0x41], // K
+    [0x7F, 0x40, 0x40, 0x40, 0x40], // L
+    [0x7F, 0x02, 0x0C, 0x02, 0x7F], // M
+    [0x7F, 0x04, 0x08, 0x10, 0x7F], // N
+    [0x3E, 0x41, 0x41, 0x41, 0x3E], // O
+    [0x7F, 0x09, 0x09, 0x09, 0x06], // P
+    [0x3E, 0x41, 0x51, 0x21, 0x5E], // Q
+    [0x7F, 0x09, 0x19, 0x29, 0x46], // R
+    [0x46, 0x49, 0x49, 0x49, 0x31], // S
+    [0x01, 0x01, 0x7F, 0x01, 0x01], // T
+    [0x3F, 0x40, 0x40, 0x40, 0x3F], // U
+    [0x1F, 0x20, 0x40, 0x20, 0x1F], // V
+    [0x3F, 0x40, 0x38, 0x40, 0x3F], // W
+    [0x63, 0x14, 0x08, 0x14, 0x63], // X
+    [0x07, 0x08, 0x70, 0x08, 0x07], // Y
+    [0x61, 0x51, 0x49, 0x45, 0x43], // Z
+];
+
+const DIGITS: [[u8; GLYPH_WIDTH]; 10] = [
+    [0x3E, 0x51, 0x49, 0x45, 0x3E], // 0
+    [0x00, 0x42, 0x7F, 0x40, 0x00], // 1
+    [0x42, 0x61, 0x51, 0x49, 0x46], // 2
+    [0x21, 0x41, 0x45, 0x4B, 0x31], // 3
+    [0x18, 0x14, 0x12, 0x7F, 0x10], // 4
+    [0x27, 0x45, 0x45, 0x45, 0x39], // 5
+    [0x3C, 0x4A, 0x49, 0x49, 0x30], // 6
+    [0x01, 0x71, 0x09, 0x05, 0x03], // 7
+    [0x36, 0x49, 0x49, 0x49, 0x36], // 8
+    [0x06, 0x49, 0x49, 0x29, 0x1E], // 9
+];
+
+const UNKNOWN: [u8; GLYPH_WIDTH] = [0x02, 0x01, 0x51, 0x09, 0x06]; // ?
+
+/// Returns the columns for `c`, left to right. Lower case maps to upper
+/// case and characters without a glyph are drawn as `?`.
+pub fn glyph(c: char) -> [u8; GLYPH_WIDTH] {
+    match c {
+        'A'..='Z' => LETTERS[(c as u8 - b'A') as usize],
+        'a'..='z' => LETTERS[(c as u8 - b'a') as usize],
+        '0'..='9' => DIGITS[(c as u8 - b'0') as usize],
+        ' ' => [0x00; GLYPH_WIDTH],
+        '.' => [0x00, 0x60, 0x60, 0x00, 0x00],
+        '-' => [0x08, 0x08, 0x08, 0x08, 0x08],
+        ':' => [0x00, 0x36, 0x36, 0x00, 0x00],
+        '/' => [0x20, 0x10, 0x08, 0x04, 0x02],
+        _ => UNKNOWN,
+    }
+}
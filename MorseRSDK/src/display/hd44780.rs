@@ -0,0 +1,125 @@
+//! # HD44780 over PCF8574
+//!
+//! What [`Lcd1602`](super::Lcd1602) and
+//! [`AsyncLcd1602`](super::AsyncLcd1602) send, as [`Step`]s: every byte
+//! for the expander and every wait, in order. Each driver only runs the
+//! steps on its own bus, so the two cannot drift apart. 4-bit mode; same
+//! init sequence and timings as `lcd_init` in the C receiver.
+
+use super::lcd::LCD_TITLE;
+use crate::{
+    LCD_2LINE, LCD_4BITMODE, LCD_5X8_DOTS, LCD_BACKLIGHT, LCD_BLINKOFF, LCD_CLEARDISPLAY,
+    LCD_CURSOROFF, LCD_DISPLAYCONTROL, LCD_DISPLAYON, LCD_EN_BIT, LCD_ENTRYLEFT,
+    LCD_ENTRYMODESET, LCD_ENTRYSHIFTDECREMENT, LCD_FUNCTIONSET, LCD_RETURNHOME, LCD_RS_BIT,
+    LCD_SETDDRAMADDR,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum Step {
+    /// Writes a byte to the expander; if it is not acknowledged the
+    /// expander is missing and the sequence stops with the error
+    Probe(u8),
+    /// Writes a byte to the expander, ignoring errors
+    Write(u8),
+    DelayUs(u32),
+}
+
+/// Latches the top four bits of `data` with an enable pulse
+fn pulse(data: u8) -> [Step; 4] {
+    [
+        Step::Write(data | LCD_EN_BIT | LCD_BACKLIGHT),
+        Step::DelayUs(500),
+        Step::Write((data & !LCD_EN_BIT) | LCD_BACKLIGHT),
+        Step::DelayUs(100),
+    ]
+}
+
+/// A byte as two nibbles, high first. `rs` selects data rather than a
+/// command.
+fn byte(value: u8, rs: u8, settle_us: u32) -> impl Iterator<Item = Step> {
+    pulse((value & 0xF0) | rs | LCD_BACKLIGHT)
+        .into_iter()
+        .chain(pulse(((value << 4) & 0xF0) | rs | LCD_BACKLIGHT))
+        .chain([Step::DelayUs(settle_us)])
+}
+
+fn command(command: u8) -> impl Iterator<Item = Step> {
+    byte(command, 0, 2000)
+}
+
+fn print(text: &str) -> impl Iterator<Item = Step> + '_ {
+    text.chars().flat_map(|c| byte(c as u8, LCD_RS_BIT, 100))
+}
+
+fn set_cursor(col: u8, row: u8) -> impl Iterator<Item = Step> {
+    let row_offsets = [0x00, 0x40, 0x14, 0x54];
+    command(LCD_SETDDRAMADDR | (col + row_offsets[row as usize % 4]))
+}
+
+/// Switches the controller to 4-bit mode, sets it up and prints "LCD Ready"
+pub(super) fn init() -> impl Iterator<Item = Step> {
+    let wake = |value: u8| {
+        pulse((value & 0xF0) | LCD_BACKLIGHT)
+            .into_iter()
+            .chain([Step::DelayUs(5000)])
+    };
+    [Step::DelayUs(50_000), Step::Probe(LCD_BACKLIGHT), Step::DelayUs(50_000)]
+        .into_iter()
+        .chain(wake(0x30))
+        .chain(wake(0x30))
+        .chain(wake(0x30))
+        .chain(wake(0x20))
+        .chain(command(LCD_FUNCTIONSET | LCD_4BITMODE | LCD_2LINE | LCD_5X8_DOTS))
+        .chain(command(LCD_DISPLAYCONTROL | LCD_DISPLAYON | LCD_CURSOROFF | LCD_BLINKOFF))
+        .chain(clear())
+        .chain(command(LCD_ENTRYMODESET | LCD_ENTRYLEFT | LCD_ENTRYSHIFTDECREMENT))
+        .chain(command(LCD_RETURNHOME))
+        .chain([Step::DelayUs(5000)])
+        .chain(print("LCD Ready"))
+}
+
+pub(super) fn clear() -> impl Iterator<Item = Step> {
+    command(LCD_CLEARDISPLAY).chain([Step::DelayUs(5000)])
+}
+
+/// Both rows: the title on row 0 and `line` on row 1
+pub(super) fn redraw(line: &str) -> impl Iterator<Item = Step> + '_ {
+    clear()
+        .chain(set_cursor(0, 0))
+        .chain(print(LCD_TITLE))
+        .chain(set_cursor(0, 1))
+        .chain(print(line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    #[test]
+    fn data_byte_is_two_enable_pulses_with_rs() {
+        let steps: Vec<Step> = print("A").collect();
+        let high = LCD_RS_BIT | LCD_BACKLIGHT | 0x40;
+        let low = LCD_RS_BIT | LCD_BACKLIGHT | 0x10;
+        assert_eq!(steps, [
+            Step::Write(high | LCD_EN_BIT),
+            Step::DelayUs(500),
+            Step::Write(high),
+            Step::DelayUs(100),
+            Step::Write(low | LCD_EN_BIT),
+            Step::DelayUs(500),
+            Step::Write(low),
+            Step::DelayUs(100),
+            Step::DelayUs(100),
+        ]);
+    }
+
+    #[test]
+    fn init_probes_the_expander_once_before_anything_else() {
+        let steps: Vec<Step> = init().collect();
+        let probes: Vec<usize> = (0..steps.len())
+            .filter(|&i| matches!(steps[i], Step::Probe(_)))
+            .collect();
+        assert_eq!(probes, [1]);
+    }
+}
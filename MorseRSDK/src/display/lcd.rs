@@ -0,0 +1,157 @@
+//! # I2C LCD1602 Backend
+//!
+//! HD44780 16x2 character LCD behind a PCF8574 I2C expander, driven in
+//! 4-bit mode. The bytes and timings come from `hd44780`.
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal_0_2::blocking::i2c;
+use heapless::String;
+
+use super::hd44780::{self, Step};
+use super::Display;
+use crate::message::MessageRing;
+use crate::LCD_CHAR_WIDTH;
+
+/// Text shown on the top row
+pub const LCD_TITLE: &str = "Morse Receiver";
+
+/// LCD1602 on a PCF8574 backpack.
+///
+/// Row 0 holds a fixed title. Row 1 shows the latest status until the
+/// next decoded character replaces it with the tail of the message.
+pub struct Lcd1602<I2C, D> {
+    i2c: I2C,
+    delay: D,
+    address: u8,
+    message: MessageRing<LCD_CHAR_WIDTH>,
+}
+
+impl<I2C, D> Lcd1602<I2C, D>
+where
+    I2C: i2c::Write,
+    D: DelayNs,
+{
+    pub fn new(i2c: I2C, delay: D, address: u8) -> Self {
+        Self {
+            i2c,
+            delay,
+            address,
+            message: MessageRing::new(),
+        }
+    }
+
+    /// Runs the HD44780 4-bit init sequence.
+    /// Fails if the backpack does not acknowledge its address.
+    pub fn init(&mut self) -> Result<(), I2C::Error> {
+        self.run(hd44780::init())
+    }
+
+    pub fn release(self) -> (I2C, D) {
+        (self.i2c, self.delay)
+    }
+
+    fn run(&mut self, steps: impl Iterator<Item = Step>) -> Result<(), I2C::Error> {
+        for step in steps {
+            match step {
+                Step::Probe(byte) => self.i2c.write(self.address, &[byte])?,
+                Step::Write(byte) => {
+                    let _ = self.i2c.write(self.address, &[byte]);
+                }
+                Step::DelayUs(us) => self.delay.delay_us(us),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<I2C, D> Display for Lcd1602<I2C, D>
+where
+    I2C: i2c::Write,
+    D: DelayNs,
+{
+    fn show_char(&mut self, c: char) {
+        self.message.push(c);
+        let line: String<LCD_CHAR_WIDTH> = self.message.tail();
+        let _ = self.run(hd44780::redraw(line.as_str()));
+    }
+
+    fn show_status(&mut self, status: &str) {
+        let end = status.len().min(LCD_CHAR_WIDTH);
+        let _ = self.run(hd44780::redraw(status.get(..end).unwrap_or("")));
+    }
+
+    fn clear(&mut self) {
+        self.message.clear();
+        let _ = self.run(hd44780::clear());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LCD_EN_BIT, LCD_RS_BIT};
+    use std::vec::Vec;
+
+    struct NoDelay;
+
+    impl DelayNs for NoDelay {
+        fn delay_ns(&mut self, _ns: u32) {}
+    }
+
+    /// Records every byte written to the expander, or NACKs everything
+    struct MockBus {
+        present: bool,
+        writes: Vec<u8>,
+    }
+
+    impl i2c::Write for MockBus {
+        type Error = ();
+
+        fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), ()> {
+            if !self.present || address != crate::LCD_ADDRESS {
+                return Err(());
+            }
+            self.writes.extend_from_slice(bytes);
+            Ok(())
+        }
+    }
+
+    fn lcd(present: bool) -> Lcd1602<MockBus, NoDelay> {
+        let bus = MockBus { present, writes: Vec::new() };
+        Lcd1602::new(bus, NoDelay, crate::LCD_ADDRESS)
+    }
+
+    /// Rebuilds the data bytes (RS set) from enable-high nibble writes
+    fn data_bytes(writes: &[u8]) -> Vec<u8> {
+        let nibbles: Vec<u8> = writes
+            .iter()
+            .filter(|&&b| b & LCD_EN_BIT != 0 && b & LCD_RS_BIT != 0)
+            .map(|&b| b & 0xF0)
+            .collect();
+        nibbles.chunks(2).map(|n| n[0] | (n[1] >> 4)).collect()
+    }
+
+    #[test]
+    fn init_fails_without_backpack() {
+        assert!(lcd(false).init().is_err());
+    }
+
+    #[test]
+    fn show_char_redraws_title_and_message() {
+        let mut lcd = lcd(true);
+        lcd.init().unwrap();
+        lcd.i2c.writes.clear();
+        lcd.show_char('S');
+        lcd.show_char('O');
+        lcd.i2c.writes.clear();
+        lcd.show_char('S');
+        assert_eq!(data_bytes(&lcd.i2c.writes), b"Morse ReceiverSOS");
+    }
+
+    #[test]
+    fn long_status_is_truncated() {
+        let mut lcd = lcd(true);
+        lcd.show_status("A status line longer than the LCD");
+        assert_eq!(data_bytes(&lcd.i2c.writes), b"Morse ReceiverA status line lo");
+    }
+}
@@ -0,0 +1,168 @@
+//! # Async I2C LCD1602 Backend
+//!
+//! The same HD44780/PCF8574 driver as [`Lcd1602`](super::Lcd1602), written
+//! against `embedded-hal-async`; both send the steps from `hd44780`. Every
+//! bus write and enable pulse is awaited, so other tasks (UART reception in
+//! `receiver_async`) keep running while the LCD is being redrawn.
+
+use embedded_hal_async::delay::DelayNs;
+use embedded_hal_async::i2c::I2c;
+use heapless::String;
+
+use super::hd44780::{self, Step};
+use crate::message::MessageRing;
+use crate::LCD_CHAR_WIDTH;
+
+/// LCD1602 on a PCF8574 backpack, driven asynchronously.
+///
+/// Shows the same layout as the blocking driver: a fixed title on row 0
+/// and the status or message tail on row 1.
+pub struct AsyncLcd1602<I2C, D> {
+    i2c: I2C,
+    delay: D,
+    address: u8,
+    message: MessageRing<LCD_CHAR_WIDTH>,
+}
+
+impl<I2C, D> AsyncLcd1602<I2C, D>
+where
+    I2C: I2c,
+    D: DelayNs,
+{
+    pub fn new(i2c: I2C, delay: D, address: u8) -> Self {
+        Self {
+            i2c,
+            delay,
+            address,
+            message: MessageRing::new(),
+        }
+    }
+
+    /// Runs the HD44780 4-bit init sequence.
+    /// Fails if the backpack does not acknowledge its address.
+    pub async fn init(&mut self) -> Result<(), I2C::Error> {
+        self.run(hd44780::init()).await
+    }
+
+    /// Shows one decoded character. A space marks a word gap.
+    pub async fn show_char(&mut self, c: char) {
+        self.message.push(c);
+        let line: String<LCD_CHAR_WIDTH> = self.message.tail();
+        let _ = self.run(hd44780::redraw(line.as_str())).await;
+    }
+
+    /// Shows a status message on row 1, truncated to the LCD width
+    pub async fn show_status(&mut self, status: &str) {
+        let end = status.len().min(LCD_CHAR_WIDTH);
+        let _ = self.run(hd44780::redraw(status.get(..end).unwrap_or(""))).await;
+    }
+
+    pub async fn clear(&mut self) {
+        self.message.clear();
+        let _ = self.run(hd44780::clear()).await;
+    }
+
+    pub fn release(self) -> (I2C, D) {
+        (self.i2c, self.delay)
+    }
+
+    async fn run(&mut self, steps: impl Iterator<Item = Step>) -> Result<(), I2C::Error> {
+        for step in steps {
+            match step {
+                Step::Probe(byte) => self.i2c.write(self.address, &[byte]).await?,
+                Step::Write(byte) => {
+                    let _ = self.i2c.write(self.address, &[byte]).await;
+                }
+                Step::DelayUs(us) => self.delay.delay_us(us).await,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embassy_futures::block_on;
+    use embedded_hal_async::i2c::{ErrorKind, ErrorType, Operation, SevenBitAddress};
+    use std::vec::Vec;
+
+    struct NoDelay;
+
+    impl DelayNs for NoDelay {
+        async fn delay_ns(&mut self, _ns: u32) {}
+    }
+
+    /// Records every byte written to the expander, or NACKs everything
+    struct MockBus {
+        present: bool,
+        writes: Vec<u8>,
+    }
+
+    impl ErrorType for MockBus {
+        type Error = ErrorKind;
+    }
+
+    impl I2c<SevenBitAddress> for MockBus {
+        async fn transaction(
+            &mut self,
+            address: u8,
+            operations: &mut [Operation<'_>],
+        ) -> Result<(), ErrorKind> {
+            if !self.present || address != crate::LCD_ADDRESS {
+                return Err(ErrorKind::Other);
+            }
+            for operation in operations {
+                if let Operation::Write(bytes) = operation {
+                    self.writes.extend_from_slice(bytes);
+                }
+            }
+            Ok(())
+        }
+    }
+
+    fn lcd(present: bool) -> AsyncLcd1602<MockBus, NoDelay> {
+        let bus = MockBus { present, writes: Vec::new() };
+        AsyncLcd1602::new(bus, NoDelay, crate::LCD_ADDRESS)
+    }
+
+    #[test]
+    fn init_fails_without_backpack() {
+        assert!(block_on(lcd(false).init()).is_err());
+    }
+
+    #[test]
+    fn matches_blocking_driver() {
+        use crate::display::{Display, Lcd1602};
+        use embedded_hal_0_2::blocking::i2c;
+
+        struct Recorder(Vec<u8>);
+        impl i2c::Write for Recorder {
+            type Error = ();
+            fn write(&mut self, _address: u8, bytes: &[u8]) -> Result<(), ()> {
+                self.0.extend_from_slice(bytes);
+                Ok(())
+            }
+        }
+        struct BlockingNoDelay;
+        impl embedded_hal::delay::DelayNs for BlockingNoDelay {
+            fn delay_ns(&mut self, _ns: u32) {}
+        }
+
+        let mut blocking = Lcd1602::new(Recorder(Vec::new()), BlockingNoDelay, crate::LCD_ADDRESS);
+        blocking.init().unwrap();
+        blocking.show_status("Waiting...");
+        blocking.show_char('S');
+        blocking.clear();
+
+        let mut lcd = lcd(true);
+        block_on(async {
+            lcd.init().await.unwrap();
+            lcd.show_status("Waiting...").await;
+            lcd.show_char('S').await;
+            lcd.clear().await;
+        });
+
+        assert_eq!(lcd.release().0.writes, blocking.release().0 .0);
+    }
+}
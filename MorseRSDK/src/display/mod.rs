@@ -0,0 +1,276 @@
+//! # Receiver Display Backends
+//!
+//! Output devices the receiver can show decoded text on, behind a common
+//! [`Display`] trait. Backends are combined with [`Display::and`], so the
+//! receiver can drive any mix of LCD, OLED, seven-segment, serial, USB and RTT
+//! output.
+
+pub mod console;
+pub mod font;
+mod hd44780;
+pub mod lcd;
+pub mod lcd_async;
+pub mod oled;
+pub mod seven_segment;
+
+pub use console::{RttConsole, SerialConsole, UsbConsole};
+pub use lcd::Lcd1602;
+pub use lcd_async::AsyncLcd1602;
+pub use oled::{Framebuffer, Ssd1306};
+pub use seven_segment::{GpioSegments, SegmentDriver, SevenSegment, ShiftRegisterSegments};
+
+/// Link health shown by displays that have room for it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LinkStats {
+    /// Estimated sending speed in words per minute
+    pub wpm: u32,
+    /// UART errors plus codes that failed to decode
+    pub errors: u32,
+}
+
+/// Something decoded Morse can be shown on
+pub trait Display {
+    /// Shows one decoded character. A space marks a word gap.
+    fn show_char(&mut self, c: char);
+
+    /// Shows a short status message such as "Waiting..."
+    fn show_status(&mut self, status: &str);
+
+    /// Clears any text currently shown
+    fn clear(&mut self);
+
+    /// Diagnostic log line. Only text consoles print these.
+    fn log(&mut self, _message: &str) {}
+
+    /// A dot or dash was received, before it is decoded
+    fn show_signal(&mut self, _signal: char) {}
+
+    /// Updated link statistics
+    fn show_stats(&mut self, _stats: LinkStats) {}
+
+    /// Sends everything shown on `self` to `other` as well
+    fn and<B: Display>(self, other: B) -> Fanout<Self, B>
+    where
+        Self: Sized,
+    {
+        Fanout::new(self, other)
+    }
+}
+
+/// Forwards every call to two displays in turn
+pub struct Fanout<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<A: Display, B: Display> Fanout<A, B> {
+    pub fn new(first: A, second: B) -> Self {
+        Self { first, second }
+    }
+
+    pub fn release(self) -> (A, B) {
+        (self.first, self.second)
+    }
+}
+
+impl<A: Display, B: Display> Display for Fanout<A, B> {
+    fn show_char(&mut self, c: char) {
+        self.first.show_char(c);
+        self.second.show_char(c);
+    }
+
+    fn show_status(&mut self, status: &str) {
+        self.first.show_status(status);
+        self.second.show_status(status);
+    }
+
+    fn clear(&mut self) {
+        self.first.clear();
+        self.second.clear();
+    }
+
+    fn log(&mut self, message: &str) {
+        self.first.log(message);
+        self.second.log(message);
+    }
+
+    fn show_signal(&mut self, signal: char) {
+        self.first.show_signal(signal);
+        self.second.show_signal(signal);
+    }
+
+    fn show_stats(&mut self, stats: LinkStats) {
+        self.first.show_stats(stats);
+        self.second.show_stats(stats);
+    }
+}
+
+/// A display that may be absent, e.g. an LCD that failed to initialise
+impl<D: Display> Display for Option<D> {
+    fn show_char(&mut self, c: char) {
+        if let Some(display) = self {
+            display.show_char(c);
+        }
+    }
+
+    fn show_status(&mut self, status: &str) {
+        if let Some(display) = self {
+            display.show_status(status);
+        }
+    }
+
+    fn clear(&mut self) {
+        if let Some(display) = self {
+            display.clear();
+        }
+    }
+
+    fn log(&mut self, message: &str) {
+        if let Some(display) = self {
+            display.log(message);
+        }
+    }
+
+    fn show_signal(&mut self, signal: char) {
+        if let Some(display) = self {
+            display.show_signal(signal);
+        }
+    }
+
+    fn show_stats(&mut self, stats: LinkStats) {
+        if let Some(display) = self {
+            display.show_stats(stats);
+        }
+    }
+}
+
+impl<D: Display + ?Sized> Display for &mut D {
+    fn show_char(&mut self, c: char) {
+        (**self).show_char(c);
+    }
+
+    fn show_status(&mut self, status: &str) {
+        (**self).show_status(status);
+    }
+
+    fn clear(&mut self) {
+        (**self).clear();
+    }
+
+    fn log(&mut self, message: &str) {
+        (**self).log(message);
+    }
+
+    fn show_signal(&mut self, signal: char) {
+        (**self).show_signal(signal);
+    }
+
+    fn show_stats(&mut self, stats: LinkStats) {
+        (**self).show_stats(stats);
+    }
+}
+
+impl<D: SegmentDriver> Display for SevenSegment<D> {
+    fn show_char(&mut self, c: char) {
+        SevenSegment::show_char(self, c);
+    }
+
+    fn show_status(&mut self, _status: &str) {}
+
+    fn clear(&mut self) {
+        self.blank();
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::string::{String, ToString};
+    use std::vec::Vec;
+
+    #[derive(Debug, PartialEq)]
+    pub(crate) enum Event {
+        Char(char),
+        Status(String),
+        Clear,
+        Log(String),
+        Signal(char),
+        Stats(LinkStats),
+    }
+
+    /// Display that records every call, for tests
+    #[derive(Default)]
+    pub(crate) struct Recording {
+        pub(crate) events: Vec<Event>,
+    }
+
+    impl Display for Recording {
+        fn show_char(&mut self, c: char) {
+            self.events.push(Event::Char(c));
+        }
+
+        fn show_status(&mut self, status: &str) {
+            self.events.push(Event::Status(status.to_string()));
+        }
+
+        fn clear(&mut self) {
+            self.events.push(Event::Clear);
+        }
+
+        fn log(&mut self, message: &str) {
+            self.events.push(Event::Log(message.to_string()));
+        }
+
+        fn show_signal(&mut self, signal: char) {
+            self.events.push(Event::Signal(signal));
+        }
+
+        fn show_stats(&mut self, stats: LinkStats) {
+            self.events.push(Event::Stats(stats));
+        }
+    }
+
+    #[test]
+    fn fanout_forwards_to_both() {
+        let mut display = Recording::default().and(Recording::default());
+        display.show_char('E');
+        display.show_status("Waiting...");
+        display.clear();
+        let (first, second) = display.release();
+        let expected = [
+            Event::Char('E'),
+            Event::Status("Waiting...".to_string()),
+            Event::Clear,
+        ];
+        assert_eq!(first.events, expected);
+        assert_eq!(second.events, expected);
+    }
+
+    #[test]
+    fn absent_display_is_skipped() {
+        let mut present = Recording::default();
+        {
+            let mut display = (&mut present).and(None::<Recording>);
+            display.show_char('T');
+            display.log("hello");
+        }
+        assert_eq!(present.events, [Event::Char('T'), Event::Log("hello".to_string())]);
+    }
+
+    #[test]
+    fn seven_segment_ignores_status() {
+        struct LastPattern(u8);
+        impl SegmentDriver for &mut LastPattern {
+            fn write_segments(&mut self, pattern: u8) {
+                self.0 = pattern;
+            }
+        }
+
+        let mut last = LastPattern(0xFF);
+        let mut display = SevenSegment::new(&mut last);
+        Display::show_char(&mut display, 'H');
+        display.show_status("ignored");
+        display.release();
+        assert_eq!(last.0, seven_segment::glyph('H'));
+    }
+}
@@ -0,0 +1,402 @@
+//! # SSD1306 128x64 OLED Backend
+//!
+//! Everything is drawn into an in-memory [`Framebuffer`] first and then
+//! sent to the panel in one go, so rendering can be checked on the host.
+//!
+//! ```text
+//!   y  0..7    status line
+//!   y  8..14   WPM and link error count
+//!   y 17..47   last four lines of decoded text
+//!   y 52..62   key-down/up scope, newest sample on the right
+//! ```
+
+use core::fmt::Write;
+use embedded_hal_0_2::blocking::i2c;
+use heapless::{Deque, String};
+
+use super::font::{self, GLYPH_ADVANCE, GLYPH_HEIGHT, GLYPH_WIDTH};
+use super::{Display, LinkStats};
+
+pub const OLED_WIDTH: usize = 128;
+pub const OLED_HEIGHT: usize = 64;
+const OLED_PAGES: usize = OLED_HEIGHT / 8;
+
+/// Characters that fit on one line of the panel
+pub const OLED_CHARS_PER_LINE: usize = OLED_WIDTH / GLYPH_ADVANCE;
+/// Lines of decoded text kept on screen
+pub const OLED_TEXT_LINES: usize = 4;
+
+const STATS_Y: usize = 8;
+const TEXT_Y: usize = 17;
+const SCOPE_HIGH_Y: usize = 52;
+const SCOPE_LOW_Y: usize = 62;
+/// Scope pixels per Morse unit
+const SCOPE_UNIT_PX: usize = 2;
+
+// SSD1306 control bytes
+const CONTROL_COMMAND: u8 = 0x00;
+const CONTROL_DATA: u8 = 0x40;
+
+/// Power-up sequence for a 128x64 panel with the internal charge pump
+const INIT_SEQUENCE: [u8; 25] = [
+    0xAE, // Display off
+    0xD5, 0x80, // Clock divide ratio
+    0xA8, 0x3F, // Multiplex ratio 64
+    0xD3, 0x00, // No display offset
+    0x40, // Start line 0
+    0x8D, 0x14, // Charge pump on
+    0x20, 0x00, // Horizontal addressing mode
+    0xA1, // Segment remap (column 127 = SEG0)
+    0xC8, // COM scan direction remapped
+    0xDA, 0x12, // COM pin configuration
+    0x81, 0xCF, // Contrast
+    0xD9, 0xF1, // Pre-charge period
+    0xDB, 0x40, // VCOMH deselect level
+    0xA4, // Display follows RAM
+    0xA6, // Normal (not inverted)
+    0xAF, // Display on
+];
+
+/// 1 bit per pixel, laid out in the SSD1306's native page order:
+/// byte `page * 128 + x` holds rows `page * 8` to `page * 8 + 7`, LSB on top.
+pub struct Framebuffer {
+    bytes: [u8; OLED_WIDTH * OLED_PAGES],
+}
+
+impl Framebuffer {
+    pub const fn new() -> Self {
+        Self { bytes: [0; OLED_WIDTH * OLED_PAGES] }
+    }
+
+    pub fn clear(&mut self) {
+        self.bytes = [0; OLED_WIDTH * OLED_PAGES];
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Sets or clears a pixel. Out-of-range coordinates are ignored.
+    pub fn set_pixel(&mut self, x: usize, y: usize, on: bool) {
+        if x >= OLED_WIDTH || y >= OLED_HEIGHT {
+            return;
+        }
+        let index = (y / 8) * OLED_WIDTH + x;
+        let mask = 1 << (y % 8);
+        if on {
+            self.bytes[index] |= mask;
+        } else {
+            self.bytes[index] &= !mask;
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        if x >= OLED_WIDTH || y >= OLED_HEIGHT {
+            return false;
+        }
+        self.bytes[(y / 8) * OLED_WIDTH + x] & (1 << (y % 8)) != 0
+    }
+
+    pub fn hline(&mut self, x0: usize, x1: usize, y: usize) {
+        for x in x0..=x1 {
+            self.set_pixel(x, y, true);
+        }
+    }
+
+    pub fn vline(&mut self, x: usize, y0: usize, y1: usize) {
+        let (top, bottom) = if y0 <= y1 { (y0, y1) } else { (y1, y0) };
+        for y in top..=bottom {
+            self.set_pixel(x, y, true);
+        }
+    }
+
+    /// Draws one glyph with its top-left corner at (x, y)
+    pub fn draw_char(&mut self, x: usize, y: usize, c: char) {
+        for (dx, column) in font::glyph(c).iter().enumerate() {
+            for dy in 0..GLYPH_HEIGHT {
+                if column & (1 << dy) != 0 {
+                    self.set_pixel(x + dx, y + dy, true);
+                }
+            }
+        }
+    }
+
+    /// Draws a string left to right, clipped at the right edge
+    pub fn draw_text(&mut self, x: usize, y: usize, text: &str) {
+        for (i, c) in text.chars().enumerate() {
+            let cx = x + i * GLYPH_ADVANCE;
+            if cx + GLYPH_WIDTH > OLED_WIDTH {
+                break;
+            }
+            self.draw_char(cx, y, c);
+        }
+    }
+}
+
+impl Default for Framebuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// SSD1306 OLED showing status, link stats, several lines of text and a
+/// scope of the received keying.
+pub struct Ssd1306<I2C> {
+    i2c: I2C,
+    address: u8,
+    frame: Framebuffer,
+    status: String<OLED_CHARS_PER_LINE>,
+    stats: LinkStats,
+    lines: Deque<String<OLED_CHARS_PER_LINE>, OLED_TEXT_LINES>,
+    scope: Deque<bool, OLED_WIDTH>,
+}
+
+impl<I2C: i2c::Write> Ssd1306<I2C> {
+    pub fn new(i2c: I2C, address: u8) -> Self {
+        let mut oled = Self {
+            i2c,
+            address,
+            frame: Framebuffer::new(),
+            status: String::new(),
+            stats: LinkStats::default(),
+            lines: Deque::new(),
+            scope: Deque::new(),
+        };
+        oled.set_status("Morse Receiver");
+        oled
+    }
+
+    /// Sends the power-up sequence and a first frame.
+    /// Fails if the panel does not acknowledge its address.
+    pub fn init(&mut self) -> Result<(), I2C::Error> {
+        for command in INIT_SEQUENCE {
+            self.command(command)?;
+        }
+        self.render();
+        self.flush()
+    }
+
+    pub fn framebuffer(&self) -> &Framebuffer {
+        &self.frame
+    }
+
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    /// Redraws the framebuffer from the current state. Does not touch the bus.
+    pub fn render(&mut self) {
+        self.frame.clear();
+        self.frame.draw_text(0, 0, self.status.as_str());
+
+        let mut stats = String::<OLED_CHARS_PER_LINE>::new();
+        let _ = write!(stats, "WPM {:>2}  ERR {}", self.stats.wpm, self.stats.errors);
+        self.frame.draw_text(0, STATS_Y, stats.as_str());
+        self.frame.hline(0, OLED_WIDTH - 1, STATS_Y + GLYPH_HEIGHT);
+
+        for (row, line) in self.lines.iter().enumerate() {
+            self.frame.draw_text(0, TEXT_Y + row * 8, line.as_str());
+        }
+
+        let mut previous: Option<bool> = None;
+        for (x, &down) in self.scope.iter().enumerate() {
+            let y = if down { SCOPE_HIGH_Y } else { SCOPE_LOW_Y };
+            match previous {
+                Some(was_down) if was_down != down => self.frame.vline(x, SCOPE_HIGH_Y, SCOPE_LOW_Y),
+                _ => self.frame.set_pixel(x, y, true),
+            }
+            previous = Some(down);
+        }
+    }
+
+    /// Sends the whole framebuffer to the panel
+    pub fn flush(&mut self) -> Result<(), I2C::Error> {
+        for command in [0x21, 0x00, (OLED_WIDTH - 1) as u8, 0x22, 0x00, (OLED_PAGES - 1) as u8] {
+            self.command(command)?;
+        }
+
+        let mut chunk = [0u8; 17];
+        chunk[0] = CONTROL_DATA;
+        for data in self.frame.bytes.chunks(16) {
+            chunk[1..=data.len()].copy_from_slice(data);
+            self.i2c.write(self.address, &chunk[..=data.len()])?;
+        }
+        Ok(())
+    }
+
+    fn command(&mut self, command: u8) -> Result<(), I2C::Error> {
+        self.i2c.write(self.address, &[CONTROL_COMMAND, command])
+    }
+
+    fn set_status(&mut self, status: &str) {
+        self.status.clear();
+        for c in status.chars().take(OLED_CHARS_PER_LINE) {
+            let _ = self.status.push(c);
+        }
+    }
+
+    fn push_text(&mut self, c: char) {
+        let needs_line = self
+            .lines
+            .back()
+            .is_none_or(|line| line.len() >= OLED_CHARS_PER_LINE);
+        if needs_line {
+            if self.lines.is_full() {
+                self.lines.pop_front();
+            }
+            let _ = self.lines.push_back(String::new());
+        }
+        if let Some(line) = self.lines.back_mut() {
+            let _ = line.push(c.to_ascii_uppercase());
+        }
+    }
+
+    fn push_scope(&mut self, down: bool, units: usize) {
+        for _ in 0..units * SCOPE_UNIT_PX {
+            if self.scope.is_full() {
+                self.scope.pop_front();
+            }
+            let _ = self.scope.push_back(down);
+        }
+    }
+
+    fn refresh(&mut self) {
+        self.render();
+        let _ = self.flush();
+    }
+}
+
+impl<I2C: i2c::Write> Display for Ssd1306<I2C> {
+    fn show_char(&mut self, c: char) {
+        self.push_text(c);
+        // Signals already left one unit of gap; pad to 3 units, or 7 for a word
+        self.push_scope(false, if c == ' ' { 4 } else { 2 });
+        self.refresh();
+    }
+
+    fn show_status(&mut self, status: &str) {
+        self.set_status(status);
+        self.refresh();
+    }
+
+    fn clear(&mut self) {
+        self.lines.clear();
+        self.scope.clear();
+        self.refresh();
+    }
+
+    fn show_signal(&mut self, signal: char) {
+        let units = if signal == '-' { 3 } else { 1 };
+        self.push_scope(true, units);
+        self.push_scope(false, 1);
+        self.refresh();
+    }
+
+    fn show_stats(&mut self, stats: LinkStats) {
+        if stats != self.stats {
+            self.stats = stats;
+            self.refresh();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::string::String as StdString;
+    use std::vec::Vec;
+
+    #[derive(Default)]
+    struct MockBus {
+        writes: Vec<(u8, Vec<u8>)>,
+    }
+
+    impl i2c::Write for MockBus {
+        type Error = ();
+
+        fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), ()> {
+            self.writes.push((address, bytes.to_vec()));
+            Ok(())
+        }
+    }
+
+    fn oled() -> Ssd1306<MockBus> {
+        Ssd1306::new(MockBus::default(), crate::OLED_ADDRESS)
+    }
+
+    /// Renders the framebuffer as text, '#' for lit pixels
+    fn snapshot(frame: &Framebuffer) -> StdString {
+        let mut text = StdString::new();
+        for y in 0..OLED_HEIGHT {
+            for x in 0..OLED_WIDTH {
+                text.push(if frame.pixel(x, y) { '#' } else { '.' });
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    #[test]
+    fn pixels_use_page_layout() {
+        let mut frame = Framebuffer::new();
+        frame.set_pixel(3, 10, true);
+        assert_eq!(frame.as_bytes()[OLED_WIDTH + 3], 0b100);
+        assert!(frame.pixel(3, 10));
+        frame.set_pixel(3, 10, false);
+        assert!(frame.as_bytes().iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn out_of_range_pixels_are_ignored() {
+        let mut frame = Framebuffer::new();
+        frame.set_pixel(OLED_WIDTH, 0, true);
+        frame.set_pixel(0, OLED_HEIGHT, true);
+        assert!(frame.as_bytes().iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn render_matches_snapshot() {
+        let mut oled = oled();
+        oled.show_status("RX OK");
+        oled.show_stats(LinkStats { wpm: 12, errors: 3 });
+        for signal in ['.', '.', '.'] {
+            oled.show_signal(signal);
+        }
+        oled.show_char('S');
+        for signal in ['-', '-', '-'] {
+            oled.show_signal(signal);
+        }
+        oled.show_char('O');
+        oled.show_char(' ');
+        assert_eq!(snapshot(oled.framebuffer()), include_str!("snapshots/oled_render.txt"));
+    }
+
+    #[test]
+    fn text_wraps_and_scrolls() {
+        let mut oled = oled();
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        for c in alphabet.chars().cycle().take(OLED_CHARS_PER_LINE * 5 + 3) {
+            oled.push_text(c);
+        }
+        let lines: Vec<&str> = oled.lines.iter().map(|l| l.as_str()).collect();
+        assert_eq!(lines.len(), OLED_TEXT_LINES);
+        // 108 characters make six lines; the first two have scrolled off
+        assert_eq!(lines[0], "QRSTUVWXYZABCDEFGHIJK");
+        assert_eq!(lines[3], "BCD");
+    }
+
+    #[test]
+    fn flush_sends_whole_frame() {
+        let mut oled = oled();
+        oled.flush().unwrap();
+        let data: Vec<u8> = oled
+            .i2c
+            .writes
+            .iter()
+            .filter(|(_, bytes)| bytes[0] == CONTROL_DATA)
+            .flat_map(|(_, bytes)| bytes[1..].to_vec())
+            .collect();
+        assert_eq!(data.len(), OLED_WIDTH * OLED_PAGES);
+        assert!(oled.i2c.writes.iter().all(|(addr, _)| *addr == crate::OLED_ADDRESS));
+    }
+}
@@ -0,0 +1,276 @@
+//! # Seven-Segment Display Backend
+//!
+//! Rust counterpart of the `segment_encoding` table in the C receiver.
+//! Glyphs use the same bit layout: bit 6 = segment a through bit 0 = segment g.
+//!
+//! ```text
+//!    aaa
+//!   f   b
+//!    ggg
+//!   e   c
+//!    ddd
+//! ```
+
+use embedded_hal::digital::OutputPin;
+
+/// Segment patterns for A-Z, identical to `segment_encoding` in `receiver.c`
+pub const LETTER_SEGMENTS: [u8; 26] = [
+    0b1110111, // A
+    0b0011111, // B
+    0b1001110, // C
+    0b0111101, // D
+    0b1001111, // E
+    0b1000111, // F
+    0b1011110, // G
+    0b0110111, // H
+    0b0110000, // I
+    0b0111100, // J
+    0b1110110, // K
+    0b0001110, // L
+    0b1010101, // M
+    0b0010101, // N
+    0b1111110, // O
+    0b1100111, // P
+    0b1110011, // Q
+    0b0000101, // R
+    0b1011011, // S
+    0b0001111, // T
+    0b0111110, // U
+    0b0111110, // V
+    0b1111010, // W
+    0b0110111, // X
+    0b0111011, // Y
+    0b1101101, // Z
+];
+
+/// Segment patterns for 0-9
+pub const DIGIT_SEGMENTS: [u8; 10] = [
+    0b1111110, // 0
+    0b0110000, // 1
+    0b1101101, // 2
+    0b1111001, // 3
+    0b0110011, // 4
+    0b1011011, // 5
+    0b1011111, // 6
+    0b1110000, // 7
+    0b1111111, // 8
+    0b1111011, // 9
+];
+
+/// Pattern shown for characters without a glyph (segment g only)
+pub const UNKNOWN_SEGMENTS: u8 = 0b0000001;
+
+/// Looks up the segment pattern for a character.
+/// Lower-case letters share the upper-case glyphs and a space is blank.
+pub fn glyph(c: char) -> u8 {
+    match c {
+        'A'..='Z' => LETTER_SEGMENTS[(c as u8 - b'A') as usize],
+        'a'..='z' => LETTER_SEGMENTS[(c as u8 - b'a') as usize],
+        '0'..='9' => DIGIT_SEGMENTS[(c as u8 - b'0') as usize],
+        ' ' => 0,
+        _ => UNKNOWN_SEGMENTS,
+    }
+}
+
+/// Something that can light segments a-g from a pattern byte
+pub trait SegmentDriver {
+    /// Drives segment a from bit 6 down to segment g from bit 0.
+    /// A set bit means the pin is driven high.
+    fn write_segments(&mut self, pattern: u8);
+}
+
+/// One GPIO per segment, ordered a to g
+pub struct GpioSegments<P> {
+    pins: [P; 7],
+}
+
+impl<P: OutputPin> GpioSegments<P> {
+    pub fn new(pins: [P; 7]) -> Self {
+        Self { pins }
+    }
+
+    pub fn release(self) -> [P; 7] {
+        self.pins
+    }
+}
+
+impl<P: OutputPin> SegmentDriver for GpioSegments<P> {
+    fn write_segments(&mut self, pattern: u8) {
+        for (i, pin) in self.pins.iter_mut().enumerate() {
+            if pattern & (0x40 >> i) != 0 {
+                let _ = pin.set_high();
+            } else {
+                let _ = pin.set_low();
+            }
+        }
+    }
+}
+
+/// 74HC595-style shift register, shifted MSB first.
+///
+/// Wire Q6 to segment a through Q0 to segment g; Q7 is left free
+/// (it would be the decimal point).
+pub struct ShiftRegisterSegments<DATA, CLK, LATCH> {
+    data: DATA,
+    clock: CLK,
+    latch: LATCH,
+}
+
+impl<DATA, CLK, LATCH> ShiftRegisterSegments<DATA, CLK, LATCH>
+where
+    DATA: OutputPin,
+    CLK: OutputPin,
+    LATCH: OutputPin,
+{
+    pub fn new(mut data: DATA, mut clock: CLK, mut latch: LATCH) -> Self {
+        let _ = data.set_low();
+        let _ = clock.set_low();
+        let _ = latch.set_low();
+        Self { data, clock, latch }
+    }
+
+    pub fn release(self) -> (DATA, CLK, LATCH) {
+        (self.data, self.clock, self.latch)
+    }
+}
+
+impl<DATA, CLK, LATCH> SegmentDriver for ShiftRegisterSegments<DATA, CLK, LATCH>
+where
+    DATA: OutputPin,
+    CLK: OutputPin,
+    LATCH: OutputPin,
+{
+    fn write_segments(&mut self, pattern: u8) {
+        // The '595 tolerates >20 MHz clocks, so no delays are needed here
+        for bit in (0..8).rev() {
+            if pattern & (1 << bit) != 0 {
+                let _ = self.data.set_high();
+            } else {
+                let _ = self.data.set_low();
+            }
+            let _ = self.clock.set_high();
+            let _ = self.clock.set_low();
+        }
+        let _ = self.latch.set_high();
+        let _ = self.latch.set_low();
+    }
+}
+
+/// Single-digit seven-segment display showing the last decoded character
+pub struct SevenSegment<D> {
+    driver: D,
+    common_anode: bool,
+}
+
+impl<D: SegmentDriver> SevenSegment<D> {
+    /// Display whose segments light when driven high
+    pub fn new(driver: D) -> Self {
+        let mut display = Self { driver, common_anode: false };
+        display.blank();
+        display
+    }
+
+    /// Display whose segments light when driven low
+    pub fn common_anode(driver: D) -> Self {
+        let mut display = Self { driver, common_anode: true };
+        display.blank();
+        display
+    }
+
+    pub fn show_char(&mut self, c: char) {
+        self.show_pattern(glyph(c));
+    }
+
+    /// Lights an arbitrary pattern (bit 6 = a ... bit 0 = g)
+    pub fn show_pattern(&mut self, pattern: u8) {
+        let pattern = pattern & 0x7F;
+        let pattern = if self.common_anode { !pattern & 0x7F } else { pattern };
+        self.driver.write_segments(pattern);
+    }
+
+    pub fn blank(&mut self) {
+        self.show_pattern(0);
+    }
+
+    pub fn release(self) -> D {
+        self.driver
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::convert::Infallible;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::vec::Vec;
+
+    #[derive(Clone, Default)]
+    struct MockPin(Rc<RefCell<Vec<bool>>>);
+
+    impl embedded_hal::digital::ErrorType for MockPin {
+        type Error = Infallible;
+    }
+
+    impl OutputPin for MockPin {
+        fn set_low(&mut self) -> Result<(), Infallible> {
+            self.0.borrow_mut().push(false);
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Infallible> {
+            self.0.borrow_mut().push(true);
+            Ok(())
+        }
+    }
+
+    impl MockPin {
+        fn last(&self) -> bool {
+            *self.0.borrow().last().unwrap()
+        }
+    }
+
+    #[test]
+    fn glyphs_match_c_table() {
+        assert_eq!(glyph('A'), 0b1110111);
+        assert_eq!(glyph('z'), 0b1101101);
+        assert_eq!(glyph('8'), 0b1111111);
+        assert_eq!(glyph(' '), 0);
+        assert_eq!(glyph('?'), UNKNOWN_SEGMENTS);
+    }
+
+    #[test]
+    fn gpio_driver_maps_bit6_to_segment_a() {
+        let pins: [MockPin; 7] = Default::default();
+        let mut display = SevenSegment::new(GpioSegments::new(pins.clone()));
+        display.show_char('1'); // segments b and c
+        let lit: Vec<bool> = pins.iter().map(MockPin::last).collect();
+        assert_eq!(lit, [false, true, true, false, false, false, false]);
+    }
+
+    #[test]
+    fn common_anode_inverts() {
+        let pins: [MockPin; 7] = Default::default();
+        let mut display = SevenSegment::common_anode(GpioSegments::new(pins.clone()));
+        display.show_char('1');
+        let lit: Vec<bool> = pins.iter().map(MockPin::last).collect();
+        assert_eq!(lit, [true, false, false, true, true, true, true]);
+    }
+
+    #[test]
+    fn shift_register_sends_msb_first() {
+        let data = MockPin::default();
+        let clock = MockPin::default();
+        let latch = MockPin::default();
+        let mut driver = ShiftRegisterSegments::new(data.clone(), clock.clone(), latch.clone());
+        data.0.borrow_mut().clear();
+        driver.write_segments(glyph('E'));
+        assert_eq!(
+            *data.0.borrow(),
+            [false, true, false, false, true, true, true, true]
+        );
+        // One rising edge per bit, then a single latch pulse
+        assert_eq!(clock.0.borrow().iter().filter(|&&b| b).count(), 8);
+        assert_eq!(latch.0.borrow().iter().filter(|&&b| b).count(), 1);
+    }
+}
@@ -0,0 +1,97 @@
+//! # DMA Channel Access
+//!
+//! Raw register access to one DMA channel, shared by the DMA variants of
+//! the GPIO, ADC and UART benchmarks. Each benchmark takes the DMA block
+//! out of reset with [`reset`], starts one [`Transfer`] at a time on
+//! [`CHANNEL`] and waits for it with [`wait`].
+//!
+//! The DMA cannot reach the SIO, which sits on each core's private
+//! IOPORT, so it drives GPIO through IO_BANK0 instead.
+
+use core::sync::atomic::{compiler_fence, Ordering};
+
+use rp2040_hal as hal;
+
+/// The channel the benchmarks use
+pub const CHANNEL: usize = 0;
+
+/// Transfer request signals, from the RP2040 datasheet's DREQ table
+pub const TREQ_UART0_TX: u8 = 20;
+pub const TREQ_ADC: u8 = 36;
+/// No pacing: the channel runs as fast as the bus lets it
+pub const TREQ_PERMANENT: u8 = 0x3f;
+
+/// Size of each transfer
+#[derive(Clone, Copy)]
+pub enum Size {
+    Byte = 0,
+    HalfWord = 1,
+    Word = 2,
+}
+
+/// One transfer: `count` items of `size` from `read` to `write`
+pub struct Transfer {
+    pub read: u32,
+    pub write: u32,
+    pub count: u32,
+    pub size: Size,
+    pub incr_read: bool,
+    pub incr_write: bool,
+    /// Wraps the read address on a `1 << read_ring_bits` byte boundary,
+    /// e.g. to repeat a short pattern; 0 for no ring
+    pub read_ring_bits: u8,
+    pub treq: u8,
+}
+
+fn dma() -> &'static hal::pac::dma::RegisterBlock {
+    unsafe { &*hal::pac::DMA::ptr() }
+}
+
+/// Resets the DMA block and waits for it to come back
+pub fn reset() {
+    let resets = unsafe { &*hal::pac::RESETS::ptr() };
+    resets.reset().modify(|_, w| w.dma().set_bit());
+    resets.reset().modify(|_, w| w.dma().clear_bit());
+    while resets.reset_done().read().dma().bit_is_clear() {}
+}
+
+/// Starts `transfer` on [`CHANNEL`]
+pub fn start(transfer: &Transfer) {
+    // Buffer writes land before the DMA reads them
+    compiler_fence(Ordering::Release);
+    let channel = dma().ch(CHANNEL);
+    channel.ch_read_addr().write(|w| unsafe { w.bits(transfer.read) });
+    channel.ch_write_addr().write(|w| unsafe { w.bits(transfer.write) });
+    channel.ch_trans_count().write(|w| unsafe { w.bits(transfer.count) });
+    // Writing the control register through its trigger alias starts it
+    channel.ch_ctrl_trig().write(|w| unsafe {
+        w.data_size().bits(transfer.size as u8)
+            .incr_read().bit(transfer.incr_read)
+            .incr_write().bit(transfer.incr_write)
+            .ring_size().bits(transfer.read_ring_bits)
+            .ring_sel().clear_bit()
+            // Chaining to itself means no chaining
+            .chain_to().bits(CHANNEL as u8)
+            .treq_sel().bits(transfer.treq)
+            .irq_quiet().set_bit()
+            .en().set_bit()
+    });
+}
+
+pub fn is_busy() -> bool {
+    dma().ch(CHANNEL).ch_ctrl_trig().read().busy().bit_is_set()
+}
+
+/// Waits for the transfer on [`CHANNEL`] to finish
+pub fn wait() {
+    while is_busy() {}
+    // And buffer reads happen after the DMA wrote them
+    compiler_fence(Ordering::Acquire);
+}
+
+/// Stops [`CHANNEL`] and disables it
+pub fn stop() {
+    dma().chan_abort().write(|w| unsafe { w.bits(1 << CHANNEL) });
+    while dma().chan_abort().read().bits() & (1 << CHANNEL) != 0 {}
+    dma().ch(CHANNEL).ch_ctrl_trig().write(|w| unsafe { w.bits(0) });
+}
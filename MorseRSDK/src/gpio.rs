@@ -1,21 +1,38 @@
-use cortex_m::delay::Delay;
+use embedded_hal::delay::DelayNs;
 use embedded_hal_0_2::digital::v2::OutputPin;
-use rp2040_hal::Timer;
 use rtt_target::rprintln;
 
+use crate::bench::{Metric, Run, Samples};
+use crate::cycles::CycleCounter;
+use crate::dma;
+
+/// GPIO_CTRL value with the pin's output enabled and forced high or low
+/// through its overrides: OEOVER (bits 13:12) = enable, OUTOVER (bits
+/// 9:8) = high or low, FUNCSEL (bits 4:0) = none
+const CTRL_OUT_HIGH: u32 = (3 << 12) | (3 << 8) | 0x1f;
+const CTRL_OUT_LOW: u32 = (3 << 12) | (2 << 8) | 0x1f;
+
+/// One toggle's two GPIO_CTRL writes, aligned for the DMA's address ring
+#[repr(C, align(8))]
+struct TogglePattern([u32; 2]);
+
 pub fn benchmark_gpio_toggle<P: rp2040_hal::gpio::PinId, PT: rp2040_hal::gpio::PullType>(
-    timer: &Timer,
+    cycles: &CycleCounter,
     pin: &mut rp2040_hal::gpio::Pin<P, rp2040_hal::gpio::FunctionSio<rp2040_hal::gpio::SioOutput>, PT>,
-    delay: &mut Delay,
+    delay: &mut impl DelayNs,
 ) {
-    // Header matches the C version
-    rprintln!("task,method,iteration,toggles,total_time_us,avg_toggle_us");
-
     const NUM_ITERATIONS: usize = 100;
     const TOGGLE_COUNT: usize = 1000; // One toggle = set high + set low
 
+    let run = Run {
+        task: "gpio",
+        method: "toggle",
+        params: &[("toggles", TOGGLE_COUNT as u32)],
+        clock_hz: cycles.system_clock_hz(),
+    };
+    let mut samples = Samples::new("cycles");
     for i in 0..NUM_ITERATIONS {
-        let start = timer.get_counter().ticks();
+        let start = cycles.now();
 
         for _ in 0..TOGGLE_COUNT {
             // Use the rp2040-hal's direct pin methods for optimal performance
@@ -23,51 +40,62 @@ pub fn benchmark_gpio_toggle<P: rp2040_hal::gpio::PinId, PT: rp2040_hal::gpio::P
             let _ = pin.set_low();
         }
 
-        let end = timer.get_counter().ticks();
-        let duration_us = end.wrapping_sub(start);
+        let duration_cycles = cycles.since(start);
+        let duration_us = cycles.to_micros(duration_cycles);
 
-        let avg_us = if TOGGLE_COUNT > 0 {
-            duration_us as f32 / TOGGLE_COUNT as f32
+        let avg_cycles = if TOGGLE_COUNT > 0 {
+            duration_cycles as f32 / TOGGLE_COUNT as f32
         } else {
             0.0
         };
-
-        rprintln!(
-            "gpio,toggle,{},{},{},{:.2}",
-            i,
-            TOGGLE_COUNT,
-            duration_us,
-            avg_us
-        );
+        let avg_us = avg_cycles / cycles.cycles_per_micro() as f32;
+
+        samples.record(duration_cycles as u32);
+        run.sample(i as u32, &[
+            Metric::new("total_time", duration_us, "us"),
+            Metric::new("avg_toggle", avg_us, "us"),
+            Metric::new("total_time", duration_cycles, "cycles"),
+            Metric::new("avg_toggle", avg_cycles, "cycles"),
+            // The CPU toggles every edge itself
+            Metric::new("cpu_time", duration_cycles, "cycles"),
+        ]);
 
         delay.delay_ms(100);
     }
+    run.summary(&samples);
     rprintln!("GPIO benchmark finished.");
 }
 
 pub fn benchmark_gpio_toggle_raw(
-    timer: &Timer,
+    cycles: &CycleCounter,
     pin_number: u8,  // GPIO pin number
-    delay: &mut Delay,
+    delay: &mut impl DelayNs,
 ) {
-    // Header matches the C version
-    rprintln!("task,method,iteration,toggles,total_time_us,avg_toggle_us");
-
     const NUM_ITERATIONS: usize = 100;
     const TOGGLE_COUNT: usize = 1000; // One toggle = set high + set low
 
-    // Get direct pointers to SIO registers
+    let run = Run {
+        task: "gpio",
+        method: "toggle_raw",
+        params: &[("toggles", TOGGLE_COUNT as u32)],
+        clock_hz: cycles.system_clock_hz(),
+    };
+
+    // Get direct pointers to SIO and IO bank registers
     let sio = unsafe { &*rp2040_hal::pac::SIO::ptr() };
+    let io_bank0 = unsafe { &*rp2040_hal::pac::IO_BANK0::ptr() };
     
     // Configure the pin as output (equivalent to gpio_init and gpio_set_dir in C)
     let pin_mask = 1u32 << pin_number;
+    io_bank0.gpio(pin_number as usize).gpio_ctrl().write(|w| unsafe { w.funcsel().bits(5) }); // SIO
     unsafe {
         // Set GPIO direction to output using the proper method
         sio.gpio_oe_set().write(|w| unsafe { w.bits(pin_mask) });
     }
 
+    let mut samples = Samples::new("cycles");
     for i in 0..NUM_ITERATIONS {
-        let start = timer.get_counter().ticks();
+        let start = cycles.now();
 
         for _ in 0..TOGGLE_COUNT {
             // Direct register manipulation - equivalent to gpio_put in C SDK
@@ -80,24 +108,101 @@ pub fn benchmark_gpio_toggle_raw(
             }
         }
 
-        let end = timer.get_counter().ticks();
-        let duration_us = end.wrapping_sub(start);
+        let duration_cycles = cycles.since(start);
+        let duration_us = cycles.to_micros(duration_cycles);
 
-        let avg_us = if TOGGLE_COUNT > 0 {
-            duration_us as f32 / TOGGLE_COUNT as f32
+        let avg_cycles = if TOGGLE_COUNT > 0 {
+            duration_cycles as f32 / TOGGLE_COUNT as f32
         } else {
             0.0
         };
+        let avg_us = avg_cycles / cycles.cycles_per_micro() as f32;
 
-        rprintln!(
-            "gpio,toggle_raw,{},{},{},{:.2}",
-            i,
-            TOGGLE_COUNT,
-            duration_us,
-            avg_us
-        );
+        samples.record(duration_cycles as u32);
+        run.sample(i as u32, &[
+            Metric::new("total_time", duration_us, "us"),
+            Metric::new("avg_toggle", avg_us, "us"),
+            Metric::new("total_time", duration_cycles, "cycles"),
+            Metric::new("avg_toggle", avg_cycles, "cycles"),
+            Metric::new("cpu_time", duration_cycles, "cycles"),
+        ]);
 
         delay.delay_ms(100);
     }
+    run.summary(&samples);
+    
+    // Release the pin so the HAL version can take it next
+    sio.gpio_oe_clr().write(|w| unsafe { w.bits(pin_mask) });
+    io_bank0.gpio(pin_number as usize).gpio_ctrl().write(|w| unsafe { w.funcsel().bits(0x1f) });
+    
     rprintln!("GPIO raw benchmark finished.");
-}
\ No newline at end of file
+}
+
+/// Toggles the pin with DMA. The DMA cannot write the SIO, so it drives
+/// the pin through its IO_BANK0 output override instead, repeating a
+/// high, low pattern with an address ring on the read side.
+pub fn benchmark_gpio_toggle_dma(
+    cycles: &CycleCounter,
+    pin_number: u8,  // GPIO pin number
+    delay: &mut impl DelayNs,
+) {
+    const NUM_ITERATIONS: usize = 100;
+    const TOGGLE_COUNT: usize = 1000; // One toggle = set high + set low
+
+    let run = Run {
+        task: "gpio",
+        method: "toggle_dma",
+        params: &[("toggles", TOGGLE_COUNT as u32)],
+        clock_hz: cycles.system_clock_hz(),
+    };
+
+    let io_bank0 = unsafe { &*rp2040_hal::pac::IO_BANK0::ptr() };
+    let ctrl = io_bank0.gpio(pin_number as usize).gpio_ctrl();
+    ctrl.write(|w| unsafe { w.bits(CTRL_OUT_LOW) });
+    dma::reset();
+
+    let pattern = TogglePattern([CTRL_OUT_HIGH, CTRL_OUT_LOW]);
+
+    let mut samples = Samples::new("cycles");
+    for i in 0..NUM_ITERATIONS {
+        let start = cycles.now();
+
+        // The CPU's part is only setting the channel up
+        dma::start(&dma::Transfer {
+            read: pattern.0.as_ptr() as u32,
+            write: ctrl.as_ptr() as u32,
+            count: 2 * TOGGLE_COUNT as u32,
+            size: dma::Size::Word,
+            incr_read: true,
+            incr_write: false,
+            read_ring_bits: 3, // The 8-byte pattern
+            treq: dma::TREQ_PERMANENT,
+        });
+        let cpu_cycles = cycles.since(start);
+        dma::wait();
+
+        let duration_cycles = cycles.since(start);
+        let duration_us = cycles.to_micros(duration_cycles);
+
+        let avg_cycles = duration_cycles as f32 / TOGGLE_COUNT as f32;
+        let avg_us = avg_cycles / cycles.cycles_per_micro() as f32;
+
+        samples.record(duration_cycles as u32);
+        run.sample(i as u32, &[
+            Metric::new("total_time", duration_us, "us"),
+            Metric::new("avg_toggle", avg_us, "us"),
+            Metric::new("total_time", duration_cycles, "cycles"),
+            Metric::new("avg_toggle", avg_cycles, "cycles"),
+            Metric::new("cpu_time", cpu_cycles, "cycles"),
+        ]);
+
+        delay.delay_ms(100);
+    }
+    run.summary(&samples);
+
+    // Release the pin: no overrides, no function
+    dma::stop();
+    ctrl.write(|w| unsafe { w.funcsel().bits(0x1f) });
+
+    rprintln!("GPIO DMA benchmark finished.");
+}
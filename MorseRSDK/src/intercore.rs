@@ -0,0 +1,178 @@
+//! # Inter-core Messages
+//!
+//! The receiver runs on both RP2040 cores: core0 captures link bytes and
+//! keeps time, core1 decodes them and updates the displays and message
+//! log. Core0 sends each [`CoreMessage`] over the SIO FIFO as two words:
+//!
+//! ```text
+//! word 0  bits 24..32  kind
+//!         bits 16..24  byte, or a count of lost bytes
+//!         bits 0..16   time, bits 32..48
+//! word 1               time, bits 0..32
+//! ```
+//!
+//! Times are core0 [`Instant`]s, carried to 48 bits: about 8.9 years of
+//! uptime.
+
+use core::fmt;
+
+use crate::{Duration, Instant};
+
+const KIND_BYTE: u32 = 1;
+const KIND_TICK: u32 = 2;
+const KIND_OVERRUN: u32 = 3;
+const KIND_RX_ERROR: u32 = 4;
+
+const TIME_MASK: u64 = (1 << 48) - 1;
+
+/// What core0 tells core1
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CoreMessage {
+    /// A byte from the link, stamped when core0 took it off the ring
+    Byte { byte: u8, at: Instant },
+    /// The time now, so the gap timeouts run while the line is idle
+    Tick { at: Instant },
+    /// Bytes lost because the receive ring or the UART FIFO overflowed
+    Overrun { count: u8, at: Instant },
+    /// Bytes lost to parity, framing or break errors
+    RxError { count: u8, at: Instant },
+}
+
+impl CoreMessage {
+    /// When core0 made the message
+    pub fn at(&self) -> Instant {
+        match *self {
+            CoreMessage::Byte { at, .. }
+            | CoreMessage::Tick { at }
+            | CoreMessage::Overrun { at, .. }
+            | CoreMessage::RxError { at, .. } => at,
+        }
+    }
+
+    pub fn to_words(&self) -> [u32; 2] {
+        let (kind, data) = match *self {
+            CoreMessage::Byte { byte, .. } => (KIND_BYTE, byte),
+            CoreMessage::Tick { .. } => (KIND_TICK, 0),
+            CoreMessage::Overrun { count, .. } => (KIND_OVERRUN, count),
+            CoreMessage::RxError { count, .. } => (KIND_RX_ERROR, count),
+        };
+        let at = self.at().ticks() & TIME_MASK;
+        [kind << 24 | u32::from(data) << 16 | (at >> 32) as u32, at as u32]
+    }
+
+    /// Returns `None` for an unknown kind, e.g. if the FIFO fell out of step
+    pub fn from_words(words: [u32; 2]) -> Option<Self> {
+        let data = (words[0] >> 16) as u8;
+        let at = Instant::from_ticks(u64::from(words[0] & 0xFFFF) << 32 | u64::from(words[1]));
+        match words[0] >> 24 {
+            KIND_BYTE => Some(CoreMessage::Byte { byte: data, at }),
+            KIND_TICK => Some(CoreMessage::Tick { at }),
+            KIND_OVERRUN => Some(CoreMessage::Overrun { count: data, at }),
+            KIND_RX_ERROR => Some(CoreMessage::RxError { count: data, at }),
+            _ => None,
+        }
+    }
+}
+
+/// Running statistics of the time from a byte arriving on core0 to the
+/// character it completes being on every display
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LatencyStats {
+    count: u32,
+    last: Duration,
+    min: Duration,
+    max: Duration,
+    total: Duration,
+}
+
+impl LatencyStats {
+    pub const fn new() -> Self {
+        const ZERO: Duration = Duration::from_ticks(0);
+        Self { count: 0, last: ZERO, min: ZERO, max: ZERO, total: ZERO }
+    }
+
+    pub fn record(&mut self, latency: Duration) {
+        if self.count == 0 || latency < self.min {
+            self.min = latency;
+        }
+        self.max = self.max.max(latency);
+        self.last = latency;
+        self.total += latency;
+        self.count += 1;
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    pub fn mean(&self) -> Option<Duration> {
+        (self.count > 0).then(|| self.total / self.count)
+    }
+}
+
+impl Default for LatencyStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for LatencyStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.mean() {
+            Some(mean) => write!(
+                f,
+                "last {} us, min {} us, mean {} us, max {} us ({} chars)",
+                self.last.to_micros(),
+                self.min.to_micros(),
+                mean.to_micros(),
+                self.max.to_micros(),
+                self.count
+            ),
+            None => f.write_str("no characters yet"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::string::ToString;
+
+    fn at(us: u64) -> Instant {
+        Instant::from_ticks(us)
+    }
+
+    #[test]
+    fn messages_survive_the_fifo() {
+        let messages = [
+            CoreMessage::Byte { byte: b'.', at: at(0) },
+            CoreMessage::Byte { byte: 0xFF, at: at(TIME_MASK) },
+            CoreMessage::Tick { at: at(0x1234_5678_9ABC) },
+            CoreMessage::Overrun { count: 255, at: at(1) },
+            CoreMessage::RxError { count: 3, at: at(1 << 32) },
+        ];
+        for message in messages {
+            assert_eq!(CoreMessage::from_words(message.to_words()), Some(message));
+        }
+    }
+
+    #[test]
+    fn unknown_kinds_are_rejected() {
+        assert_eq!(CoreMessage::from_words([0, 0]), None);
+        assert_eq!(CoreMessage::from_words([0xFF00_0000, 0]), None);
+    }
+
+    #[test]
+    fn latency_tracks_min_mean_and_max() {
+        let mut stats = LatencyStats::default();
+        assert_eq!(stats.mean(), None);
+        assert_eq!(stats.to_string(), "no characters yet");
+
+        for us in [300, 100, 500] {
+            stats.record(Duration::micros(us));
+        }
+        assert_eq!(stats.count(), 3);
+        assert_eq!(stats.mean(), Some(Duration::micros(300)));
+        assert_eq!(stats.to_string(), "last 500 us, min 100 us, mean 300 us, max 500 us (3 chars)");
+    }
+}
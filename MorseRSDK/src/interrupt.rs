@@ -2,23 +2,27 @@
 //!
 //! Contains the benchmark logic for measuring GPIO interrupt latency.
 //! Designed to be called from a central benchmark runner.
+//!
+//! The benchmark stimulates itself: it reads the cycle counter, makes a
+//! falling edge on GPIO16 and the ISR reads the counter again on entry.
+//! The edge comes either from GPIO16's own input override, with nothing
+//! wired, or from GPIO17 jumpered to GPIO16. See [`Stimulus`].
 
 #![no_std]
 
 // --- RTT Import ---
 use rtt_target::rprintln;
 
-// Alias for our HAL crate
 use rp2040_hal::{
-    self as hal, fugit, gpio::{
+    gpio::{
         // Import Gpio pins from bank0
-        bank0::{Gpio16, Gpio25}, FunctionSio, Interrupt as GpioInterrupt, Pin, PullDown, PullUp, SioInput, SioOutput
-    }, pac::{self, interrupt, Interrupt as HalInterrupt}, timer::Timer, Clock // Import Clock trait for freq()
+        bank0::{Gpio16, Gpio17, Gpio25}, FunctionSio, Interrupt as GpioInterrupt, Pin, PullDown, PullUp, SioInput, SioOutput
+    }, pac::{interrupt, Interrupt as HalInterrupt}
 };
 
 // For interrupt handling
 use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
-use cortex_m::delay::Delay;
+use embedded_hal::delay::DelayNs;
 
 use embedded_hal_0_2::digital::v2::OutputPin; // Use 0.2 trait for set_high/low
 
@@ -26,18 +30,75 @@ use embedded_hal_0_2::digital::v2::OutputPin; // Use 0.2 trait for set_high/low
 use core::cell::RefCell;
 use critical_section::Mutex;
 
+use crate::bench::{Metric, Run, Samples};
+use crate::cycles::{self, CycleCounter};
+
 // Define GPIO pins used, matching the C code
-const BUTTON_GPIO_PIN_NUM: u8 = 16; // GPIO16 for button
+const BUTTON_GPIO_PIN_NUM: usize = 16; // GPIO16 takes the edge
+
+/// Edges measured in a run
+const NUM_SAMPLES: u32 = 10_000;
+/// Time for GPIO16 to settle high before the next edge
+const SETTLE_US: u32 = 10;
+/// An edge not handled within this is counted as missed
+const TIMEOUT_US: u32 = 1_000;
 
 // --- Type Aliases for Shared Pins ---
 // Define the specific types for the pins we need to share with the ISR
 type ButtonPinType = Pin<Gpio16, FunctionSio<SioInput>, PullUp>;
-// Although LED is flashed in the main loop, include if ISR needed it later
+type StimulusPinType = Pin<Gpio17, FunctionSio<SioOutput>, PullDown>;
+
+/// How the benchmark makes the falling edge on GPIO16
+pub enum Stimulus {
+    /// Forces GPIO16's input low with its IO_BANK0 input override. The
+    /// edge detector sees the overridden input, so nothing is wired.
+    PadOverride,
+    /// Drives GPIO17 low; wire GPIO17 to GPIO16. Includes the pad's input
+    /// synchroniser, as an external edge would.
+    Jumper(StimulusPinType),
+}
+
+impl Stimulus {
+    /// Method name in the results
+    fn method(&self) -> &'static str {
+        match self {
+            Stimulus::PadOverride => "pad_override",
+            Stimulus::Jumper(_) => "jumper",
+        }
+    }
+
+    /// Makes the falling edge
+    fn trigger(&mut self) {
+        match self {
+            Stimulus::PadOverride => {
+                let io_bank0 = unsafe { &*rp2040_hal::pac::IO_BANK0::ptr() };
+                io_bank0.gpio(BUTTON_GPIO_PIN_NUM).gpio_ctrl().modify(|_, w| w.inover().low());
+            }
+            Stimulus::Jumper(pin) => {
+                pin.set_low().ok();
+            }
+        }
+    }
+
+    /// Lets GPIO16 go high again
+    fn release(&mut self) {
+        match self {
+            Stimulus::PadOverride => {
+                let io_bank0 = unsafe { &*rp2040_hal::pac::IO_BANK0::ptr() };
+                io_bank0.gpio(BUTTON_GPIO_PIN_NUM).gpio_ctrl().modify(|_, w| w.inover().normal());
+            }
+            Stimulus::Jumper(pin) => {
+                pin.set_high().ok();
+            }
+        }
+    }
+}
 
 // --- Static Variables for ISR Communication ---
+// Cycle counts, low 32 bits: enough for 34 s at 125 MHz
 static IRQ_START_TIME: AtomicU32 = AtomicU32::new(0);
-static IRQ_LATENCY: AtomicU32 = AtomicU32::new(0); // Stores raw latency ticks calculated by ISR
-static TRIGGER_LED: AtomicBool = AtomicBool::new(false); // Flag set by ISR
+static IRQ_LATENCY: AtomicU32 = AtomicU32::new(0); // Stores latency cycles calculated by ISR
+static IRQ_HANDLED: AtomicBool = AtomicBool::new(false); // Flag set by ISR
 
 // --- Mutex for Sharing Button Pin with ISR ---
 // The ISR needs access to the button pin to clear the interrupt.
@@ -47,40 +108,43 @@ static SHARED_BUTTON_PIN: Mutex<RefCell<Option<ButtonPinType>>> = Mutex::new(Ref
 // Use the correct interrupt attribute
 #[interrupt]
 fn IO_IRQ_BANK0() {
+    // Read the counter first, so the latency ends at handler entry
+    let end_time_raw = cycles::now() as u32;
     critical_section::with(|cs| {
         if let Some(button_pin) = SHARED_BUTTON_PIN.borrow(cs).borrow_mut().as_mut() {
             if button_pin.interrupt_status(GpioInterrupt::EdgeLow) {
-                let timer = unsafe { &*pac::TIMER::ptr() };
-                let end_time_raw = timer.timerawl().read().bits();
                 let start_time_raw = IRQ_START_TIME.load(Ordering::Relaxed);
                 let latency_raw = end_time_raw.wrapping_sub(start_time_raw);
                 IRQ_LATENCY.store(latency_raw, Ordering::Relaxed);
-                TRIGGER_LED.store(true, Ordering::Relaxed);
+                IRQ_HANDLED.store(true, Ordering::Release);
                 button_pin.clear_interrupt(GpioInterrupt::EdgeLow);
             }
         }
     });
 }
 
+type LedPinType = Pin<Gpio25, FunctionSio<SioOutput>, PullDown>;
+
+/// Measures `NUM_SAMPLES` edges made by `stimulus`. Returns the button
+/// and LED pins when done, with the button interrupt off.
 pub fn benchmark_interrupt(
-    timer: &Timer,
+    cycles: &CycleCounter,
     mut button_pin: ButtonPinType,
-    mut led_pin: Pin<Gpio25, FunctionSio<SioOutput>, PullDown>,
-    delay: &mut Delay,
-    clock_freq: fugit::Rate<u32, 1, 1>,
-) {
-    // Initialize button pin with proper interrupt configuration
+    mut led_pin: LedPinType,
+    stimulus: &mut Stimulus,
+    delay: &mut impl DelayNs,
+) -> (ButtonPinType, LedPinType) {
     rprintln!("Initializing interrupt benchmark...");
-    delay.delay_ms(1000);
-    
-    // Make sure LED is off at start
-    led_pin.set_low().ok();
-    
+
+    // GPIO16 idles high, so the first edge is a real one
+    stimulus.release();
+    delay.delay_us(SETTLE_US);
+
     // Configure the button pin for interrupts
     button_pin.set_interrupt_enabled(GpioInterrupt::EdgeLow, false); // Disable first
     button_pin.clear_interrupt(GpioInterrupt::EdgeLow); // Clear any pending interrupts
     button_pin.set_interrupt_enabled(GpioInterrupt::EdgeLow, true); // Now enable
-    
+
     // Share the button pin with the ISR
     critical_section::with(|cs| {
         SHARED_BUTTON_PIN.borrow(cs).replace(Some(button_pin));
@@ -93,55 +157,69 @@ pub fn benchmark_interrupt(
     }
 
     rprintln!("Benchmark: Interrupt Latency");
-    rprintln!("task,method,latency_us");
-    rprintln!("Press the button to trigger interrupt...");
-
-    let num_triggers_to_run = 100; // Reduced for testing
-    let mut triggers_counted = 0;
-
-    // Store initial time
-    IRQ_START_TIME.store(timer.get_counter_low(), Ordering::Relaxed);
-
-    while triggers_counted < num_triggers_to_run {
-        // Update start time periodically
-        IRQ_START_TIME.store(timer.get_counter_low(), Ordering::Relaxed);
-
-        // Check if interrupt triggered
-        if TRIGGER_LED.load(Ordering::Relaxed) {
-            TRIGGER_LED.store(false, Ordering::Relaxed);
-            triggers_counted += 1;
-
-            let latency_raw = IRQ_LATENCY.load(Ordering::Relaxed);
-            let sys_freq_hz = clock_freq.to_Hz();
-            let latency_us = if sys_freq_hz > 0 {
-                (latency_raw as u64 * 1_000_000 / sys_freq_hz as u64) as u32
-            } else {
-                0
-            };
-
-            rprintln!("interrupt,triggered,{}", latency_us);
-
-            led_pin.set_high().ok();
-            delay.delay_ms(100);
-            led_pin.set_low().ok();
-            
-            // Wait for button release to prevent multiple triggers
-            delay.delay_ms(200);
+    let run = Run {
+        task: "interrupt",
+        method: stimulus.method(),
+        params: &[("samples", NUM_SAMPLES)],
+        clock_hz: cycles.system_clock_hz(),
+    };
+    let timeout_cycles = u64::from(TIMEOUT_US * cycles.cycles_per_micro());
+
+    // LED stays on while the run is going
+    led_pin.set_high().ok();
+
+    let mut samples = Samples::new("cycles");
+    let mut missed = 0;
+    for i in 0..NUM_SAMPLES {
+        IRQ_HANDLED.store(false, Ordering::Relaxed);
+
+        // Stamp the start as close to the edge as possible
+        let start = cycles.now();
+        IRQ_START_TIME.store(start as u32, Ordering::Relaxed);
+        stimulus.trigger();
+
+        while !IRQ_HANDLED.load(Ordering::Acquire) && cycles.now() - start < timeout_cycles {
+            core::hint::spin_loop();
+        }
+        stimulus.release();
+
+        if IRQ_HANDLED.load(Ordering::Acquire) {
+            // The ISR's read of the counter is as late as a `since` would be
+            let latency_cycles = u64::from(IRQ_LATENCY.load(Ordering::Relaxed)).saturating_sub(cycles.overhead());
+            let latency_us = latency_cycles as f32 / cycles.cycles_per_micro() as f32;
+
+            samples.record(latency_cycles as u32);
+            run.sample(i, &[
+                Metric::new("latency", latency_us, "us"),
+                Metric::new("latency", latency_cycles, "cycles"),
+            ]);
+        } else {
+            missed += 1;
         }
-        
-        // Short delay to avoid tight loop
-        delay.delay_ms(5);
+
+        delay.delay_us(SETTLE_US);
     }
 
     // Disable the interrupt when finished
     unsafe {
         rp2040_hal::pac::NVIC::mask(HalInterrupt::IO_IRQ_BANK0);
     }
-    
+
     // Return ownership of the button pin
-    let _button_pin_returned = critical_section::with(|cs| {
+    let button_pin = critical_section::with(|cs| {
         SHARED_BUTTON_PIN.borrow(cs).replace(None)
-    });
+    }).unwrap();
+    button_pin.set_interrupt_enabled(GpioInterrupt::EdgeLow, false);
+    led_pin.set_low().ok();
 
-    rprintln!("Interrupt benchmark finished after {} triggers.", num_triggers_to_run);
-}
\ No newline at end of file
+    run.summary(&samples);
+    if missed > 0 {
+        rprintln!("{} of {} edges were not handled within {} us.", missed, NUM_SAMPLES, TIMEOUT_US);
+        if let Stimulus::Jumper(_) = stimulus {
+            rprintln!("Check that GPIO17 is wired to GPIO16.");
+        }
+    }
+    rprintln!("Interrupt benchmark finished after {} edges.", NUM_SAMPLES);
+
+    (button_pin, led_pin)
+}
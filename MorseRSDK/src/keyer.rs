@@ -0,0 +1,339 @@
+//! # Text Keyer
+//!
+//! Turns typed text into a queue of Morse elements for the transmitter.
+//! Lines arrive byte by byte through a [`LineEditor`], are parsed into a
+//! [`Command`], and text is encoded into a [`KeyQueue`] that the
+//! transmitter drains one element at a time at the current [`Speed`].
+//!
+//! Commands start with `/`:
+//! - `/wpm N` sets the speed to N words per minute
+//! - `/abort` drops everything still queued
+//! - `/help` lists the commands
+//!
+//! Any other `/` line is passed on as [`Command::Shell`], so the
+//! transmitter can hand it to the [configuration shell](crate::shell).
+
+use heapless::{Deque, String};
+
+use crate::{Duration, MORSE_CODE};
+
+/// Slowest speed accepted by `/wpm`
+pub const MIN_WPM: u32 = 1;
+/// Fastest speed accepted by `/wpm`
+pub const MAX_WPM: u32 = 40;
+
+/// One keyed element. Gaps are the extra silence on top of the one-unit
+/// gap that always follows a dot or dash.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Element {
+    Dot,
+    Dash,
+    /// End of a character: 3 units of silence in total
+    CharGap,
+    /// End of a word: 7 units of silence in total
+    WordGap,
+}
+
+/// Keying speed as the length of one Morse unit (one dot)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Speed {
+    unit_ms: u32,
+}
+
+impl Speed {
+    /// PARIS timing: a unit is 1200 / WPM milliseconds.
+    /// Returns `None` outside `MIN_WPM..=MAX_WPM`.
+    pub fn from_wpm(wpm: u32) -> Option<Self> {
+        if (MIN_WPM..=MAX_WPM).contains(&wpm) {
+            Some(Self { unit_ms: 1200 / wpm })
+        } else {
+            None
+        }
+    }
+
+    pub const fn from_unit_ms(unit_ms: u32) -> Self {
+        Self { unit_ms }
+    }
+
+    pub fn wpm(&self) -> u32 {
+        1200 / self.unit_ms.max(1)
+    }
+
+    pub fn unit_ms(&self) -> u32 {
+        self.unit_ms
+    }
+
+    fn units(&self, count: u32) -> Duration {
+        Duration::millis(u64::from(count * self.unit_ms))
+    }
+
+    /// How long the key is down for `element`
+    pub fn on(&self, element: Element) -> Duration {
+        match element {
+            Element::Dot => self.units(1),
+            Element::Dash => self.units(3),
+            Element::CharGap | Element::WordGap => Duration::from_ticks(0),
+        }
+    }
+
+    /// Silence after `element`
+    pub fn off(&self, element: Element) -> Duration {
+        match element {
+            Element::Dot | Element::Dash => self.units(1),
+            Element::CharGap => self.units(2),
+            Element::WordGap => self.units(4),
+        }
+    }
+}
+
+impl Default for Speed {
+    /// The original fixed timing: 250 ms dots, 750 ms dashes
+    fn default() -> Self {
+        Self::from_unit_ms(crate::DOT_DURATION.to_millis() as u32)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command<'a> {
+    /// Text to key out
+    Send(&'a str),
+    /// `/wpm N` with N in range
+    Wpm(u32),
+    /// `/abort`
+    Abort,
+    /// `/help`
+    Help,
+    /// Any other `/` command, without the slash
+    Shell(&'a str),
+    /// A keyer command with a bad argument
+    Invalid(&'a str),
+    /// Blank line
+    Empty,
+}
+
+/// Parses one complete input line
+pub fn parse_line(line: &str) -> Command<'_> {
+    let line = line.trim();
+    let Some(command) = line.strip_prefix('/') else {
+        return if line.is_empty() { Command::Empty } else { Command::Send(line) };
+    };
+
+    let mut words = command.split_whitespace();
+    let name = words.next().unwrap_or("");
+    let argument = words.next();
+    let extra = words.next();
+
+    match (name, argument, extra) {
+        ("wpm", Some(value), None) => match value.parse() {
+            Ok(wpm) if Speed::from_wpm(wpm).is_some() => Command::Wpm(wpm),
+            _ => Command::Invalid(line),
+        },
+        ("abort", None, _) => Command::Abort,
+        ("help", None, _) => Command::Help,
+        ("wpm" | "abort" | "help", _, _) => Command::Invalid(line),
+        _ => Command::Shell(command),
+    }
+}
+
+/// Collects typed bytes into lines, handling backspace
+pub struct LineEditor<const N: usize> {
+    line: String<N>,
+}
+
+impl<const N: usize> LineEditor<N> {
+    pub const fn new() -> Self {
+        Self { line: String::new() }
+    }
+
+    /// Feeds one byte. Returns the finished line on CR or LF.
+    /// Characters beyond the capacity and non-printable bytes are dropped.
+    pub fn push(&mut self, byte: u8) -> Option<String<N>> {
+        match byte {
+            b'\r' | b'\n' => Some(core::mem::take(&mut self.line)),
+            0x08 | 0x7F => {
+                self.line.pop();
+                None
+            }
+            0x20..=0x7E => {
+                let _ = self.line.push(byte as char);
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// The partial line typed so far
+    pub fn as_str(&self) -> &str {
+        self.line.as_str()
+    }
+}
+
+impl<const N: usize> Default for LineEditor<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returned when text does not fit in the queue. Nothing is queued.
+#[derive(Debug, PartialEq, Eq)]
+pub struct QueueFull;
+
+/// Elements waiting to be keyed
+pub struct KeyQueue<const N: usize> {
+    elements: Deque<Element, N>,
+}
+
+impl<const N: usize> KeyQueue<N> {
+    pub const fn new() -> Self {
+        Self { elements: Deque::new() }
+    }
+
+    /// Queues `text` followed by a word gap. Letters are keyed, spaces
+    /// become word gaps and anything else is skipped.
+    /// Returns the number of skipped characters.
+    pub fn push_text(&mut self, text: &str) -> Result<usize, QueueFull> {
+        let needed: usize = text.chars().filter_map(encode).map(|code| code.len() + 1).sum::<usize>() + 1;
+        if needed > N - self.elements.len() {
+            return Err(QueueFull);
+        }
+
+        let mut skipped = 0;
+        for c in text.chars() {
+            match encode(c) {
+                Some("") => self.push(Element::WordGap),
+                Some(code) => {
+                    for signal in code.bytes() {
+                        self.push(if signal == b'.' { Element::Dot } else { Element::Dash });
+                    }
+                    self.push(Element::CharGap);
+                }
+                None => skipped += 1,
+            }
+        }
+        self.push(Element::WordGap);
+        Ok(skipped)
+    }
+
+    pub fn pop(&mut self) -> Option<Element> {
+        self.elements.pop_front()
+    }
+
+    /// Drops everything still queued
+    pub fn clear(&mut self) {
+        self.elements.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    fn push(&mut self, element: Element) {
+        // Cannot fail: push_text checked there is room
+        let _ = self.elements.push_back(element);
+    }
+}
+
+impl<const N: usize> Default for KeyQueue<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Dot/dash code for a letter, `""` for a space, `None` if unsupported
+fn encode(c: char) -> Option<&'static str> {
+    match c {
+        'A'..='Z' => Some(MORSE_CODE[(c as u8 - b'A') as usize]),
+        'a'..='z' => Some(MORSE_CODE[(c as u8 - b'a') as usize]),
+        ' ' => Some(""),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+    use Element::*;
+
+    fn drain<const N: usize>(queue: &mut KeyQueue<N>) -> Vec<Element> {
+        core::iter::from_fn(|| queue.pop()).collect()
+    }
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(parse_line("  hello world \r"), Command::Send("hello world"));
+        assert_eq!(parse_line("/wpm 12"), Command::Wpm(12));
+        assert_eq!(parse_line("/abort"), Command::Abort);
+        assert_eq!(parse_line("/help"), Command::Help);
+        assert_eq!(parse_line(""), Command::Empty);
+        assert_eq!(parse_line("/wpm 0"), Command::Invalid("/wpm 0"));
+        assert_eq!(parse_line("/wpm fast"), Command::Invalid("/wpm fast"));
+        assert_eq!(parse_line("/wpm"), Command::Invalid("/wpm"));
+        assert_eq!(parse_line("/abort now"), Command::Invalid("/abort now"));
+        assert_eq!(parse_line("/set dot_freq 700"), Command::Shell("set dot_freq 700"));
+        assert_eq!(parse_line("/show"), Command::Shell("show"));
+    }
+
+    #[test]
+    fn editor_splits_lines_and_handles_backspace() {
+        let mut editor = LineEditor::<8>::new();
+        let lines: Vec<_> = b"SOX\x08S\r\nHI\x7f\x7fOK\n"
+            .iter()
+            .filter_map(|&b| editor.push(b))
+            .collect();
+        assert_eq!(lines, ["SOS", "", "OK"]);
+    }
+
+    #[test]
+    fn editor_drops_overflow() {
+        let mut editor = LineEditor::<4>::new();
+        for &b in b"ABCDEF" {
+            assert_eq!(editor.push(b), None);
+        }
+        assert_eq!(editor.as_str(), "ABCD");
+    }
+
+    #[test]
+    fn encodes_letters_and_spaces() {
+        let mut queue = KeyQueue::<32>::new();
+        assert_eq!(queue.push_text("Et a"), Ok(0));
+        assert_eq!(
+            drain(&mut queue),
+            [Dot, CharGap, Dash, CharGap, WordGap, Dot, Dash, CharGap, WordGap]
+        );
+    }
+
+    #[test]
+    fn skips_unsupported_characters() {
+        let mut queue = KeyQueue::<32>::new();
+        assert_eq!(queue.push_text("E1!"), Ok(2));
+        assert_eq!(drain(&mut queue), [Dot, CharGap, WordGap]);
+    }
+
+    #[test]
+    fn rejects_text_that_does_not_fit() {
+        let mut queue = KeyQueue::<8>::new();
+        assert_eq!(queue.push_text("SOS"), Err(QueueFull));
+        assert!(queue.is_empty());
+        assert_eq!(queue.push_text("EE"), Ok(0));
+        assert_eq!(queue.len(), 5);
+        assert_eq!(queue.push_text("TT"), Err(QueueFull));
+        queue.clear();
+        assert_eq!(queue.push_text("TT"), Ok(0));
+    }
+
+    #[test]
+    fn speed_follows_paris_timing() {
+        let speed = Speed::from_wpm(20).unwrap();
+        assert_eq!(speed.unit_ms(), 60);
+        assert_eq!(speed.on(Dash), Duration::millis(180));
+        assert_eq!(speed.off(Dot) + speed.off(CharGap), Duration::millis(180));
+        assert_eq!(speed.off(Dot) + speed.off(CharGap) + speed.off(WordGap), Duration::millis(420));
+        assert_eq!(Speed::from_wpm(MAX_WPM + 1), None);
+        assert_eq!(Speed::default().on(Dash), crate::DASH_DURATION);
+    }
+}
@@ -1,12 +1,35 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 
-use embedded_time::duration::Milliseconds;
+/// A reading of the 1 MHz TIMER counter. All times in this crate are
+/// these, so milliseconds and microseconds cannot be mixed up.
+pub type Instant = fugit::TimerInstantU64<1_000_000>;
+/// The difference between two [`Instant`]s
+pub type Duration = fugit::TimerDurationU64<1_000_000>;
 
 pub mod adc;
+pub mod async_io;
+pub mod bench;
+pub mod config;
+pub mod cycles;
+pub mod decoder;
+pub mod display;
+pub mod dma;
 pub mod gpio;
+pub mod intercore;
 pub mod interrupt;
+pub mod keyer;
+pub mod lockout;
+pub mod message;
+pub mod message_log;
+pub mod pio_gpio;
 pub mod pwm;
+pub mod shell;
+pub mod stack;
+pub mod storage;
+pub mod time_driver;
 pub mod uart;
+pub mod uart_loopback;
+pub mod usb;
 
 // Constants
 pub const UART_ID: u8 = 0;
@@ -14,13 +37,13 @@ pub const BAUD_RATE: u32 = 115200;
 pub const UART_TX_PIN: u8 = 0;
 pub const UART_RX_PIN: u8 = 1;
 
-pub const DOT_DURATION: Milliseconds<u32> = Milliseconds(250);
-pub const DASH_DURATION: Milliseconds<u32> = Milliseconds(750);
-pub const INTRA_CHAR_GAP: Milliseconds<u32> = Milliseconds(250);
-pub const INTER_CHAR_GAP: Milliseconds<u32> = Milliseconds(750);
-pub const WORD_GAP: Milliseconds<u32> = Milliseconds(1750);
-pub const MIN_SIGNAL_GAP: Milliseconds<u32> = Milliseconds(150);
-pub const MAX_CHAR_TIME: Milliseconds<u32> = Milliseconds(3000);
+pub const DOT_DURATION: Duration = Duration::millis(250);
+pub const DASH_DURATION: Duration = Duration::millis(750);
+pub const INTRA_CHAR_GAP: Duration = Duration::millis(250);
+pub const INTER_CHAR_GAP: Duration = Duration::millis(750);
+pub const WORD_GAP: Duration = Duration::millis(1750);
+pub const MIN_SIGNAL_GAP: Duration = Duration::millis(150);
+pub const MAX_CHAR_TIME: Duration = Duration::millis(3000);
 
 pub const BUTTON_PIN: u8 = 16;
 pub const LED_PIN: u8 = 15;
@@ -47,6 +70,8 @@ pub const SCL_PIN: u8 = 5;
 pub const LCD_ADDRESS: u8 = 0x27;
 pub const LCD_CHAR_WIDTH: usize = 16;
 pub const LCD_NUM_LINES: usize = 2;
+pub const OLED_ADDRESS: u8 = 0x3C; // SSD1306 on the same I2C0 bus
+
 // LCD Commands
 pub const LCD_CLEARDISPLAY: u8 = 0x01;
 pub const LCD_RETURNHOME: u8 = 0x02;
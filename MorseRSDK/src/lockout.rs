@@ -0,0 +1,124 @@
+//! # Flash Lockout
+//!
+//! Nothing may run from flash while it is erased or programmed, on
+//! either core. [`Rp2040Flash`](crate::storage::Rp2040Flash) keeps the
+//! core doing the write in RAM; this module parks the other one, like
+//! the Pico SDK's `multicore_lockout`. The core to be parked calls
+//! [`init_victim`] and binds its SIO interrupt:
+//!
+//! ```ignore
+//! #[interrupt]
+//! fn SIO_IRQ_PROC0() {
+//!     morse_rsdk::lockout::on_fifo_irq();
+//! }
+//! ```
+//!
+//! Before each write, [`pause_other_core`] pushes a word into that
+//! core's SIO FIFO. Its interrupt then waits in a loop in RAM, with
+//! interrupts off, until the write is done. Only the FIFO into the
+//! parked core is used, so the other direction stays free for
+//! [`CoreMessage`](crate::intercore::CoreMessage)s; nothing else may
+//! write into the parked core's FIFO.
+
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+use rp2040_hal::pac;
+use rp2040_hal::Sio;
+
+/// The FIFO word that asks for a pause, "LOCK"
+const PAUSE_REQUEST: u32 = u32::from_be_bytes(*b"LOCK");
+const NO_VICTIM: u8 = u8::MAX;
+
+/// The core that [`init_victim`] was called on
+static VICTIM: AtomicU8 = AtomicU8::new(NO_VICTIM);
+/// Set by the writing core for as long as the other one must stay parked
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+/// Set by the parked core while it waits in RAM
+static PARKED: AtomicBool = AtomicBool::new(false);
+
+fn this_core() -> u8 {
+    Sio::core() as u8
+}
+
+/// Lets the other core park this one during flash writes. Call on the
+/// core to be parked once core1 is running: spawning it talks over the
+/// same FIFO.
+pub fn init_victim() {
+    let core = this_core();
+    let sio = unsafe { &*pac::SIO::ptr() };
+    while sio.fifo_st().read().vld().bit_is_set() {
+        sio.fifo_rd().read();
+    }
+    sio.fifo_st().write(|w| w.wof().clear_bit_by_one().roe().clear_bit_by_one());
+    VICTIM.store(core, Ordering::Release);
+    unsafe {
+        pac::NVIC::unmask(match core {
+            0 => pac::Interrupt::SIO_IRQ_PROC0,
+            _ => pac::Interrupt::SIO_IRQ_PROC1,
+        });
+    }
+}
+
+/// Runs `f` with the other core parked in RAM if it called
+/// [`init_victim`], or just runs it if not
+pub fn pause_other_core<R>(f: impl FnOnce() -> R) -> R {
+    let victim = VICTIM.load(Ordering::Acquire);
+    if victim == NO_VICTIM || victim == this_core() {
+        return f();
+    }
+    let sio = unsafe { &*pac::SIO::ptr() };
+    REQUESTED.store(true, Ordering::Release);
+    while !sio.fifo_st().read().rdy().bit_is_set() {
+        core::hint::spin_loop();
+    }
+    sio.fifo_wr().write(|w| unsafe { w.bits(PAUSE_REQUEST) });
+    cortex_m::asm::sev();
+    while !PARKED.load(Ordering::Acquire) {
+        core::hint::spin_loop();
+    }
+
+    let result = f();
+
+    REQUESTED.store(false, Ordering::Release);
+    // Otherwise the next pause could take this one's flag for its own
+    while PARKED.load(Ordering::Acquire) {
+        core::hint::spin_loop();
+    }
+    result
+}
+
+/// Handles the parked core's SIO interrupt
+#[cfg(target_arch = "arm")]
+pub fn on_fifo_irq() {
+    let sio = unsafe { &*pac::SIO::ptr() };
+    // The error flags raise the interrupt too
+    sio.fifo_st().write(|w| w.wof().clear_bit_by_one().roe().clear_bit_by_one());
+    while sio.fifo_st().read().vld().bit_is_set() {
+        if sio.fifo_rd().read().bits() == PAUSE_REQUEST {
+            let (requested, parked) = (REQUESTED.as_ptr(), PARKED.as_ptr());
+            // Safety: both point to statics, which live in RAM
+            cortex_m::interrupt::free(|_| unsafe { park(requested, parked) });
+        }
+    }
+}
+
+/// Sets `parked`, waits for `requested` to clear, then clears `parked`.
+/// Assembly, so that not even a call at `opt-level = 0` reaches flash.
+#[cfg(target_arch = "arm")]
+#[inline(never)]
+#[link_section = ".data.ram_func"]
+unsafe fn park(requested: *const bool, parked: *mut bool) {
+    core::arch::asm!(
+        "movs r2, #1",
+        "strb r2, [r1]",
+        "2:",
+        "ldrb r2, [r0]",
+        "cmp r2, #0",
+        "bne 2b",
+        "strb r2, [r1]",
+        in("r0") requested,
+        in("r1") parked,
+        out("r2") _,
+        options(nostack),
+    );
+}
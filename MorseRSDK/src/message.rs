@@ -0,0 +1,178 @@
+//! # Received Message Buffer
+//!
+//! Fixed-capacity ring of decoded characters for the receiver.
+//! Appending is O(1): once the ring is full the oldest character is
+//! dropped instead of shifting the whole message down by one.
+
+use core::fmt;
+use heapless::{Deque, String};
+
+/// Ring of the most recent `N` decoded characters.
+///
+/// Characters are stored as ASCII bytes; anything outside ASCII is
+/// stored as `?`, matching what `decode_morse` reports for unknown codes.
+pub struct MessageRing<const N: usize> {
+    chars: Deque<u8, N>,
+}
+
+impl<const N: usize> MessageRing<N> {
+    pub const fn new() -> Self {
+        Self { chars: Deque::new() }
+    }
+
+    /// Appends a character, dropping the oldest one if the ring is full.
+    pub fn push(&mut self, c: char) {
+        let byte = if c.is_ascii() { c as u8 } else { b'?' };
+        if self.chars.is_full() {
+            self.chars.pop_front();
+        }
+        // Cannot fail: a slot was freed above if the ring was full
+        let _ = self.chars.push_back(byte);
+    }
+
+    pub fn len(&self) -> usize {
+        self.chars.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chars.is_empty()
+    }
+
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    pub fn clear(&mut self) {
+        self.chars.clear();
+    }
+
+    /// Iterates over the whole message, oldest character first.
+    pub fn chars(&self) -> impl Iterator<Item = char> + '_ {
+        self.chars.iter().map(|&b| b as char)
+    }
+
+    /// View of the last `width` characters (or fewer if the message is shorter).
+    pub fn window(&self, width: usize) -> Window<'_, N> {
+        let skip = self.chars.len().saturating_sub(width);
+        Window { ring: self, skip }
+    }
+
+    /// Copies the last `W` characters into a string, e.g. one LCD line.
+    pub fn tail<const W: usize>(&self) -> String<W> {
+        let mut text = String::new();
+        for c in self.window(W).chars() {
+            // Cannot fail: the window is at most W characters long
+            let _ = text.push(c);
+        }
+        text
+    }
+}
+
+impl<const N: usize> Default for MessageRing<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Trailing slice of a [`MessageRing`], printable with `write!`.
+pub struct Window<'a, const N: usize> {
+    ring: &'a MessageRing<N>,
+    skip: usize,
+}
+
+impl<'a, const N: usize> Window<'a, N> {
+    pub fn chars(&self) -> impl Iterator<Item = char> + 'a {
+        self.ring.chars().skip(self.skip)
+    }
+
+    pub fn len(&self) -> usize {
+        self.ring.len() - self.skip
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<const N: usize> fmt::Display for Window<'_, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.chars() {
+            fmt::Write::write_char(f, c)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ring_with<const N: usize>(text: &str) -> MessageRing<N> {
+        let mut ring = MessageRing::new();
+        for c in text.chars() {
+            ring.push(c);
+        }
+        ring
+    }
+
+    #[test]
+    fn keeps_everything_below_capacity() {
+        let ring: MessageRing<8> = ring_with("SOS");
+        assert_eq!(ring.len(), 3);
+        assert_eq!(ring.chars().collect::<std::string::String>(), "SOS");
+    }
+
+    #[test]
+    fn drops_oldest_on_wraparound() {
+        let ring: MessageRing<4> = ring_with("ABCDEF");
+        assert_eq!(ring.len(), 4);
+        assert_eq!(ring.chars().collect::<std::string::String>(), "CDEF");
+    }
+
+    #[test]
+    fn wraps_many_times() {
+        let mut ring: MessageRing<5> = MessageRing::new();
+        for i in 0..1000u32 {
+            ring.push((b'A' + (i % 26) as u8) as char);
+        }
+        // 995..1000 -> offsets 7..12 -> "HIJKL"
+        assert_eq!(ring.chars().collect::<std::string::String>(), "HIJKL");
+    }
+
+    #[test]
+    fn window_is_trailing_slice() {
+        let ring: MessageRing<8> = ring_with("HELLO WORLD");
+        assert_eq!(std::format!("{}", ring.window(5)), "WORLD");
+        assert_eq!(ring.window(5).len(), 5);
+        assert_eq!(std::format!("{}", ring.window(32)), "LO WORLD");
+    }
+
+    #[test]
+    fn window_of_empty_ring() {
+        let ring: MessageRing<4> = MessageRing::new();
+        assert!(ring.window(16).is_empty());
+        assert_eq!(ring.tail::<16>().as_str(), "");
+    }
+
+    #[test]
+    fn tail_after_wraparound() {
+        let ring: MessageRing<6> = ring_with("MORSE CODE");
+        assert_eq!(ring.tail::<4>().as_str(), "CODE");
+        assert_eq!(ring.tail::<16>().as_str(), "E CODE");
+    }
+
+    #[test]
+    fn non_ascii_is_replaced() {
+        let ring: MessageRing<4> = ring_with("Aé");
+        assert_eq!(ring.tail::<4>().as_str(), "A?");
+    }
+
+    #[test]
+    fn clear_empties_ring() {
+        let mut ring: MessageRing<4> = ring_with("ABCDE");
+        ring.clear();
+        assert!(ring.is_empty());
+        ring.push('Z');
+        assert_eq!(ring.tail::<4>().as_str(), "Z");
+    }
+}
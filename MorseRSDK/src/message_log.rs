@@ -0,0 +1,378 @@
+//! # Flash Message Log
+//!
+//! Decoded words kept across power cycles. The receiver collects
+//! finished words in RAM and appends them, as many as fit, to a circular
+//! log of [records](crate::storage) in the flash sectors below the
+//! configuration area:
+//!
+//! ```text
+//! 0  boot       u32  start-up number
+//! then, for each word:
+//!    uptime_ms  u32  time since that start-up when the word began
+//!    length     u8   text bytes
+//!    text       [..] the word, ASCII
+//! ```
+//!
+//! A 240-byte payload holds six of the longest words, or about twenty
+//! typical ones, so the 64K log keeps several thousand words.
+//!
+//! Words are only written by [`MessageLog::flush`], which the receiver
+//! calls once the link has gone quiet: an erase or program stops
+//! everything running from flash. A power cut loses the words still
+//! waiting in RAM, and one while appending loses at most the record
+//! being written, as a record only counts once its trailing CRC is in
+//! flash. When the log is full, the sector holding the oldest words is
+//! erased; a cut during that erase loses only words from that sector.
+
+use core::fmt::{self, Write};
+
+use heapless::{Deque, String, Vec};
+
+use crate::storage::{Flash, Record, RecordStore, StorageError, LOG_OFFSET, LOG_SECTORS, MAX_PAYLOAD};
+use crate::{Duration, Instant};
+
+/// Payload format of message log records, see the module docs
+pub const LOG_RECORD_VERSION: u16 = 2;
+/// Longest word kept. Longer words are cut short.
+pub const MAX_WORD: usize = 32;
+/// How long the link should be quiet before the receivers
+/// [flush](MessageLog::flush) the log: longer than the decoder's word
+/// timeout, so the last word is finished. Bytes arriving during a write
+/// could overrun the UART FIFO.
+pub const LOG_IDLE: Duration = Duration::secs(3);
+/// Finished words held in RAM. When a word finds the queue full,
+/// [`MessageLog::push`] writes the queue out itself.
+pub const PENDING_WORDS: usize = 32;
+
+const BOOT_SIZE: usize = 4;
+const ENTRY_HEADER: usize = 5;
+const _: () = assert!(BOOT_SIZE + ENTRY_HEADER + MAX_WORD <= MAX_PAYLOAD);
+
+/// One logged word
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub boot: u32,
+    pub uptime_ms: u32,
+    pub text: String<MAX_WORD>,
+}
+
+impl Entry {
+    /// Calls `f` for each word in `record`, stopping at the first one
+    /// that does not parse
+    fn parse_all(record: &Record, mut f: impl FnMut(&Entry)) {
+        let payload = &record.payload;
+        if record.version != LOG_RECORD_VERSION || payload.len() < BOOT_SIZE {
+            return;
+        }
+        let word = |at: usize| u32::from_le_bytes([payload[at], payload[at + 1], payload[at + 2], payload[at + 3]]);
+        let boot = word(0);
+        let mut at = BOOT_SIZE;
+        while at + ENTRY_HEADER <= payload.len() {
+            let end = at + ENTRY_HEADER + usize::from(payload[at + 4]);
+            let Some(text) = payload.get(at + ENTRY_HEADER..end) else {
+                return;
+            };
+            let Some(text) = core::str::from_utf8(text).ok().and_then(|text| String::try_from(text).ok()) else {
+                return;
+            };
+            f(&Entry { boot, uptime_ms: word(at), text });
+            at = end;
+        }
+    }
+
+    fn encoded_len(&self) -> usize {
+        ENTRY_HEADER + self.text.len()
+    }
+}
+
+/// `#<boot> <seconds>.<millis>s <word>`, e.g. `#3 12.345s HELLO`
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{} {}.{:03}s {}", self.boot, self.uptime_ms / 1000, self.uptime_ms % 1000, self.text)
+    }
+}
+
+/// Collects decoded characters into words and appends them to flash
+pub struct MessageLog<F> {
+    records: RecordStore<F>,
+    boot: u32,
+    word: String<MAX_WORD>,
+    word_start: Instant,
+    /// Finished words not yet in flash, oldest first
+    pending: Deque<Entry, PENDING_WORDS>,
+}
+
+impl<F: Flash> MessageLog<F> {
+    /// Uses the reserved log sectors below the configuration area
+    pub fn new(flash: F) -> Result<Self, F::Error> {
+        Self::with_area(flash, LOG_OFFSET, LOG_SECTORS)
+    }
+
+    /// Scans the log and numbers this start-up one after the newest
+    /// logged word's. Start-ups that logged nothing are not counted.
+    pub fn with_area(flash: F, offset: u32, sectors: u32) -> Result<Self, F::Error> {
+        let mut records = RecordStore::new(flash, offset, sectors)?;
+        let mut last_boot = None;
+        records.for_each(|record| Entry::parse_all(record, |entry| last_boot = Some(entry.boot)))?;
+
+        Ok(Self {
+            records,
+            boot: last_boot.map_or(0, |boot| boot.wrapping_add(1)),
+            word: String::new(),
+            word_start: Instant::from_ticks(0),
+            pending: Deque::new(),
+        })
+    }
+
+    /// Number of this start-up, as written in its entries
+    pub fn boot(&self) -> u32 {
+        self.boot
+    }
+
+    /// Finished words waiting for [`flush`](Self::flush)
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Adds one decoded character. A space ends the word and queues it.
+    /// Non-ASCII characters are stored as `?`. `now` is the time since
+    /// start-up. Only writes to flash if the queue is full.
+    pub fn push(&mut self, c: char, now: Instant) -> Result<(), StorageError<F::Error>> {
+        if c == ' ' {
+            return self.end_word();
+        }
+        if self.word.is_empty() {
+            self.word_start = now;
+        }
+        let _ = self.word.push(if c.is_ascii() { c } else { '?' });
+        Ok(())
+    }
+
+    /// Queues the word in progress, if there is one
+    fn end_word(&mut self) -> Result<(), StorageError<F::Error>> {
+        if self.word.is_empty() {
+            return Ok(());
+        }
+        let result = if self.pending.is_full() { self.write_pending() } else { Ok(()) };
+        let entry = Entry {
+            boot: self.boot,
+            uptime_ms: self.word_start.duration_since_epoch().to_millis() as u32,
+            text: core::mem::take(&mut self.word),
+        };
+        // Cannot fail: the queue was emptied above if it was full
+        let _ = self.pending.push_back(entry);
+        result
+    }
+
+    /// Appends the queued words and the word in progress, packed into as
+    /// few records as they fit. Words in a record that fails to write
+    /// are dropped from RAM.
+    pub fn flush(&mut self) -> Result<(), StorageError<F::Error>> {
+        self.end_word()?;
+        self.write_pending()
+    }
+
+    fn write_pending(&mut self) -> Result<(), StorageError<F::Error>> {
+        while !self.pending.is_empty() {
+            let mut payload = Vec::<u8, MAX_PAYLOAD>::new();
+            // Cannot fail: every word is checked to fit before it is added
+            let _ = payload.extend_from_slice(&self.boot.to_le_bytes());
+            while let Some(entry) = self.pending.front() {
+                if payload.len() + entry.encoded_len() > MAX_PAYLOAD {
+                    break;
+                }
+                let _ = payload.extend_from_slice(&entry.uptime_ms.to_le_bytes());
+                let _ = payload.push(entry.text.len() as u8);
+                let _ = payload.extend_from_slice(entry.text.as_bytes());
+                self.pending.pop_front();
+            }
+            self.records.append(LOG_RECORD_VERSION, &payload)?;
+        }
+        Ok(())
+    }
+
+    /// Calls `f` for every logged word, oldest first, then for the words
+    /// still waiting in RAM
+    pub fn for_each(&mut self, mut f: impl FnMut(&Entry)) -> Result<(), F::Error> {
+        self.records.for_each(|record| Entry::parse_all(record, &mut f))?;
+        self.pending.iter().for_each(f);
+        Ok(())
+    }
+
+    /// Writes every logged word to `out`, oldest first, one per CRLF
+    /// line, and returns how many there were. Output is best effort.
+    pub fn dump<W: Write>(&mut self, out: &mut W) -> Result<usize, F::Error> {
+        let mut count = 0;
+        self.for_each(|entry| {
+            let _ = write!(out, "{}\r\n", entry);
+            count += 1;
+        })?;
+        Ok(count)
+    }
+
+    pub fn release(self) -> F {
+        self.records.release()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::tests::{NorError, NorFlash};
+    use crate::storage::{PAGE_SIZE, SECTOR_SIZE};
+    use std::string::{String as StdString, ToString};
+    use std::vec::Vec as StdVec;
+
+    const AREA: u32 = 2 * SECTOR_SIZE;
+
+    fn mount(flash: NorFlash) -> MessageLog<NorFlash> {
+        MessageLog::with_area(flash, 0, 2).unwrap()
+    }
+
+    fn words(log: &mut MessageLog<NorFlash>) -> StdVec<StdString> {
+        let mut words = StdVec::new();
+        log.for_each(|e| words.push(e.text.to_string())).unwrap();
+        words
+    }
+
+    fn at_ms(ms: u32) -> Instant {
+        Instant::from_ticks(u64::from(ms) * 1000)
+    }
+
+    fn type_text(log: &mut MessageLog<NorFlash>, text: &str, start_ms: u32) -> Result<(), StorageError<NorError>> {
+        for (i, c) in text.chars().enumerate() {
+            log.push(c, at_ms(start_ms + 100 * i as u32))?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn words_survive_restart_with_boot_numbers() {
+        let mut log = mount(NorFlash::new(AREA));
+        assert_eq!(log.boot(), 0);
+        type_text(&mut log, "HI THERE ", 1500).unwrap();
+        log.flush().unwrap();
+
+        let mut log = mount(log.release());
+        assert_eq!(log.boot(), 1);
+        type_text(&mut log, "AGAIN", 62_000).unwrap();
+        log.flush().unwrap();
+
+        let mut out = StdString::new();
+        assert_eq!(log.dump(&mut out), Ok(3));
+        assert_eq!(out, "#0 1.500s HI\r\n#0 1.800s THERE\r\n#1 62.000s AGAIN\r\n");
+    }
+
+    #[test]
+    fn words_wait_in_ram_until_flushed() {
+        let mut log = mount(NorFlash::new(AREA));
+        type_text(&mut log, "HI THERE ", 0).unwrap();
+        assert_eq!(log.pending(), 2);
+        assert_eq!(words(&mut log), ["HI", "THERE"]);
+        assert!(log.records.release().data.iter().all(|&b| b == 0xFF));
+    }
+
+    #[test]
+    fn words_are_packed_into_shared_records() {
+        let mut log = mount(NorFlash::new(AREA));
+        // 4 bytes of boot, then 5 + 4 per word: 26 words to a record
+        type_text(&mut log, &"WORD ".repeat(30), 0).unwrap();
+        log.flush().unwrap();
+        assert_eq!(log.pending(), 0);
+        assert_eq!(words(&mut log).len(), 30);
+
+        let flash = log.release();
+        let used = |page: usize| flash.data[page * PAGE_SIZE as usize] != 0xFF;
+        assert_eq!((used(0), used(1), used(2)), (true, true, false));
+    }
+
+    #[test]
+    fn full_queue_is_written_without_flush() {
+        let mut log = mount(NorFlash::new(AREA));
+        type_text(&mut log, &"E ".repeat(PENDING_WORDS + 1), 0).unwrap();
+        assert_eq!(log.pending(), 1);
+        assert_eq!(words(&mut mount(log.release())).len(), PENDING_WORDS);
+    }
+
+    #[test]
+    fn spaces_alone_write_nothing() {
+        let mut log = mount(NorFlash::new(AREA));
+        type_text(&mut log, "   ", 0).unwrap();
+        log.flush().unwrap();
+        assert!(words(&mut log).is_empty());
+        assert_eq!(mount(log.release()).boot(), 0);
+    }
+
+    #[test]
+    fn long_words_are_cut_short() {
+        let mut log = mount(NorFlash::new(AREA));
+        let long: StdString = core::iter::repeat_n('E', MAX_WORD + 10).collect();
+        type_text(&mut log, &long, 0).unwrap();
+        log.flush().unwrap();
+        assert_eq!(words(&mut log), [long[..MAX_WORD].to_string()]);
+    }
+
+    #[test]
+    fn power_cut_at_any_byte_keeps_committed_words() {
+        // Header, boot, word header, "TWO" and CRC. The rest of the page
+        // is padding, so a cut after the CRC still leaves a complete record.
+        let record_len = 12 + BOOT_SIZE + ENTRY_HEADER + 3 + 4;
+        for budget in (0..=record_len + 4).chain([PAGE_SIZE as usize]) {
+            let mut log = mount(NorFlash::new(AREA));
+            type_text(&mut log, "ONE ", 0).unwrap();
+            log.flush().unwrap();
+
+            let mut flash = log.release();
+            flash.power_budget = Some(budget);
+            let mut log = mount(flash);
+            type_text(&mut log, "TWO ", 1000).unwrap();
+            assert_eq!(log.flush().is_ok(), budget == PAGE_SIZE as usize);
+
+            let mut flash = log.release();
+            flash.power_budget = None;
+            let mut log = mount(flash);
+            if budget < record_len {
+                assert_eq!(words(&mut log), ["ONE"], "budget {}", budget);
+            } else {
+                assert_eq!(words(&mut log), ["ONE", "TWO"], "budget {}", budget);
+            }
+
+            // Appending carries on past a torn page
+            type_text(&mut log, "THREE ", 2000).unwrap();
+            log.flush().unwrap();
+            assert_eq!(words(&mut mount(log.release())).last().unwrap(), "THREE");
+        }
+    }
+
+    #[test]
+    fn power_cut_during_erase_loses_only_oldest_sector() {
+        let slots = AREA / PAGE_SIZE;
+        let per_sector = SECTOR_SIZE / PAGE_SIZE;
+        let mut log = mount(NorFlash::new(AREA));
+        for i in 0..slots {
+            log.push('E', at_ms(i)).unwrap();
+            log.flush().unwrap();
+        }
+        assert_eq!(words(&mut log).len(), slots as usize);
+
+        // The next word needs the first sector erased
+        let mut flash = log.release();
+        flash.power_budget = Some(0);
+        let mut log = mount(flash);
+        log.push('T', at_ms(0)).unwrap();
+        assert_eq!(log.flush(), Err(StorageError::Flash(NorError::PowerLoss)));
+
+        // Half the sector was erased: its second half and the other
+        // sector are still there
+        let mut flash = log.release();
+        flash.power_budget = None;
+        let mut log = mount(flash);
+        assert_eq!(words(&mut log).len(), (slots - per_sector / 2) as usize);
+
+        log.push('T', at_ms(0)).unwrap();
+        log.flush().unwrap();
+        let words = words(&mut log);
+        assert_eq!(words.len(), (slots - per_sector + 1) as usize);
+        assert_eq!(words.last().unwrap(), "T");
+    }
+}
@@ -0,0 +1,421 @@
+//! # PIO Benchmark Functions for RP2040
+//!
+//! Drives GPIO from PIO0's state machine 0 and compares it with the SIO:
+//!
+//! - `gpio` / `toggle_pio`: the same 1000 toggles as the SIO benchmarks,
+//!   two system clock cycles each, started with one FIFO write
+//! - `keying` / `pio` and `sio`: the sidetone and keying of "PARIS" at
+//!   20 WPM, one sample per tone or silence, timed against its nominal
+//!   length
+//!
+//! The keying program runs at 1 µs a PIO cycle and takes one word per
+//! element from the TX FIFO, laid out LSB first:
+//!
+//! | Bits  | Field                                       |
+//! |-------|---------------------------------------------|
+//! | 0     | 1 for a tone, 0 for silence                 |
+//! | 1-11  | (Period - [`PERIOD_OVERHEAD_US`]) / 2       |
+//! | 12-31 | Periods less one                            |
+//!
+//! It pushes a word to the RX FIFO as it starts each element, which the
+//! CPU timestamps. `cpu_time` counts the cycles spent reading that word
+//! and writing the next element, not the polling in between, which an
+//! interrupt would hand back to the application. The SIO keys as the
+//! transmitter does, so the CPU is busy for the whole element.
+//!
+//! Designed to be called from a central benchmark runner.
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::OutputPin;
+use heapless::Vec;
+use rp2040_hal as hal;
+use rtt_target::rprintln;
+use hal::gpio::{FunctionPio0, Pin, PinId, PullType};
+use hal::pac::{PIO0, RESETS};
+use hal::pio::{PIOBuilder, PIOExt, PinDir, PinState};
+
+use crate::bench::{Metric, Run, Samples};
+use crate::cycles::CycleCounter;
+use crate::keyer::{Element, KeyQueue, Speed};
+use crate::{DASH_FREQ, DOT_FREQ};
+
+/// The standard word for timing Morse
+const KEYING_TEXT: &str = "PARIS";
+const KEYING_WPM: u32 = 20;
+/// Times "PARIS" is keyed by each method
+const KEYING_REPETITIONS: usize = 3;
+/// Tones and silences of the keyed text, with room to spare
+const MAX_SEGMENTS: usize = 48;
+/// Silence is counted out in periods of this length
+const SILENCE_PERIOD_US: u32 = 1000;
+/// PIO cycles of each period outside its two delay loops
+const PERIOD_OVERHEAD_US: u32 = 8;
+/// A single 8 µs period of silence, written after the last element so
+/// that its end is pushed too
+const END_WORD: u32 = 0;
+
+/// One stretch of the keying waveform
+#[derive(Clone, Copy)]
+struct Segment {
+    /// 0 for silence
+    tone_hz: u32,
+    duration_us: u32,
+}
+
+/// "PARIS" as the transmitter keys it: dots at [`DOT_FREQ`], dashes at
+/// [`DASH_FREQ`], each followed by its silence
+fn keying_segments() -> Vec<Segment, MAX_SEGMENTS> {
+    let speed = Speed::from_wpm(KEYING_WPM).unwrap();
+    let mut queue = KeyQueue::<32>::new();
+    let _ = queue.push_text(KEYING_TEXT);
+
+    let mut segments = Vec::new();
+    while let Some(element) = queue.pop() {
+        let tone_hz = match element {
+            Element::Dot => DOT_FREQ,
+            Element::Dash => DASH_FREQ,
+            Element::CharGap | Element::WordGap => 0,
+        };
+        let on = speed.on(element).to_micros() as u32;
+        if on > 0 {
+            let _ = segments.push(Segment { tone_hz, duration_us: on });
+        }
+        let off = speed.off(element).to_micros() as u32;
+        let _ = segments.push(Segment { tone_hz: 0, duration_us: off });
+    }
+    segments
+}
+
+/// `count` toggles on the side-set pin, two cycles each. The count less
+/// one comes in through the TX FIFO and a word goes back through the RX
+/// FIFO when the toggles are done.
+fn toggle_program() -> pio::Program<{ pio::RP2040_MAX_PROGRAM_SIZE }> {
+    let mut a = pio::Assembler::new_with_side_set(pio::SideSet::new(false, 1, false));
+    let mut toggle = a.label();
+    a.pull_with_side_set(false, true, 0);
+    a.out_with_side_set(pio::OutDestination::X, 32, 0);
+    a.bind(&mut toggle);
+    a.nop_with_side_set(1);
+    a.jmp_with_side_set(pio::JmpCondition::XDecNonZero, &mut toggle, 0);
+    a.push_with_side_set(false, true, 0);
+    a.assemble_program()
+}
+
+/// Plays one element word per pull, as laid out in the module docs. A
+/// period is two delay loops of `half + 1` cycles plus
+/// [`PERIOD_OVERHEAD_US`]; starting an element takes 6 more cycles, and
+/// ending a tone 1.
+fn keying_program() -> pio::Program<{ pio::RP2040_MAX_PROGRAM_SIZE }> {
+    use pio::{JmpCondition, MovDestination, MovOperation, MovSource, OutDestination, SetDestination};
+
+    let mut a = pio::Assembler::<{ pio::RP2040_MAX_PROGRAM_SIZE }>::new();
+    let mut top = a.label();
+    let mut tone = a.label();
+    let mut tone_high = a.label();
+    let mut tone_low = a.label();
+    let mut silence = a.label();
+    let mut silence_first = a.label();
+    let mut silence_second = a.label();
+
+    a.bind(&mut top);
+    a.pull(false, true);
+    a.push(false, false);
+    a.out(OutDestination::Y, 1);
+    a.out(OutDestination::ISR, 11);
+    a.out(OutDestination::X, 20);
+    a.jmp(JmpCondition::YIsZero, &mut silence);
+
+    a.bind(&mut tone);
+    a.set(SetDestination::PINS, 1);
+    a.mov(MovDestination::Y, MovOperation::None, MovSource::ISR);
+    a.bind(&mut tone_high);
+    a.jmp(JmpCondition::YDecNonZero, &mut tone_high);
+    a.set(SetDestination::PINS, 0);
+    a.mov(MovDestination::Y, MovOperation::None, MovSource::ISR);
+    a.bind(&mut tone_low);
+    a.jmp(JmpCondition::YDecNonZero, &mut tone_low);
+    a.nop();
+    a.jmp(JmpCondition::XDecNonZero, &mut tone);
+    a.jmp(JmpCondition::Always, &mut top);
+
+    // The same loop with the pin left low
+    a.bind(&mut silence);
+    a.nop();
+    a.mov(MovDestination::Y, MovOperation::None, MovSource::ISR);
+    a.bind(&mut silence_first);
+    a.jmp(JmpCondition::YDecNonZero, &mut silence_first);
+    a.nop();
+    a.mov(MovDestination::Y, MovOperation::None, MovSource::ISR);
+    a.bind(&mut silence_second);
+    a.jmp(JmpCondition::YDecNonZero, &mut silence_second);
+    a.nop();
+    a.jmp(JmpCondition::XDecNonZero, &mut silence);
+    // Wraps back to `top`
+    a.assemble_program()
+}
+
+/// The TX FIFO word for `segment`. Tone periods are rounded down to an
+/// even number of microseconds.
+fn keying_word(segment: &Segment) -> u32 {
+    let (key, period_us) = match segment.tone_hz {
+        0 => (0, SILENCE_PERIOD_US),
+        tone_hz => (1, (1_000_000 / tone_hz) & !1),
+    };
+    let half = (period_us - PERIOD_OVERHEAD_US) / 2;
+    let periods = (segment.duration_us / period_us).max(1);
+    key | (half << 1) | ((periods - 1) << 12)
+}
+
+/// Prints a sample per element from the element start `times`, the last
+/// being the end of the last element. `busy` is the CPU cycles each
+/// element took, or `None` if the CPU was busy throughout.
+fn report_elements(
+    run: &Run,
+    samples: &mut Samples,
+    cycles: &CycleCounter,
+    segments: &[Segment],
+    repetition: usize,
+    times: &[u64],
+    busy: Option<&[u64]>,
+) {
+    let cycles_per_micro = cycles.cycles_per_micro();
+    for (index, segment) in segments.iter().enumerate() {
+        let element_cycles = times[index + 1] - times[index];
+        let nominal_cycles = u64::from(segment.duration_us) * u64::from(cycles_per_micro);
+        let error_cycles = element_cycles as i64 - nominal_cycles as i64;
+        let cpu_cycles = busy.map_or(element_cycles, |busy| busy[index]);
+
+        samples.record(error_cycles.unsigned_abs() as u32);
+        run.sample((repetition * segments.len() + index) as u32, &[
+            Metric::new("element_time", cycles.to_micros(element_cycles), "us"),
+            Metric::new("error", error_cycles as f32 / cycles_per_micro as f32, "us"),
+            Metric::new("error", error_cycles as f32, "cycles"),
+            Metric::new("cpu_time", cpu_cycles, "cycles"),
+            Metric::new("cpu_load", 100.0 * cpu_cycles as f32 / element_cycles as f32, "%"),
+        ]);
+    }
+}
+
+/// Toggles the pin from PIO0 state machine 0. The CPU only writes the
+/// toggle count; `cpu_time` is that write.
+pub fn benchmark_gpio_toggle_pio<I: PinId, P: PullType>(
+    cycles: &CycleCounter,
+    pio0: PIO0,
+    pin: &Pin<I, FunctionPio0, P>,
+    resets: &mut RESETS,
+    delay: &mut impl DelayNs,
+) -> PIO0 {
+    const NUM_ITERATIONS: usize = 100;
+    const TOGGLE_COUNT: usize = 1000; // One toggle = set high + set low
+
+    let run = Run {
+        task: "gpio",
+        method: "toggle_pio",
+        params: &[("toggles", TOGGLE_COUNT as u32)],
+        clock_hz: cycles.system_clock_hz(),
+    };
+
+    let pin_number = pin.id().num;
+    let (mut pio, sm0, sm1, sm2, sm3) = pio0.split(resets);
+    let installed = pio.install(&toggle_program()).unwrap();
+    let (mut sm, mut rx, mut tx) = PIOBuilder::from_installed_program(installed)
+        .side_set_pin_base(pin_number)
+        .build(sm0);
+    sm.set_pins([(pin_number, PinState::Low)]);
+    sm.set_pindirs([(pin_number, PinDir::Output)]);
+    let sm = sm.start();
+
+    let mut samples = Samples::new("cycles");
+    for i in 0..NUM_ITERATIONS {
+        let start = cycles.now();
+
+        tx.write(TOGGLE_COUNT as u32 - 1);
+        let cpu_cycles = cycles.since(start);
+        while rx.read().is_none() {
+            core::hint::spin_loop();
+        }
+
+        let duration_cycles = cycles.since(start);
+        let duration_us = cycles.to_micros(duration_cycles);
+
+        let avg_cycles = duration_cycles as f32 / TOGGLE_COUNT as f32;
+        let avg_us = avg_cycles / cycles.cycles_per_micro() as f32;
+
+        samples.record(duration_cycles as u32);
+        run.sample(i as u32, &[
+            Metric::new("total_time", duration_us, "us"),
+            Metric::new("avg_toggle", avg_us, "us"),
+            Metric::new("total_time", duration_cycles, "cycles"),
+            Metric::new("avg_toggle", avg_cycles, "cycles"),
+            Metric::new("cpu_time", cpu_cycles, "cycles"),
+        ]);
+
+        delay.delay_ms(100);
+    }
+    run.summary(&samples);
+
+    // Give PIO0 back; the next split resets it
+    let (sm0, installed) = sm.stop().uninit(rx, tx);
+    pio.uninstall(installed);
+    let pio0 = pio.free(sm0, sm1, sm2, sm3);
+
+    rprintln!("GPIO PIO benchmark finished.");
+    pio0
+}
+
+/// Keys "PARIS" from PIO0 state machine 0, feeding it one element word
+/// at a time. The summary is of the absolute timing error in cycles.
+pub fn benchmark_keying_pio<I: PinId, P: PullType>(
+    cycles: &CycleCounter,
+    pio0: PIO0,
+    pin: &Pin<I, FunctionPio0, P>,
+    resets: &mut RESETS,
+    delay: &mut impl DelayNs,
+) -> PIO0 {
+    let run = Run {
+        task: "keying",
+        method: "pio",
+        params: &[("wpm", KEYING_WPM)],
+        clock_hz: cycles.system_clock_hz(),
+    };
+
+    let segments = keying_segments();
+    let mut words: Vec<u32, { MAX_SEGMENTS + 1 }> = segments.iter().map(keying_word).collect();
+    let _ = words.push(END_WORD);
+
+    let pin_number = pin.id().num;
+    let (mut pio, sm0, sm1, sm2, sm3) = pio0.split(resets);
+    let installed = pio.install(&keying_program()).unwrap();
+    let (mut sm, mut rx, mut tx) = PIOBuilder::from_installed_program(installed)
+        .set_pins(pin_number, 1)
+        // One PIO cycle per microsecond
+        .clock_divisor_fixed_point(cycles.cycles_per_micro() as u16, 0)
+        .build(sm0);
+    sm.set_pins([(pin_number, PinState::Low)]);
+    sm.set_pindirs([(pin_number, PinDir::Output)]);
+    let sm = sm.start();
+
+    let mut samples = Samples::new("cycles");
+    for repetition in 0..KEYING_REPETITIONS {
+        let mut times: Vec<u64, { MAX_SEGMENTS + 1 }> = Vec::new();
+        let mut busy: Vec<u64, { MAX_SEGMENTS + 1 }> = Vec::new();
+        let mut next = 0;
+
+        // Fill the FIFO; the first element starts on the first write
+        let start = cycles.now();
+        while next < words.len() && tx.write(words[next]) {
+            next += 1;
+        }
+        let mut fill_cycles = cycles.since(start);
+
+        while times.len() < words.len() {
+            if rx.read().is_none() {
+                continue;
+            }
+            let pushed = cycles.now();
+            if next < words.len() {
+                tx.write(words[next]);
+                next += 1;
+            }
+            let _ = times.push(pushed);
+            let _ = busy.push(cycles.since(pushed) + fill_cycles);
+            fill_cycles = 0;
+        }
+
+        report_elements(&run, &mut samples, cycles, &segments, repetition, &times, Some(&busy));
+        delay.delay_ms(100);
+    }
+    run.summary(&samples);
+
+    let (sm0, installed) = sm.stop().uninit(rx, tx);
+    pio.uninstall(installed);
+    let pio0 = pio.free(sm0, sm1, sm2, sm3);
+
+    rprintln!("Keying PIO benchmark finished.");
+    pio0
+}
+
+/// Keys "PARIS" from the CPU as the transmitter does: the pin toggled
+/// through the SIO with a delay for each half period.
+pub fn benchmark_keying_sio(
+    cycles: &CycleCounter,
+    pin: &mut impl OutputPin,
+    delay: &mut impl DelayNs,
+) {
+    let run = Run {
+        task: "keying",
+        method: "sio",
+        params: &[("wpm", KEYING_WPM)],
+        clock_hz: cycles.system_clock_hz(),
+    };
+
+    let segments = keying_segments();
+    let _ = pin.set_low();
+
+    let mut samples = Samples::new("cycles");
+    for repetition in 0..KEYING_REPETITIONS {
+        let mut times: Vec<u64, { MAX_SEGMENTS + 1 }> = Vec::new();
+        let _ = times.push(cycles.now());
+
+        for segment in &segments {
+            match 1_000_000u32.checked_div(segment.tone_hz) {
+                None => delay.delay_us(segment.duration_us), // Silence
+                Some(period_us) => {
+                    for _ in 0..segment.duration_us / period_us {
+                        let _ = pin.set_high();
+                        delay.delay_us(period_us / 2);
+                        let _ = pin.set_low();
+                        delay.delay_us(period_us / 2);
+                    }
+                }
+            }
+            let _ = times.push(cycles.now());
+        }
+
+        report_elements(&run, &mut samples, cycles, &segments, repetition, &times, None);
+        delay.delay_ms(100);
+    }
+    run.summary(&samples);
+
+    rprintln!("Keying SIO benchmark finished.");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Splits a keying word into (key, half period, periods less one)
+    fn fields(word: u32) -> (u32, u32, u32) {
+        (word & 1, (word >> 1) & 0x7FF, word >> 12)
+    }
+
+    #[test]
+    fn paris_starts_with_a_dot_its_gap_and_a_dash() {
+        let segments = keying_segments();
+        let first: Vec<(u32, u32), 3> = segments[..3]
+            .iter()
+            .map(|s| (s.tone_hz, s.duration_us))
+            .collect();
+        assert_eq!(first, [(DOT_FREQ, 60_000), (0, 60_000), (DASH_FREQ, 180_000)]);
+    }
+
+    #[test]
+    fn dot_word_keys_the_dot_tone() {
+        let word = keying_word(&Segment { tone_hz: DOT_FREQ, duration_us: 60_000 });
+        // 1250 µs periods at 800 Hz, 48 of them in a 60 ms dot at 20 WPM
+        assert_eq!(fields(word), (1, (1250 - PERIOD_OVERHEAD_US) / 2, 47));
+    }
+
+    #[test]
+    fn dash_word_keys_the_dash_tone() {
+        let word = keying_word(&Segment { tone_hz: DASH_FREQ, duration_us: 180_000 });
+        // 2500 µs periods at 400 Hz, 72 of them in a 180 ms dash
+        assert_eq!(fields(word), (1, (2500 - PERIOD_OVERHEAD_US) / 2, 71));
+    }
+
+    #[test]
+    fn gap_word_counts_out_silence() {
+        let word = keying_word(&Segment { tone_hz: 0, duration_us: 60_000 });
+        assert_eq!(fields(word), (0, (SILENCE_PERIOD_US - PERIOD_OVERHEAD_US) / 2, 59));
+    }
+}
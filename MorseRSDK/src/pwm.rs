@@ -1,7 +1,9 @@
 //! # PWM Setup Time Benchmark Function for RP2040
 //!
 //! Contains the benchmark logic for measuring PWM setup time.
-//! Designed to be called from a central benchmark runner.
+//! Designed to be called from a central benchmark runner. Both versions
+//! hand the PWM block and pin back in their reset state, so the runner can
+//! run them again or run the other one next.
 
 #![no_std]
 
@@ -10,67 +12,78 @@ use rtt_target::rprintln;
 // --- Other Necessary Imports ---
 use embedded_hal::pwm::SetDutyCycle;
 use rp2040_hal::{
-    self as hal, fugit, gpio::AnyPin, pac::{self, RESETS}, pwm::{self, Pwm4, B, ValidPwmOutputPin}, timer::Timer
+    self as hal, fugit, gpio::{AnyPin, FunctionPwm, Pin}, pac::{self, RESETS}, pwm::{self, Pwm4, B, ValidPwmOutputPin}
 };
 use embedded_hal_0_2::blocking::delay::DelayMs;
 
+use crate::bench::{Metric, Run, Samples};
+use crate::cycles::CycleCounter;
+
 /// Number of iterations for the benchmark
 const NUM_ITERATIONS: usize = 100;
 
-// HAL version of the PWM benchmark
+// HAL version of the PWM benchmark. Returns the PWM block and the pin.
 pub fn benchmark_pwm<P, T>(
-    timer: &Timer,
+    cycles: &CycleCounter,
     pin: P, // The pin passed in
     pwm_peripheral: pac::PWM,
     resets: &mut RESETS,
     delay: &mut T,
     _clock_freq: fugit::Rate<u32, 1, 1>, // Prefix with _ as it's not directly used here
-) where
+) -> (pac::PWM, Pin<P::Id, FunctionPwm, P::Pull>)
+where
     P: AnyPin + 'static, // Keep AnyPin if needed for output_to, OutputPin not needed here
     T: DelayMs<u32>,
     <P as AnyPin>::Id: ValidPwmOutputPin<Pwm4, B>, // Constraint for output_to
 {
-    rprintln!("task,method,iteration,setup_time_us");
+    let run = Run { task: "pwm", method: "setup_hal", params: &[], clock_hz: cycles.system_clock_hz() };
 
     let mut pwm_slices = hal::pwm::Slices::new(pwm_peripheral, resets);
     let pwm = &mut pwm_slices.pwm4;
     pwm.enable();
 
     // Configure channel B for the pin
-    let _led_pin_pwm = pwm.channel_b.output_to(pin); // pin is consumed here
+    let led_pin_pwm = pwm.channel_b.output_to(pin); // pin is consumed here
 
+    let mut samples = Samples::new("cycles");
     for iteration in 0..NUM_ITERATIONS {
         pwm.disable();
         pwm.channel_b.set_duty_cycle(0).unwrap();
-        let start = timer.get_counter().ticks();
+        let start = cycles.now();
 
         pwm.set_div_int(100);
         pwm.set_div_frac(0);
         let _ = pwm.channel_b.set_duty_cycle(32768);
         pwm.enable();
 
-        let end = timer.get_counter().ticks();
-        let duration = end.wrapping_sub(start);
+        let duration = cycles.since(start);
 
-        rprintln!("pwm,setup_hal,{},{}", iteration, duration);
+        samples.record(duration as u32);
+        run.sample(iteration as u32, &[
+            Metric::new("setup_time", cycles.to_micros(duration), "us"),
+            Metric::new("setup_time", duration, "cycles"),
+        ]);
 
         delay.delay_ms(100);
         pwm.disable();
         delay.delay_ms(100);
     }
+    run.summary(&samples);
     rprintln!("PWM benchmark (HAL) finished.");
+    
+    (pwm_slices.free(), led_pin_pwm)
 }
 
 // Bare metal version of the PWM benchmark
 pub fn benchmark_pwm_raw<T>(
-    timer: &Timer,
+    cycles: &CycleCounter,
     pin_number: u8,  // GPIO pin number
     delay: &mut T,
     _clock_freq: fugit::Rate<u32, 1, 1>,
 ) where
     T: DelayMs<u32>,
 {
-    rprintln!("task,method,iteration,setup_time_us");
+    let run = Run { task: "pwm", method: "setup_raw", params: &[], clock_hz: cycles.system_clock_hz() };
 
     // Directly access the PWM hardware
     let pwm = unsafe { &*pac::PWM::ptr() };
@@ -101,6 +114,7 @@ pub fn benchmark_pwm_raw<T>(
     let slice_num = 4;
     let chan_b = true;
     
+    let mut samples = Samples::new("cycles");
     for iteration in 0..NUM_ITERATIONS {
         // Disable PWM
         pwm.ch(slice_num).csr().modify(|_, w| w.en().clear_bit());
@@ -108,7 +122,7 @@ pub fn benchmark_pwm_raw<T>(
         // Set channel B duty cycle to 0
         pwm.ch(slice_num).cc().modify(|_, w| unsafe { w.b().bits(0) });
         
-        let start = timer.get_counter().ticks();
+        let start = cycles.now();
         
         // Set divider
         pwm.ch(slice_num).div().write(|w| unsafe { 
@@ -126,10 +140,13 @@ pub fn benchmark_pwm_raw<T>(
         // Enable PWM
         pwm.ch(slice_num).csr().modify(|_, w| w.en().set_bit());
         
-        let end = timer.get_counter().ticks();
-        let duration = end.wrapping_sub(start);
+        let duration = cycles.since(start);
         
-        rprintln!("pwm,setup_raw,{},{}", iteration, duration);
+        samples.record(duration as u32);
+        run.sample(iteration as u32, &[
+            Metric::new("setup_time", cycles.to_micros(duration), "us"),
+            Metric::new("setup_time", duration, "cycles"),
+        ]);
         
         delay.delay_ms(100);
         
@@ -138,6 +155,10 @@ pub fn benchmark_pwm_raw<T>(
         
         delay.delay_ms(100);
     }
+    run.summary(&samples);
+    
+    // Release the pin: back to no function
+    io_bank0.gpio(pin_number as usize).gpio_ctrl().write(|w| unsafe { w.funcsel().bits(0x1f) });
     
     rprintln!("PWM benchmark (Raw) finished.");
 }
\ No newline at end of file
@@ -0,0 +1,220 @@
+//! # Configuration Shell
+//!
+//! Line-oriented interpreter for the serial consoles:
+//!
+//! ```text
+//! show                 list every setting
+//! get <name>           print one setting
+//! set <name> <value>   change a setting (decimal or 0x hex)
+//! save                 write the settings to storage
+//! reset                go back to the built-in defaults
+//! help                 list the commands
+//! ```
+//!
+//! Output is written to any [`core::fmt::Write`], one CRLF-terminated
+//! line per message.
+
+use core::fmt::{self, Write};
+
+use crate::config::{field, Config, ConfigError, ConfigStore, Field, FIELDS};
+
+/// Owns the live configuration and the store `save` writes to
+pub struct Shell<S> {
+    config: Config,
+    store: S,
+}
+
+impl<S: ConfigStore> Shell<S> {
+    /// Starts from the saved configuration, or the defaults if there is none
+    pub fn new(mut store: S) -> Self {
+        let config = store.load().unwrap_or_default();
+        Self { config, store }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// For settings changed outside the shell, e.g. the keyer's `/wpm`
+    pub fn config_mut(&mut self) -> &mut Config {
+        &mut self.config
+    }
+
+    pub fn release(self) -> S {
+        self.store
+    }
+
+    /// Runs one command line. Returns true if the configuration changed,
+    /// so the caller can apply it.
+    pub fn execute<W: Write>(&mut self, line: &str, out: &mut W) -> bool {
+        // Console output is best effort
+        self.run(line, out).unwrap_or(false)
+    }
+
+    fn run<W: Write>(&mut self, line: &str, out: &mut W) -> Result<bool, fmt::Error> {
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            return Ok(false);
+        };
+        let args: (Option<&str>, Option<&str>, Option<&str>) = (words.next(), words.next(), words.next());
+
+        match (command, args) {
+            ("show", (None, _, _)) => {
+                for f in FIELDS {
+                    print_field(out, f, &self.config)?;
+                }
+                Ok(false)
+            }
+            ("get", (Some(name), None, _)) => {
+                match field(name) {
+                    Ok(f) => print_field(out, f, &self.config)?,
+                    Err(e) => print_error(out, name, e)?,
+                }
+                Ok(false)
+            }
+            ("set", (Some(name), Some(value), None)) => {
+                let result = field(name).and_then(|f| f.set(&mut self.config, value).map(|()| f));
+                match result {
+                    Ok(f) => {
+                        print_field(out, f, &self.config)?;
+                        if f.needs_restart {
+                            write!(out, "(takes effect after save and restart)\r\n")?;
+                        }
+                        Ok(true)
+                    }
+                    Err(e) => {
+                        print_error(out, name, e)?;
+                        Ok(false)
+                    }
+                }
+            }
+            ("save", (None, _, _)) => {
+                match self.store.save(&self.config) {
+                    Ok(()) => write!(out, "Saved\r\n")?,
+                    Err(e) => write!(out, "Save failed: {:?}\r\n", e)?,
+                }
+                Ok(false)
+            }
+            ("reset", (None, _, _)) => {
+                self.config = Config::default();
+                write!(out, "Defaults restored (not saved)\r\n")?;
+                Ok(true)
+            }
+            ("help", (None, _, _)) => {
+                write!(out, "Commands: show, get <name>, set <name> <value>, save, reset\r\n")?;
+                Ok(false)
+            }
+            _ => {
+                write!(out, "Unknown command: {} (try help)\r\n", line.trim())?;
+                Ok(false)
+            }
+        }
+    }
+}
+
+fn print_field<W: Write>(out: &mut W, f: &Field, config: &Config) -> fmt::Result {
+    let value = f.get(config);
+    if f.hex {
+        write!(out, "{} = 0x{:02X}", f.name, value)?;
+    } else {
+        write!(out, "{} = {}", f.name, value)?;
+    }
+    if !f.unit.is_empty() {
+        write!(out, " {}", f.unit)?;
+    }
+    write!(out, "\r\n")
+}
+
+fn print_error<W: Write>(out: &mut W, name: &str, error: ConfigError) -> fmt::Result {
+    match error {
+        ConfigError::UnknownField => write!(out, "No setting called {}\r\n", name),
+        ConfigError::NotANumber => write!(out, "{}: not a number\r\n", name),
+        ConfigError::OutOfRange { min, max } => {
+            write!(out, "{}: must be between {} and {}\r\n", name, min, max)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::NoStore;
+    use std::string::String;
+
+    /// Store that keeps the last saved config in memory
+    #[derive(Default)]
+    struct MemoryStore(Option<Config>);
+
+    impl ConfigStore for MemoryStore {
+        type Error = ();
+
+        fn load(&mut self) -> Option<Config> {
+            self.0
+        }
+
+        fn save(&mut self, config: &Config) -> Result<(), ()> {
+            self.0 = Some(*config);
+            Ok(())
+        }
+    }
+
+    fn run<S: ConfigStore>(shell: &mut Shell<S>, line: &str) -> (bool, String) {
+        let mut out = String::new();
+        let changed = shell.execute(line, &mut out);
+        (changed, out)
+    }
+
+    #[test]
+    fn get_and_set() {
+        let mut shell = Shell::new(NoStore);
+        assert_eq!(run(&mut shell, "get dot_freq"), (false, "dot_freq = 800 Hz\r\n".into()));
+        assert_eq!(run(&mut shell, "set dot_freq 650"), (true, "dot_freq = 650 Hz\r\n".into()));
+        assert_eq!(shell.config().dot_freq, 650);
+        assert_eq!(
+            run(&mut shell, "set lcd_address 0x3F"),
+            (true, "lcd_address = 0x3F\r\n(takes effect after save and restart)\r\n".into())
+        );
+    }
+
+    #[test]
+    fn bad_input_leaves_config_alone() {
+        let mut shell = Shell::new(NoStore);
+        assert_eq!(run(&mut shell, "set dot_freq 9"), (false, "dot_freq: must be between 100 and 5000\r\n".into()));
+        assert_eq!(run(&mut shell, "set dot_freq loud"), (false, "dot_freq: not a number\r\n".into()));
+        assert_eq!(run(&mut shell, "get volume"), (false, "No setting called volume\r\n".into()));
+        assert_eq!(run(&mut shell, "set dot_freq"), (false, "Unknown command: set dot_freq (try help)\r\n".into()));
+        assert_eq!(*shell.config(), Config::default());
+    }
+
+    #[test]
+    fn show_lists_every_field() {
+        let mut shell = Shell::new(NoStore);
+        let (_, out) = run(&mut shell, "show");
+        assert_eq!(out.lines().count(), FIELDS.len());
+        assert!(out.starts_with("baud_rate = 115200 baud\r\n"));
+    }
+
+    #[test]
+    fn save_without_storage_fails() {
+        let mut shell = Shell::new(NoStore);
+        assert_eq!(run(&mut shell, "save"), (false, "Save failed: NoStorage\r\n".into()));
+    }
+
+    #[test]
+    fn saved_config_is_loaded_and_reset_restores_defaults() {
+        let mut shell = Shell::new(MemoryStore::default());
+        run(&mut shell, "set unit_ms 60");
+        run(&mut shell, "save");
+
+        let mut shell = Shell::new(shell.release());
+        assert_eq!(shell.config().unit_ms, 60);
+        assert_eq!(run(&mut shell, "reset"), (true, "Defaults restored (not saved)\r\n".into()));
+        assert_eq!(*shell.config(), Config::default());
+    }
+
+    #[test]
+    fn blank_line_is_ignored() {
+        let mut shell = Shell::new(NoStore);
+        assert_eq!(run(&mut shell, "   "), (false, String::new()));
+    }
+}
@@ -0,0 +1,203 @@
+//! # Stack High-Water Marks
+//!
+//! Nothing stops a stack from growing into the statics below it, and
+//! `cargo size` only shows the static RAM. To see how deep a stack really
+//! goes, the binaries paint the free part of each stack with [`PAINT`] at
+//! boot; the deepest word that no longer holds it is the high-water mark.
+//!
+//! A [`StackArea`] is a stack from its lowest word to its top. Core0 runs
+//! on the stack cortex-m-rt sets up, between the statics and the end of
+//! RAM ([`StackArea::main`]); core1 on whatever array it was spawned
+//! with. [`Watermark`] reports an area over RTT each time its mark has
+//! grown, as a `sample` record of the [benchmark schema](crate::bench)
+//! with task `stack`:
+//!
+//! ```text
+//! {"schema":1,...,"kind":"sample","task":"stack","method":"transmitter_main","params":{},
+//!  "iteration":0,"metrics":[{"name":"used","value":1864,"unit":"bytes"},{"name":"free","value":254160,"unit":"bytes"}]}
+//! ```
+//!
+//! (shown wrapped). Finding the mark reads every still-painted word, a
+//! few milliseconds for the main stack, so check it between jobs rather
+//! than in a tight loop.
+
+use core::ptr;
+
+use crate::bench::{Metric, Run};
+
+/// What unused stack words hold
+pub const PAINT: u32 = 0xDEAD_BEEF;
+
+/// A stack, from its lowest word up to its top, where it starts
+#[derive(Clone, Copy)]
+pub struct StackArea {
+    bottom: *mut u32,
+    words: usize,
+    /// Words painted from the bottom by the last paint. Anything above
+    /// counts as used, even if it still holds [`PAINT`] from before.
+    painted: usize,
+}
+
+impl StackArea {
+    /// # Safety
+    ///
+    /// `bottom` must point to `words` words that are used as a stack or
+    /// not at all: painting overwrites the ones below the stack pointer.
+    pub const unsafe fn new(bottom: *mut u32, words: usize) -> Self {
+        Self { bottom, words, painted: 0 }
+    }
+
+    /// Core0's stack: everything from the end of the statics (cortex-m-rt's
+    /// `__sheap`, as there is no heap) to `_stack_start`
+    #[cfg(target_arch = "arm")]
+    pub fn main() -> Self {
+        extern "C" {
+            static mut __sheap: u32;
+            static mut _stack_start: u32;
+        }
+        let bottom = ptr::addr_of_mut!(__sheap);
+        let top = ptr::addr_of_mut!(_stack_start);
+        // Safety: the linker script puts nothing between the two
+        unsafe { Self::new(bottom, (top as usize - bottom as usize) / 4) }
+    }
+
+    /// Size of the whole area in bytes
+    pub fn size(&self) -> usize {
+        self.words * 4
+    }
+
+    /// Paints the whole area. Only for a stack nothing runs on yet, e.g.
+    /// core1's before it is spawned.
+    pub fn paint(&mut self) {
+        for i in 0..self.words {
+            // Safety: within the area, which `new`'s caller vouched for
+            unsafe { ptr::write_volatile(self.bottom.add(i), PAINT) };
+        }
+        self.painted = self.words;
+    }
+
+    /// Paints the area below the stack pointer, which is free. Called
+    /// again before a job, the next mark is that job's alone.
+    ///
+    /// The loop is assembly that keeps everything in registers: a Rust
+    /// loop, at `opt-level = 0`, would keep its state in a frame below
+    /// the stack pointer it read, and paint over it.
+    #[cfg(target_arch = "arm")]
+    pub fn paint_unused(&mut self) {
+        let end: usize;
+        // Safety: writes only between `bottom` and the stack pointer,
+        // which no frame is using. An interrupt meanwhile is over before
+        // the loop goes on, and it uses its own stack space anyway.
+        unsafe {
+            core::arch::asm!(
+                "mov r1, sp",
+                "2:",
+                "cmp r0, r1",
+                "bhs 3f",
+                "str r2, [r0]",
+                "adds r0, #4",
+                "b 2b",
+                "3:",
+                inout("r0") self.bottom => _,
+                out("r1") end,
+                in("r2") PAINT,
+                options(nostack),
+            );
+        }
+        let top = self.bottom as usize + self.size();
+        self.painted = (end.min(top).saturating_sub(self.bottom as usize)) / 4;
+    }
+
+    /// Bytes from the top down to the deepest word that lost its paint
+    pub fn used(&self) -> usize {
+        // Safety: within the area. Volatile: the stack changes under us,
+        // and core1's from the other core.
+        let untouched = (0..self.painted)
+            .take_while(|&i| unsafe { ptr::read_volatile(self.bottom.add(i)) } == PAINT)
+            .count();
+        (self.words - untouched) * 4
+    }
+
+    /// Bytes never used since the area was last painted
+    pub fn free(&self) -> usize {
+        self.size() - self.used()
+    }
+}
+
+/// Reports a stack's high-water mark when it has grown
+pub struct Watermark {
+    area: StackArea,
+    /// What runs on the stack, e.g. `"receiver_core1"`
+    method: &'static str,
+    clock_hz: u32,
+    reported: Option<usize>,
+    reports: u32,
+}
+
+impl Watermark {
+    pub const fn new(area: StackArea, method: &'static str, clock_hz: u32) -> Self {
+        Self { area, method, clock_hz, reported: None, reports: 0 }
+    }
+
+    /// Prints a record the first time and whenever the mark is deeper
+    /// than the last one printed
+    pub fn check(&mut self) {
+        let used = self.area.used();
+        if self.reported.is_some_and(|reported| used <= reported) {
+            return;
+        }
+        report(&self.area, self.method, self.clock_hz, self.reports);
+        self.reported = Some(used);
+        self.reports += 1;
+    }
+}
+
+/// Prints one `stack` record for `area`, e.g. after a benchmark
+pub fn report(area: &StackArea, method: &str, clock_hz: u32, iteration: u32) {
+    let run = Run { task: "stack", method, params: &[], clock_hz };
+    let used = area.used();
+    run.sample(iteration, &[
+        Metric::new("used", used as u32, "bytes"),
+        Metric::new("free", (area.size() - used) as u32, "bytes"),
+    ]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn area(words: &mut [u32]) -> StackArea {
+        unsafe { StackArea::new(words.as_mut_ptr(), words.len()) }
+    }
+
+    #[test]
+    fn freshly_painted_stack_is_unused() {
+        let mut words = [0u32; 64];
+        let mut stack = area(&mut words);
+        stack.paint();
+        assert_eq!((stack.used(), stack.free(), stack.size()), (0, 256, 256));
+    }
+
+    #[test]
+    fn mark_is_the_deepest_overwritten_word() {
+        let mut words = [0u32; 64];
+        let mut stack = area(&mut words);
+        stack.paint();
+        // A frame that left a gap, e.g. an array it never wrote
+        unsafe {
+            stack.bottom.add(63).write(1);
+            stack.bottom.add(40).write(2);
+        }
+        assert_eq!(stack.used(), 24 * 4);
+    }
+
+    #[test]
+    fn unpainted_part_counts_as_used_despite_old_paint() {
+        let mut words = [PAINT; 64];
+        let mut stack = area(&mut words);
+        assert_eq!(stack.used(), 64 * 4);
+        // As if the stack pointer were 16 words up
+        stack.painted = 16;
+        assert_eq!(stack.used(), 48 * 4);
+    }
+}
@@ -0,0 +1,616 @@
+//! # Flash Storage
+//!
+//! Versioned, CRC-protected records kept in reserved sectors at the end
+//! of the QSPI flash (see `memory.x`). Each record fills one 256-byte
+//! page:
+//!
+//! ```text
+//! 0   magic    u32  "MREC"
+//! 4   version  u16  payload format, chosen by the caller
+//! 6   length   u16  payload bytes
+//! 8   seq      u32  increases by one per record
+//! 12  payload  [length]
+//! ..  crc      u32  CRC-32 of everything before it
+//! ```
+//!
+//! New records go into the next erased page, so writes are spread over
+//! the whole area. When the next page is the start of a sector, that
+//! sector (which holds the oldest records) is erased first. The area is
+//! at least two sectors, so the newest record is never in the sector
+//! being erased. A record torn by a power cut fails its CRC and the
+//! previous one is used instead.
+//!
+//! [`Flash`] is implemented for the RP2040 by [`Rp2040Flash`] and, in the
+//! tests, by an in-memory NOR model.
+
+use heapless::Vec;
+
+use crate::config::{Config, ConfigStore};
+
+/// Smallest erasable unit
+pub const SECTOR_SIZE: u32 = 4096;
+/// Programming unit, and the size of one record
+pub const PAGE_SIZE: u32 = 256;
+/// Total flash on the Pico
+pub const FLASH_SIZE: u32 = 2048 * 1024;
+
+/// Sectors reserved for configuration records. Must match `memory.x`.
+pub const CONFIG_SECTORS: u32 = 2;
+/// Offset of the configuration area from the start of flash
+pub const CONFIG_OFFSET: u32 = FLASH_SIZE - CONFIG_SECTORS * SECTOR_SIZE;
+
+/// Sectors reserved for the receiver's [message log](crate::message_log),
+/// just below the configuration area. Must match `memory.x`.
+pub const LOG_SECTORS: u32 = 16;
+/// Offset of the message log area from the start of flash
+pub const LOG_OFFSET: u32 = CONFIG_OFFSET - LOG_SECTORS * SECTOR_SIZE;
+
+const RECORD_MAGIC: u32 = u32::from_le_bytes(*b"MREC");
+const HEADER_SIZE: usize = 12;
+const CRC_SIZE: usize = 4;
+/// Largest payload that fits in one record
+pub const MAX_PAYLOAD: usize = PAGE_SIZE as usize - HEADER_SIZE - CRC_SIZE;
+
+/// NOR flash as seen from the start of the device: erase sets bytes to
+/// 0xFF, programming can only clear bits.
+pub trait Flash {
+    type Error: core::fmt::Debug;
+
+    fn read(&mut self, offset: u32, buf: &mut [u8]) -> Result<(), Self::Error>;
+
+    /// Erases the `SECTOR_SIZE` sector starting at `offset`
+    fn erase_sector(&mut self, offset: u32) -> Result<(), Self::Error>;
+
+    /// Programs one `PAGE_SIZE` page starting at `offset`
+    fn program_page(&mut self, offset: u32, data: &[u8; PAGE_SIZE as usize]) -> Result<(), Self::Error>;
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum StorageError<E> {
+    Flash(E),
+    /// Payload longer than `MAX_PAYLOAD`
+    TooLarge,
+    /// The record did not read back correctly after programming
+    Verify,
+}
+
+impl<E> From<E> for StorageError<E> {
+    fn from(error: E) -> Self {
+        StorageError::Flash(error)
+    }
+}
+
+/// CRC-32 (IEEE 802.3, as used by zlib), computed bit by bit to keep it
+/// out of flash-hungry lookup tables
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/// A record read back from flash
+#[derive(Debug, PartialEq, Eq)]
+pub struct Record {
+    pub version: u16,
+    pub seq: u32,
+    pub payload: Vec<u8, MAX_PAYLOAD>,
+}
+
+fn parse_record(page: &[u8; PAGE_SIZE as usize]) -> Option<Record> {
+    let word = |at: usize| u32::from_le_bytes([page[at], page[at + 1], page[at + 2], page[at + 3]]);
+    if word(0) != RECORD_MAGIC {
+        return None;
+    }
+    let version = u16::from_le_bytes([page[4], page[5]]);
+    let length = u16::from_le_bytes([page[6], page[7]]) as usize;
+    if length > MAX_PAYLOAD {
+        return None;
+    }
+    let end = HEADER_SIZE + length;
+    if word(end) != crc32(&page[..end]) {
+        return None;
+    }
+    Some(Record {
+        version,
+        seq: word(8),
+        payload: Vec::from_slice(&page[HEADER_SIZE..end]).ok()?,
+    })
+}
+
+/// Append-only record log over `sectors` sectors starting at `offset`
+pub struct RecordStore<F> {
+    flash: F,
+    offset: u32,
+    slots: u32,
+    /// Slot of the newest valid record
+    latest: Option<u32>,
+    next_seq: u32,
+}
+
+impl<F: Flash> RecordStore<F> {
+    /// Scans the area for the newest valid record
+    pub fn new(mut flash: F, offset: u32, sectors: u32) -> Result<Self, F::Error> {
+        assert!(sectors >= 2, "the newest record must survive a sector erase");
+        let slots = sectors * SECTOR_SIZE / PAGE_SIZE;
+
+        let mut latest: Option<(u32, u32)> = None;
+        let mut page = [0u8; PAGE_SIZE as usize];
+        for slot in 0..slots {
+            flash.read(offset + slot * PAGE_SIZE, &mut page)?;
+            if let Some(record) = parse_record(&page) {
+                if latest.is_none_or(|(_, seq)| record.seq > seq) {
+                    latest = Some((slot, record.seq));
+                }
+            }
+        }
+
+        Ok(Self {
+            flash,
+            offset,
+            slots,
+            latest: latest.map(|(slot, _)| slot),
+            next_seq: latest.map_or(0, |(_, seq)| seq.wrapping_add(1)),
+        })
+    }
+
+    /// The newest valid record, if any
+    pub fn latest(&mut self) -> Result<Option<Record>, F::Error> {
+        let Some(slot) = self.latest else {
+            return Ok(None);
+        };
+        let mut page = [0u8; PAGE_SIZE as usize];
+        self.flash.read(self.slot_offset(slot), &mut page)?;
+        Ok(parse_record(&page))
+    }
+
+    /// Calls `f` for every valid record, oldest first.
+    ///
+    /// Records are written in slot order, so walking the ring from the
+    /// slot after the newest record visits them in sequence order.
+    pub fn for_each(&mut self, mut f: impl FnMut(&Record)) -> Result<(), F::Error> {
+        let start = self.latest.map_or(0, |s| s + 1);
+        let mut page = [0u8; PAGE_SIZE as usize];
+        for i in 0..self.slots {
+            let slot = (start + i) % self.slots;
+            self.flash.read(self.slot_offset(slot), &mut page)?;
+            if let Some(record) = parse_record(&page) {
+                f(&record);
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes a new record after the newest one
+    pub fn append(&mut self, version: u16, payload: &[u8]) -> Result<(), StorageError<F::Error>> {
+        if payload.len() > MAX_PAYLOAD {
+            return Err(StorageError::TooLarge);
+        }
+
+        let mut page = [0xFFu8; PAGE_SIZE as usize];
+        page[0..4].copy_from_slice(&RECORD_MAGIC.to_le_bytes());
+        page[4..6].copy_from_slice(&version.to_le_bytes());
+        page[6..8].copy_from_slice(&(payload.len() as u16).to_le_bytes());
+        page[8..12].copy_from_slice(&self.next_seq.to_le_bytes());
+        let end = HEADER_SIZE + payload.len();
+        page[HEADER_SIZE..end].copy_from_slice(payload);
+        let crc = crc32(&page[..end]);
+        page[end..end + CRC_SIZE].copy_from_slice(&crc.to_le_bytes());
+
+        let slot = self.free_slot()?;
+        let offset = self.slot_offset(slot);
+        self.flash.program_page(offset, &page)?;
+
+        let mut check = [0u8; PAGE_SIZE as usize];
+        self.flash.read(offset, &mut check)?;
+        if check != page {
+            return Err(StorageError::Verify);
+        }
+
+        self.latest = Some(slot);
+        self.next_seq = self.next_seq.wrapping_add(1);
+        Ok(())
+    }
+
+    pub fn release(self) -> F {
+        self.flash
+    }
+
+    fn slot_offset(&self, slot: u32) -> u32 {
+        self.offset + slot * PAGE_SIZE
+    }
+
+    /// Finds the next erased slot after the newest record, skipping any
+    /// torn pages and erasing the following sector when the current one
+    /// is used up
+    fn free_slot(&mut self) -> Result<u32, F::Error> {
+        let slots_per_sector = SECTOR_SIZE / PAGE_SIZE;
+        let mut slot = self.latest.map_or(0, |s| (s + 1) % self.slots);
+        let mut page = [0u8; PAGE_SIZE as usize];
+        loop {
+            if slot.is_multiple_of(slots_per_sector) {
+                self.flash.erase_sector(self.slot_offset(slot))?;
+                return Ok(slot);
+            }
+            self.flash.read(self.slot_offset(slot), &mut page)?;
+            if page.iter().all(|&b| b == 0xFF) {
+                return Ok(slot);
+            }
+            slot = (slot + 1) % self.slots;
+        }
+    }
+}
+
+/// Payload format of configuration records: every field of
+/// [`FIELDS`](crate::config::FIELDS) as a little-endian `u32`, in order.
+/// New fields must be appended so older records still load.
+pub const CONFIG_RECORD_VERSION: u16 = 1;
+
+/// [`ConfigStore`] on flash. A failed scan leaves it empty, so the
+/// firmware still starts with the defaults.
+pub struct FlashConfigStore<F> {
+    records: Option<RecordStore<F>>,
+}
+
+impl<F: Flash> FlashConfigStore<F> {
+    /// Uses the reserved configuration sectors at the end of flash
+    pub fn new(flash: F) -> Self {
+        Self::with_area(flash, CONFIG_OFFSET, CONFIG_SECTORS)
+    }
+
+    pub fn with_area(flash: F, offset: u32, sectors: u32) -> Self {
+        Self { records: RecordStore::new(flash, offset, sectors).ok() }
+    }
+}
+
+/// `Flash(None)` means the area could not be scanned at start-up
+impl<F: Flash> ConfigStore for FlashConfigStore<F> {
+    type Error = StorageError<Option<F::Error>>;
+
+    fn load(&mut self) -> Option<Config> {
+        let record = self.records.as_mut()?.latest().ok()??;
+        if record.version != CONFIG_RECORD_VERSION {
+            return None;
+        }
+        Some(Config::from_bytes(&record.payload))
+    }
+
+    fn save(&mut self, config: &Config) -> Result<(), Self::Error> {
+        let records = self.records.as_mut().ok_or(StorageError::Flash(None))?;
+        records
+            .append(CONFIG_RECORD_VERSION, &config.to_bytes())
+            .map_err(|e| match e {
+                StorageError::Flash(e) => StorageError::Flash(Some(e)),
+                StorageError::TooLarge => StorageError::TooLarge,
+                StorageError::Verify => StorageError::Verify,
+            })
+    }
+}
+
+/// The Pico's QSPI flash, programmed through the boot ROM routines.
+///
+/// Erase and program run from RAM with interrupts disabled, because
+/// execute-in-place is off while the flash is busy. The other core is
+/// parked in RAM meanwhile if it called
+/// [`lockout::init_victim`](crate::lockout::init_victim); otherwise it
+/// must not be running code from flash at the time.
+pub struct Rp2040Flash;
+
+/// Start of the flash in the address map
+const XIP_BASE: u32 = 0x1000_0000;
+/// 4 KiB sector erase command, as used by the Pico SDK
+const SECTOR_ERASE_COMMAND: u8 = 0x20;
+/// boot2, the second-stage bootloader that sets up fast QSPI reads,
+/// fills the first 256 bytes of flash
+const BOOT2_WORDS: usize = 64;
+
+/// ROM routines, looked up before execute-in-place is turned off
+struct RomFlashFns {
+    connect_internal_flash: unsafe extern "C" fn(),
+    flash_exit_xip: unsafe extern "C" fn(),
+    flash_range_erase: unsafe extern "C" fn(u32, usize, u32, u8),
+    flash_range_program: unsafe extern "C" fn(u32, *const u8, usize),
+    flash_flush_cache: unsafe extern "C" fn(),
+    flash_enter_cmd_xip: unsafe extern "C" fn(),
+}
+
+impl RomFlashFns {
+    fn lookup() -> Self {
+        use rp2040_hal::rom_data;
+        Self {
+            connect_internal_flash: rom_data::connect_internal_flash::ptr(),
+            flash_exit_xip: rom_data::flash_exit_xip::ptr(),
+            flash_range_erase: rom_data::flash_range_erase::ptr(),
+            flash_range_program: rom_data::flash_range_program::ptr(),
+            flash_flush_cache: rom_data::flash_flush_cache::ptr(),
+            flash_enter_cmd_xip: rom_data::flash_enter_cmd_xip::ptr(),
+        }
+    }
+}
+
+/// Runs from RAM: nothing here may touch flash, so it only calls through
+/// the pointers it is given. Afterwards it runs `boot2`, a copy of boot2
+/// in RAM, to put back the fast QSPI mode, as the Pico SDK's
+/// `flash_range_*` do; the ROM's command-XIP mode alone would leave
+/// everything running from flash several times slower.
+#[inline(never)]
+#[link_section = ".data.ram_func"]
+unsafe fn flash_operation(fns: &RomFlashFns, boot2: &[u32; BOOT2_WORDS], offset: u32, data: *const u8, erase: bool) {
+    (fns.connect_internal_flash)();
+    (fns.flash_exit_xip)();
+    if erase {
+        (fns.flash_range_erase)(offset, SECTOR_SIZE as usize, SECTOR_SIZE, SECTOR_ERASE_COMMAND);
+    } else {
+        (fns.flash_range_program)(offset, data, PAGE_SIZE as usize);
+    }
+    (fns.flash_flush_cache)();
+    (fns.flash_enter_cmd_xip)();
+    // Set the Thumb bit; boot2 returns to its caller when called
+    let boot2: unsafe extern "C" fn() = core::mem::transmute(boot2 as *const [u32; BOOT2_WORDS] as usize | 1);
+    boot2();
+}
+
+/// Rejected offsets: unaligned, past the end of the flash, or, for
+/// erase and program, below the reserved log and configuration sectors,
+/// where the firmware lives
+#[derive(Debug, PartialEq, Eq)]
+pub struct BadOffset(pub u32);
+
+/// Start of the sectors `Rp2040Flash` may erase and program
+const WRITABLE_OFFSET: u32 = FLASH_SIZE - (CONFIG_SECTORS + LOG_SECTORS) * SECTOR_SIZE;
+
+impl Rp2040Flash {
+    fn check(offset: u32, align: u32, len: u32) -> Result<(), BadOffset> {
+        let in_range = offset.checked_add(len).is_some_and(|end| end <= FLASH_SIZE);
+        if !offset.is_multiple_of(align) || offset < WRITABLE_OFFSET || !in_range {
+            return Err(BadOffset(offset));
+        }
+        Ok(())
+    }
+
+    fn run(offset: u32, data: *const u8, erase: bool) {
+        let fns = RomFlashFns::lookup();
+        // Copied while the flash can still be read
+        let mut boot2 = [0u32; BOOT2_WORDS];
+        unsafe { core::ptr::copy_nonoverlapping(XIP_BASE as *const u32, boot2.as_mut_ptr(), BOOT2_WORDS) };
+        crate::lockout::pause_other_core(|| {
+            cortex_m::interrupt::free(|_| unsafe { flash_operation(&fns, &boot2, offset, data, erase) })
+        });
+    }
+}
+
+impl Flash for Rp2040Flash {
+    type Error = BadOffset;
+
+    fn read(&mut self, offset: u32, buf: &mut [u8]) -> Result<(), BadOffset> {
+        let in_range = (offset as usize).checked_add(buf.len()).is_some_and(|end| end <= FLASH_SIZE as usize);
+        if !in_range {
+            return Err(BadOffset(offset));
+        }
+        let source = (XIP_BASE + offset) as *const u8;
+        unsafe { core::ptr::copy_nonoverlapping(source, buf.as_mut_ptr(), buf.len()) };
+        Ok(())
+    }
+
+    fn erase_sector(&mut self, offset: u32) -> Result<(), BadOffset> {
+        Self::check(offset, SECTOR_SIZE, SECTOR_SIZE)?;
+        Self::run(offset, core::ptr::null(), true);
+        Ok(())
+    }
+
+    fn program_page(&mut self, offset: u32, data: &[u8; PAGE_SIZE as usize]) -> Result<(), BadOffset> {
+        Self::check(offset, PAGE_SIZE, PAGE_SIZE)?;
+        Self::run(offset, data.as_ptr(), false);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::vec;
+
+    #[derive(Debug, PartialEq, Eq)]
+    pub(crate) enum NorError {
+        Unaligned,
+        /// The simulated power cut happened during this operation
+        PowerLoss,
+    }
+
+    /// In-memory NOR flash. Programming ANDs bits like real NOR, and a
+    /// power cut can be scheduled after a number of programmed bytes. An
+    /// erase started with no budget left is cut halfway through.
+    pub(crate) struct NorFlash {
+        pub(crate) data: vec::Vec<u8>,
+        pub(crate) erases: vec::Vec<u32>,
+        /// Bytes that may still be programmed before the power is cut
+        pub(crate) power_budget: Option<usize>,
+    }
+
+    impl NorFlash {
+        pub(crate) fn new(size: u32) -> Self {
+            Self { data: vec![0xFF; size as usize], erases: vec::Vec::new(), power_budget: None }
+        }
+    }
+
+    impl Flash for NorFlash {
+        type Error = NorError;
+
+        fn read(&mut self, offset: u32, buf: &mut [u8]) -> Result<(), NorError> {
+            let start = offset as usize;
+            buf.copy_from_slice(&self.data[start..start + buf.len()]);
+            Ok(())
+        }
+
+        fn erase_sector(&mut self, offset: u32) -> Result<(), NorError> {
+            if !offset.is_multiple_of(SECTOR_SIZE) {
+                return Err(NorError::Unaligned);
+            }
+            let start = offset as usize;
+            if self.power_budget == Some(0) {
+                self.data[start..start + SECTOR_SIZE as usize / 2].fill(0xFF);
+                return Err(NorError::PowerLoss);
+            }
+            self.data[start..start + SECTOR_SIZE as usize].fill(0xFF);
+            self.erases.push(offset);
+            Ok(())
+        }
+
+        fn program_page(&mut self, offset: u32, data: &[u8; PAGE_SIZE as usize]) -> Result<(), NorError> {
+            if !offset.is_multiple_of(PAGE_SIZE) {
+                return Err(NorError::Unaligned);
+            }
+            let start = offset as usize;
+            for (i, &byte) in data.iter().enumerate() {
+                if let Some(budget) = self.power_budget.as_mut() {
+                    if *budget == 0 {
+                        return Err(NorError::PowerLoss);
+                    }
+                    *budget -= 1;
+                }
+                self.data[start + i] &= byte;
+            }
+            Ok(())
+        }
+    }
+
+    const AREA: u32 = 2 * SECTOR_SIZE;
+
+    fn store(flash: NorFlash) -> RecordStore<NorFlash> {
+        RecordStore::new(flash, 0, 2).unwrap()
+    }
+
+    #[test]
+    fn crc_matches_reference() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn empty_flash_has_no_record() {
+        assert_eq!(store(NorFlash::new(AREA)).latest(), Ok(None));
+    }
+
+    #[test]
+    fn latest_record_survives_remount() {
+        let mut records = store(NorFlash::new(AREA));
+        records.append(1, b"first").unwrap();
+        records.append(1, b"second").unwrap();
+
+        let mut records = store(records.release());
+        let latest = records.latest().unwrap().unwrap();
+        assert_eq!(latest.payload, b"second");
+        assert_eq!(latest.seq, 1);
+
+        records.append(1, b"third").unwrap();
+        assert_eq!(records.latest().unwrap().unwrap().seq, 2);
+    }
+
+    #[test]
+    fn wraps_and_spreads_erases() {
+        let slots = AREA / PAGE_SIZE;
+        let mut records = store(NorFlash::new(AREA));
+        for i in 0..(3 * slots) {
+            records.append(1, &i.to_le_bytes()).unwrap();
+        }
+        let latest = records.latest().unwrap().unwrap();
+        assert_eq!(latest.payload, (3 * slots - 1).to_le_bytes());
+
+        let flash = records.release();
+        let first = flash.erases.iter().filter(|&&o| o == 0).count();
+        let second = flash.erases.iter().filter(|&&o| o == SECTOR_SIZE).count();
+        assert_eq!((first, second), (3, 3));
+    }
+
+    #[test]
+    fn torn_write_falls_back_to_previous_record() {
+        // "interrupted" is a 27-byte record: cut anywhere inside it
+        for budget in [0, 1, 8, 12, 20, 26] {
+            let mut records = store(NorFlash::new(AREA));
+            records.append(1, b"good").unwrap();
+
+            let mut flash = records.release();
+            flash.power_budget = Some(budget);
+            let mut records = store(flash);
+            assert_eq!(
+                records.append(1, b"interrupted"),
+                Err(StorageError::Flash(NorError::PowerLoss))
+            );
+
+            // Power comes back: the torn page is ignored and skipped
+            let mut flash = records.release();
+            flash.power_budget = None;
+            let mut records = store(flash);
+            assert_eq!(records.latest().unwrap().unwrap().payload, b"good", "budget {}", budget);
+
+            records.append(1, b"after").unwrap();
+            let mut records = store(records.release());
+            assert_eq!(records.latest().unwrap().unwrap().payload, b"after");
+        }
+    }
+
+    #[test]
+    fn for_each_visits_oldest_first_after_wrapping() {
+        let slots = AREA / PAGE_SIZE;
+        let mut records = store(NorFlash::new(AREA));
+        for i in 0..(slots + 5) {
+            records.append(1, &i.to_le_bytes()).unwrap();
+        }
+        let mut seen = vec::Vec::new();
+        records.for_each(|r| seen.push(u32::from_le_bytes(r.payload[..].try_into().unwrap()))).unwrap();
+        // Wrapping to slot 0 erased records 0..16 with the first sector
+        let per_sector = SECTOR_SIZE / PAGE_SIZE;
+        assert_eq!(seen, (per_sector..slots + 5).collect::<vec::Vec<_>>());
+    }
+
+    #[test]
+    fn corrupted_record_is_ignored() {
+        let mut records = store(NorFlash::new(AREA));
+        records.append(1, b"old").unwrap();
+        records.append(1, b"new").unwrap();
+
+        let mut flash = records.release();
+        flash.data[PAGE_SIZE as usize + HEADER_SIZE] ^= 0x01;
+        assert_eq!(store(flash).latest().unwrap().unwrap().payload, b"old");
+    }
+
+    #[test]
+    fn oversized_payload_is_rejected() {
+        let mut records = store(NorFlash::new(AREA));
+        assert_eq!(records.append(1, &[0; MAX_PAYLOAD + 1]), Err(StorageError::TooLarge));
+        assert_eq!(records.append(1, &[0; MAX_PAYLOAD]), Ok(()));
+    }
+
+    #[test]
+    fn rp2040_writes_stay_in_the_reserved_sectors() {
+        let check = |offset| Rp2040Flash::check(offset, SECTOR_SIZE, SECTOR_SIZE);
+        assert_eq!(check(LOG_OFFSET), Ok(()));
+        assert_eq!(check(FLASH_SIZE - SECTOR_SIZE), Ok(()));
+        // The firmware, the end of the flash and a wrapping length
+        assert_eq!(check(LOG_OFFSET - SECTOR_SIZE), Err(BadOffset(LOG_OFFSET - SECTOR_SIZE)));
+        assert_eq!(check(0), Err(BadOffset(0)));
+        assert_eq!(check(FLASH_SIZE), Err(BadOffset(FLASH_SIZE)));
+        assert_eq!(check(u32::MAX - SECTOR_SIZE + 1), Err(BadOffset(u32::MAX - SECTOR_SIZE + 1)));
+        assert_eq!(Rp2040Flash.read(u32::MAX, &mut [0; 4]), Err(BadOffset(u32::MAX)));
+    }
+
+    #[test]
+    fn config_round_trips_and_ignores_other_versions() {
+        let mut store = FlashConfigStore::with_area(NorFlash::new(AREA), 0, 2);
+        assert_eq!(store.load(), None);
+
+        let config = Config { dot_freq: 650, lcd_address: 0x3F, ..Config::default() };
+        store.save(&config).unwrap();
+        assert_eq!(store.load(), Some(config));
+
+        let mut records = store.records.take().unwrap();
+        records.append(CONFIG_RECORD_VERSION + 1, &[0; 8]).unwrap();
+        let mut store = FlashConfigStore::with_area(records.release(), 0, 2);
+        assert_eq!(store.load(), None);
+    }
+}
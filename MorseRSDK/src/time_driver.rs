@@ -0,0 +1,102 @@
+//! # Embassy Time Driver
+//!
+//! Runs `embassy-time` (`Timer::after`, `Ticker`, `with_timeout`) on the
+//! rp2040-hal TIMER, so the Embassy executor can be used alongside the
+//! rest of this crate without embassy-rp. A tick is one microsecond of
+//! the TIMER counter. ALARM0 wakes the earliest entry of a small timer
+//! queue. Call [`init`] before spawning tasks, then bind the interrupt:
+//!
+//! ```ignore
+//! #[interrupt]
+//! fn TIMER_IRQ_0() {
+//!     morse_rsdk::time_driver::on_interrupt();
+//! }
+//! ```
+
+use core::cell::RefCell;
+use core::task::Waker;
+
+use critical_section::{CriticalSection, Mutex};
+use embassy_time_driver::Driver;
+use embassy_time_queue_utils::Queue;
+use rp2040_hal::pac;
+use rp2040_hal::timer::{Alarm, Alarm0, Instant, Timer};
+
+struct State {
+    timer: Timer,
+    alarm: Alarm0,
+    queue: Queue,
+}
+
+impl State {
+    /// Arms the alarm for `at`. Returns false if `at` has already passed,
+    /// so the caller can expire those timers and ask again.
+    fn set_alarm(&mut self, at: u64) -> bool {
+        if at == u64::MAX {
+            let _ = self.alarm.cancel();
+            return true;
+        }
+        if at <= self.timer.get_counter().ticks() {
+            return false;
+        }
+        // More than ~71 minutes away: wake early and reschedule then
+        let at = at.min(self.timer.get_counter().ticks() + u64::from(u32::MAX / 2));
+        let _ = self.alarm.schedule_at(Instant::from_ticks(at));
+        true
+    }
+
+    fn rearm(&mut self) {
+        let mut next = self.queue.next_expiration(self.timer.get_counter().ticks());
+        while !self.set_alarm(next) {
+            next = self.queue.next_expiration(self.timer.get_counter().ticks());
+        }
+    }
+}
+
+static STATE: Mutex<RefCell<Option<State>>> = Mutex::new(RefCell::new(None));
+
+struct RpTimerDriver;
+
+impl Driver for RpTimerDriver {
+    fn now(&self) -> u64 {
+        critical_section::with(|cs| with_state(cs, |state| state.timer.get_counter().ticks())).unwrap_or(0)
+    }
+
+    fn schedule_wake(&self, at: u64, waker: &Waker) {
+        critical_section::with(|cs| {
+            with_state(cs, |state| {
+                if state.queue.schedule_wake(at, waker) {
+                    state.rearm();
+                }
+            })
+        });
+    }
+}
+
+embassy_time_driver::time_driver_impl!(static DRIVER: RpTimerDriver = RpTimerDriver);
+
+fn with_state<R>(cs: CriticalSection, f: impl FnOnce(&mut State) -> R) -> Option<R> {
+    STATE.borrow(cs).borrow_mut().as_mut().map(f)
+}
+
+/// Hands the TIMER and its ALARM0 to the driver and enables `TIMER_IRQ_0`.
+/// `timer` stays usable elsewhere: the counter is only read.
+pub fn init(timer: Timer, mut alarm: Alarm0) {
+    alarm.enable_interrupt();
+    critical_section::with(|cs| {
+        STATE.borrow(cs).replace(Some(State { timer, alarm, queue: Queue::new() }));
+    });
+    unsafe {
+        pac::NVIC::unmask(pac::Interrupt::TIMER_IRQ_0);
+    }
+}
+
+/// Wakes expired timers. Call from the `TIMER_IRQ_0` handler.
+pub fn on_interrupt() {
+    critical_section::with(|cs| {
+        with_state(cs, |state| {
+            state.alarm.clear_interrupt();
+            state.rearm();
+        })
+    });
+}
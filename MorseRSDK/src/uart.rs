@@ -1,6 +1,7 @@
 //! # UART Performance Benchmark Function for RP2040
 //!
-//! Contains the benchmark logic for measuring UART transmission times.
+//! Contains the benchmark logic for measuring UART transmission times,
+//! with the CPU or DMA feeding the TX FIFO.
 //! Designed to be called from a central benchmark runner.
 
 #![no_std]
@@ -8,12 +9,16 @@
 // --- RTT Import ---
 use rtt_target::rprintln;
 // --- Other Necessary Imports ---
-use rp2040_hal::{self as hal, timer::Timer};
+use rp2040_hal as hal;
 use core::fmt::Write; // Keep Write as UartPeripheral implements it
 use hal::uart::{UartPeripheral, ValidUartPinout};
-use cortex_m::delay::Delay;
+use embedded_hal::delay::DelayNs;
 use hal::pac::UART0; // Keep UART0
 
+use crate::bench::{Metric, Run, Samples};
+use crate::cycles::CycleCounter;
+use crate::dma;
+
 /// Constants for benchmark configuration
 const NUM_ITERATIONS: usize = 100;
 const NUM_TEST_SIZES: usize = 4;
@@ -22,13 +27,11 @@ const TEST_SIZES: [usize; NUM_TEST_SIZES] = [10, 50, 100, 250];
 /// Runs the UART TX benchmark using HAL UART peripheral
 pub fn benchmark_uart<PINS>(
     uart: &mut UartPeripheral<hal::uart::Enabled, UART0, PINS>,
-    timer: &Timer,
-    delay: &mut Delay,
+    cycles: &CycleCounter,
+    delay: &mut impl DelayNs,
 ) where
     PINS: ValidUartPinout<UART0>,
 {
-    // Print header via RTT
-    rprintln!("task,method,iteration,bytes,time_us,bytes_per_sec");
 
     let max_size = TEST_SIZES.iter().max().copied().unwrap_or(0);
     let mut test_message = [b'A'; 1000]; // Increased buffer size for larger tests
@@ -40,6 +43,13 @@ pub fn benchmark_uart<PINS>(
     for &msg_size in TEST_SIZES.iter() {
         if msg_size > test_message.len() { continue; }
 
+        let run = Run {
+            task: "uart",
+            method: "tx_hal",
+            params: &[("bytes", msg_size as u32)],
+            clock_hz: cycles.system_clock_hz(),
+        };
+        let mut samples = Samples::new("cycles");
         for iteration in 0..NUM_ITERATIONS {
             // Pad data with iteration number to prevent UART optimizations
             for i in 0..msg_size.min(test_message.len()) {
@@ -49,7 +59,7 @@ pub fn benchmark_uart<PINS>(
             // Wait before starting timing
             delay.delay_ms(10);
             
-            let start = timer.get_counter();
+            let start = cycles.now();
             
             // Send the data
             uart.write_full_blocking(&test_message[0..msg_size]);
@@ -62,9 +72,8 @@ pub fn benchmark_uart<PINS>(
             while !uart0.uartfr().read().txfe().bit() {}
             while uart0.uartfr().read().busy().bit() {}
             
-            let end = timer.get_counter();
-
-            let duration_us = end.checked_duration_since(start).map(|d| d.to_micros()).unwrap_or(0);
+            let duration_cycles = cycles.since(start);
+            let duration_us = cycles.to_micros(duration_cycles);
             
             // Only calculate throughput if we have a valid duration
             let bytes_per_sec = if duration_us > 10 { // Threshold to avoid division by small numbers
@@ -74,14 +83,18 @@ pub fn benchmark_uart<PINS>(
                 (115200 / 10) // Roughly 11,520 bytes per second at 115200 baud
             };
 
+            samples.record(duration_cycles as u32);
             // Output results via RTT
-            rprintln!(
-                "uart,tx_hal,{},{},{},{}",
-                iteration, msg_size, duration_us, bytes_per_sec
-            );
+            run.sample(iteration as u32, &[
+                Metric::new("time", duration_us, "us"),
+                Metric::new("throughput", bytes_per_sec, "bytes_per_sec"),
+                Metric::new("time", duration_cycles, "cycles"),
+                Metric::new("cpu_time", duration_cycles, "cycles"),
+            ]);
 
             delay.delay_ms(100); // Longer delay between tests
         }
+        run.summary(&samples);
     }
     // Indicate completion via RTT
     rprintln!("UART benchmark (HAL) finished.");
@@ -92,46 +105,14 @@ pub fn benchmark_uart<PINS>(
 
 /// Runs the UART TX benchmark using raw register access
 pub fn benchmark_uart_raw(
-    timer: &Timer,
-    delay: &mut Delay,
+    cycles: &CycleCounter,
+    delay: &mut impl DelayNs,
     baud_rate: u32,
     system_clock_hz: u32,
 ) {
-    // Print header via RTT
-    rprintln!("task,method,iteration,bytes,time_us,bytes_per_sec");
-    
-    // Get direct pointers to UART and reset controller
     let uart0 = unsafe { &*hal::pac::UART0::ptr() };
-    let resets = unsafe { &*hal::pac::RESETS::ptr() };
-    
-    // Reset UART
-    resets.reset().modify(|_, w| w.uart0().set_bit());
-    resets.reset().modify(|_, w| w.uart0().clear_bit());
-    while resets.reset_done().read().uart0().bit_is_clear() {}
-    
-    // Configure GPIO pins for UART (GPIO 0 = TX, GPIO 1 = RX)
-    let io_bank0 = unsafe { &*hal::pac::IO_BANK0::ptr() };
-    
-    // Set GPIO 0 and 1 to UART function (function 2)
-    io_bank0.gpio(0).gpio_ctrl().write(|w| unsafe { w.funcsel().bits(2) });
-    io_bank0.gpio(1).gpio_ctrl().write(|w| unsafe { w.funcsel().bits(2) });
-    
-    // Calculate baud rate divisor
-    // BAUDDIV = (UARTCLK / (16 * Baud rate))
-    let baud_div = (8 * system_clock_hz) / (baud_rate);
-    let baud_div_int = baud_div / 16;
-    let baud_div_frac = ((baud_div % 16) * 64 + 8) / 16; // Round properly
-    
-    // Configure UART
-    uart0.uartibrd().write(|w| unsafe { w.baud_divint().bits(baud_div_int as u16) });
-    uart0.uartfbrd().write(|w| unsafe { w.baud_divfrac().bits(baud_div_frac as u8) });
-    
-    // Set line control (8 bits, no parity, 1 stop bit)
-    uart0.uartlcr_h().write(|w| unsafe { w.wlen().bits(0b11).fen().set_bit() });
-    
-    // Enable UART
-    uart0.uartcr().write(|w| w.uarten().set_bit().txe().set_bit().rxe().set_bit());
-    
+    setup_raw(baud_rate, system_clock_hz);
+
     // Test data
     let max_size = TEST_SIZES.iter().max().copied().unwrap_or(0);
     let test_message = [b'A'; 250]; // Ensure this buffer is large enough
@@ -139,8 +120,15 @@ pub fn benchmark_uart_raw(
     for &msg_size in TEST_SIZES.iter() {
         if msg_size > max_size { continue; }
         
+        let run = Run {
+            task: "uart",
+            method: "tx_raw",
+            params: &[("bytes", msg_size as u32)],
+            clock_hz: cycles.system_clock_hz(),
+        };
+        let mut samples = Samples::new("cycles");
         for iteration in 0..NUM_ITERATIONS {
-            let start = timer.get_counter();
+            let start = cycles.now();
             
             // Send data
             for &byte in &test_message[0..msg_size] {
@@ -155,23 +143,26 @@ pub fn benchmark_uart_raw(
             while !uart0.uartfr().read().txfe().bit() {}
             while uart0.uartfr().read().busy().bit() {}
             
-            let end = timer.get_counter();
-            
-            let duration_us = end.checked_duration_since(start).map(|d| d.to_micros()).unwrap_or(0);
+            let duration_cycles = cycles.since(start);
+            let duration_us = cycles.to_micros(duration_cycles);
             let bytes_per_sec = if duration_us > 0 {
                 (msg_size as u64 * 1_000_000) / duration_us as u64
             } else {
                 0
             };
             
+            samples.record(duration_cycles as u32);
             // Output results via RTT
-            rprintln!(
-                "uart,tx_raw,{},{},{},{}",
-                iteration, msg_size, duration_us, bytes_per_sec
-            );
+            run.sample(iteration as u32, &[
+                Metric::new("time", duration_us, "us"),
+                Metric::new("throughput", bytes_per_sec, "bytes_per_sec"),
+                Metric::new("time", duration_cycles, "cycles"),
+                Metric::new("cpu_time", duration_cycles, "cycles"),
+            ]);
             
             delay.delay_ms(50);
         }
+        run.summary(&samples);
     }
     
     // Send completion message via UART
@@ -181,6 +172,128 @@ pub fn benchmark_uart_raw(
         uart0.uartdr().write(|w| unsafe { w.data().bits(byte) });
     }
     
+    // Release UART0 and its pins once the message is out
+    release_raw();
+    
     // Indicate completion via RTT
     rprintln!("UART benchmark (Raw) finished.");
-}
\ No newline at end of file
+}
+
+/// Runs the UART TX benchmark with DMA feeding the TX FIFO, paced by
+/// UART0's TX DREQ
+pub fn benchmark_uart_dma(
+    cycles: &CycleCounter,
+    delay: &mut impl DelayNs,
+    baud_rate: u32,
+    system_clock_hz: u32,
+) {
+    let uart0 = unsafe { &*hal::pac::UART0::ptr() };
+    setup_raw(baud_rate, system_clock_hz);
+    uart0.uartdmacr().write(|w| w.txdmae().set_bit());
+    dma::reset();
+
+    let mut test_message = [0u8; 250];
+    for &msg_size in TEST_SIZES.iter() {
+        if msg_size > test_message.len() { continue; }
+
+        let run = Run {
+            task: "uart",
+            method: "tx_dma",
+            params: &[("bytes", msg_size as u32)],
+            clock_hz: cycles.system_clock_hz(),
+        };
+        let mut samples = Samples::new("cycles");
+        for iteration in 0..NUM_ITERATIONS {
+            for (i, byte) in test_message[..msg_size].iter_mut().enumerate() {
+                *byte = b'A' + ((i + iteration) % 26) as u8;
+            }
+
+            let start = cycles.now();
+
+            dma::start(&dma::Transfer {
+                read: test_message.as_ptr() as u32,
+                write: uart0.uartdr().as_ptr() as u32,
+                count: msg_size as u32,
+                size: dma::Size::Byte,
+                incr_read: true,
+                incr_write: false,
+                read_ring_bits: 0,
+                treq: dma::TREQ_UART0_TX,
+            });
+            // The CPU's part is only setting the channel up
+            let cpu_cycles = cycles.since(start);
+            dma::wait();
+
+            // The DMA is done once the last byte is in the FIFO
+            while !uart0.uartfr().read().txfe().bit() {}
+            while uart0.uartfr().read().busy().bit() {}
+
+            let duration_cycles = cycles.since(start);
+            let duration_us = cycles.to_micros(duration_cycles);
+            let bytes_per_sec = (msg_size as u64 * 1_000_000).checked_div(duration_us).unwrap_or(0);
+
+            samples.record(duration_cycles as u32);
+            run.sample(iteration as u32, &[
+                Metric::new("time", duration_us, "us"),
+                Metric::new("throughput", bytes_per_sec, "bytes_per_sec"),
+                Metric::new("time", duration_cycles, "cycles"),
+                Metric::new("cpu_time", cpu_cycles, "cycles"),
+            ]);
+
+            delay.delay_ms(50);
+        }
+        run.summary(&samples);
+    }
+
+    dma::stop();
+    uart0.uartdmacr().write(|w| unsafe { w.bits(0) });
+    release_raw();
+
+    rprintln!("UART benchmark (DMA) finished.");
+}
+
+/// Resets UART0, routes it to GPIO0 (TX) and GPIO1 (RX) and enables it
+/// at `baud_rate`, 8N1 with FIFOs
+fn setup_raw(baud_rate: u32, system_clock_hz: u32) {
+    // Get direct pointers to UART and reset controller
+    let uart0 = unsafe { &*hal::pac::UART0::ptr() };
+    let resets = unsafe { &*hal::pac::RESETS::ptr() };
+    
+    // Reset UART
+    resets.reset().modify(|_, w| w.uart0().set_bit());
+    resets.reset().modify(|_, w| w.uart0().clear_bit());
+    while resets.reset_done().read().uart0().bit_is_clear() {}
+    
+    // Configure GPIO pins for UART (GPIO 0 = TX, GPIO 1 = RX)
+    let io_bank0 = unsafe { &*hal::pac::IO_BANK0::ptr() };
+    
+    // Set GPIO 0 and 1 to UART function (function 2)
+    io_bank0.gpio(0).gpio_ctrl().write(|w| unsafe { w.funcsel().bits(2) });
+    io_bank0.gpio(1).gpio_ctrl().write(|w| unsafe { w.funcsel().bits(2) });
+    
+    // Calculate baud rate divisor
+    // BAUDDIV = (UARTCLK / (16 * Baud rate))
+    let baud_div = (8 * system_clock_hz) / (baud_rate);
+    let baud_div_int = baud_div / 16;
+    let baud_div_frac = ((baud_div % 16) * 64 + 8) / 16; // Round properly
+    
+    // Configure UART
+    uart0.uartibrd().write(|w| unsafe { w.baud_divint().bits(baud_div_int as u16) });
+    uart0.uartfbrd().write(|w| unsafe { w.baud_divfrac().bits(baud_div_frac as u8) });
+    
+    // Set line control (8 bits, no parity, 1 stop bit)
+    uart0.uartlcr_h().write(|w| unsafe { w.wlen().bits(0b11).fen().set_bit() });
+    
+    // Enable UART
+    uart0.uartcr().write(|w| w.uarten().set_bit().txe().set_bit().rxe().set_bit());
+}
+
+/// Waits for UART0 to go idle, then disables it and releases its pins
+fn release_raw() {
+    let uart0 = unsafe { &*hal::pac::UART0::ptr() };
+    let io_bank0 = unsafe { &*hal::pac::IO_BANK0::ptr() };
+    while uart0.uartfr().read().busy().bit() {}
+    uart0.uartcr().write(|w| unsafe { w.bits(0) });
+    io_bank0.gpio(0).gpio_ctrl().write(|w| unsafe { w.funcsel().bits(0x1f) });
+    io_bank0.gpio(1).gpio_ctrl().write(|w| unsafe { w.funcsel().bits(0x1f) });
+}
@@ -0,0 +1,636 @@
+//! # UART Loopback Benchmark Functions for RP2040
+//!
+//! Measures the receive path of UART0 with the PL011's internal loopback
+//! (UARTCR.LBE), so nothing needs wiring:
+//!
+//! - `uart_rx`: one byte from the TX FIFO to readable in the RX FIFO
+//! - `uart_fifo`: time to write a 32-byte burst into the empty TX FIFO,
+//!   and how many of a 40-byte burst the 32-byte RX FIFO keeps
+//! - `uart_loopback`: a 256-byte stream sent and received at once, at
+//!   several baud rates, with a count of bytes that came back wrong
+//! - `uart_errors`: whether parity, framing and break errors are reported
+//!
+//! Loopback cannot make a bad frame, since TX and RX share the line
+//! settings, so the error test turns loopback off and bit-bangs frames
+//! onto GPIO1 (RX) through its input override, timed on the cycle counter.
+//!
+//! Designed to be called from a central benchmark runner.
+
+use heapless::Vec;
+use rp2040_hal as hal;
+use rtt_target::rprintln;
+use embedded_hal::delay::DelayNs;
+use hal::fugit::{HertzU32, RateExtU32};
+use hal::pac::UART0;
+use hal::uart::{DataBits, Disabled, Enabled, Parity, ReadErrorType, StopBits, UartConfig, UartPeripheral, ValidUartPinout};
+
+use crate::bench::{Metric, Run, Samples};
+use crate::cycles::CycleCounter;
+
+/// Baud rate of the latency, FIFO and error tests
+const BASE_BAUD: u32 = 115_200;
+/// Baud rates of the stream test; the PL011 tops out at UARTCLK / 16
+const STREAM_BAUD_RATES: [u32; 4] = [115_200, 460_800, 921_600, 3_000_000];
+
+const RX_ITERATIONS: u32 = 100;
+const FIFO_ITERATIONS: u32 = 20;
+const STREAM_ITERATIONS: u32 = 20;
+const FRAMES_PER_FAULT: u32 = 100;
+
+/// Depth of each of the PL011's FIFOs
+const FIFO_DEPTH: usize = 32;
+/// Bytes sent unread in the FIFO test, enough to overrun the RX FIFO
+const FIFO_BURST: usize = FIFO_DEPTH + 8;
+const STREAM_BYTES: usize = 256;
+
+/// Bits on the line per frame at 8N1
+const FRAME_BITS: u64 = 10;
+/// Bits on the line per frame at 8E1, as the error test sends them
+const PARITY_FRAME_BITS: u32 = 11;
+/// The UART RX pin whose input the error test overrides
+const RX_PIN: usize = 1;
+
+/// What the UART made of one frame
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Received {
+    Data(u8),
+    Overrun,
+    Break,
+    Parity,
+    Framing,
+}
+
+impl From<ReadErrorType> for Received {
+    fn from(error: ReadErrorType) -> Self {
+        match error {
+            ReadErrorType::Overrun => Received::Overrun,
+            ReadErrorType::Break => Received::Break,
+            ReadErrorType::Parity => Received::Parity,
+            ReadErrorType::Framing => Received::Framing,
+        }
+    }
+}
+
+impl Received {
+    /// One UARTDR read, flags in the HAL's order of precedence
+    fn from_dr(dr: &hal::pac::uart0::uartdr::R) -> Self {
+        if dr.oe().bit_is_set() {
+            Received::Overrun
+        } else if dr.be().bit_is_set() {
+            Received::Break
+        } else if dr.pe().bit_is_set() {
+            Received::Parity
+        } else if dr.fe().bit_is_set() {
+            Received::Framing
+        } else {
+            Received::Data(dr.data().bits())
+        }
+    }
+}
+
+/// A frame the error test forces onto the RX input
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Fault {
+    /// A good frame, to show the test itself is sound
+    Clean,
+    Parity,
+    /// Stop bit low
+    Framing,
+    /// Line low for two frames
+    Break,
+}
+
+impl Fault {
+    const ALL: [Fault; 4] = [Fault::Clean, Fault::Parity, Fault::Framing, Fault::Break];
+
+    fn method(self, raw: bool) -> &'static str {
+        match (self, raw) {
+            (Fault::Clean, false) => "clean_hal",
+            (Fault::Clean, true) => "clean_raw",
+            (Fault::Parity, false) => "parity_hal",
+            (Fault::Parity, true) => "parity_raw",
+            (Fault::Framing, false) => "framing_hal",
+            (Fault::Framing, true) => "framing_raw",
+            (Fault::Break, false) => "break_hal",
+            (Fault::Break, true) => "break_raw",
+        }
+    }
+
+    /// What the UART should report for `byte` sent with this fault
+    fn expected(self, byte: u8) -> Received {
+        match self {
+            Fault::Clean => Received::Data(byte),
+            Fault::Parity => Received::Parity,
+            Fault::Framing => Received::Framing,
+            Fault::Break => Received::Break,
+        }
+    }
+
+    /// Line levels for `byte` at 8E1, each with its length in bit times
+    fn levels(self, byte: u8) -> Vec<(bool, u32), 11> {
+        let mut levels = Vec::new();
+        if self == Fault::Break {
+            levels.push((false, 2 * PARITY_FRAME_BITS)).ok();
+            return levels;
+        }
+        levels.push((false, 1)).ok(); // Start bit
+        for bit in 0..8 {
+            levels.push(((byte >> bit) & 1 == 1, 1)).ok();
+        }
+        // Even parity: the parity bit makes the count of ones even
+        let parity = byte.count_ones() % 2 == 1;
+        levels.push((parity != (self == Fault::Parity), 1)).ok();
+        levels.push((self != Fault::Framing, 1)).ok(); // Stop bit
+        levels
+    }
+}
+
+/// Cycles `frames` 8N1 frames take at `baud`
+fn frame_cycles(cycles: &CycleCounter, baud: u32, frames: u64) -> u64 {
+    frames * FRAME_BITS * u64::from(cycles.system_clock_hz()) / u64::from(baud)
+}
+
+/// Turns the internal loopback on or off. The HAL has no setting for it,
+/// and `enable` clears it.
+fn set_loopback(enabled: bool) {
+    let uart0 = unsafe { &*hal::pac::UART0::ptr() };
+    uart0.uartcr().modify(|_, w| w.lbe().bit(enabled));
+}
+
+/// Reads and clears the receive overrun flag. The HAL only reports an
+/// overrun on the next byte to arrive, and in a burst none does.
+fn take_overrun() -> bool {
+    let uart0 = unsafe { &*hal::pac::UART0::ptr() };
+    let overrun = uart0.uartrsr().read().oe().bit_is_set();
+    uart0.uartrsr().write(|w| unsafe { w.bits(0) });
+    overrun
+}
+
+/// Forces GPIO1's input high or low; `None` gives it back to the pad
+fn force_rx(level: Option<bool>) {
+    let io_bank0 = unsafe { &*hal::pac::IO_BANK0::ptr() };
+    io_bank0.gpio(RX_PIN).gpio_ctrl().modify(|_, w| match level {
+        Some(true) => w.inover().high(),
+        Some(false) => w.inover().low(),
+        None => w.inover().normal(),
+    });
+}
+
+/// Drives `levels` onto the RX input, each held for its bit times, then
+/// idles high for a frame. Returns the cycles from the start of the last
+/// level until `readable`, if the UART took a frame by then.
+fn inject(cycles: &CycleCounter, bit_cycles: u64, levels: &[(bool, u32)], readable: impl Fn() -> bool) -> Option<u64> {
+    let (&(last, last_bits), body) = levels.split_last()?;
+    // Edges are scheduled from the first, so waits do not add up
+    let mut edge = cycles.now();
+    for &(level, bits) in body {
+        force_rx(Some(level));
+        edge += u64::from(bits) * bit_cycles;
+        while cycles.now() < edge {}
+    }
+    force_rx(Some(last));
+    let last_start = edge;
+    let idle_at = edge + u64::from(last_bits) * bit_cycles;
+    let end = idle_at + u64::from(PARITY_FRAME_BITS) * bit_cycles;
+
+    let mut arrived = None;
+    let mut idle = false;
+    loop {
+        let now = cycles.now();
+        if !idle && now >= idle_at {
+            force_rx(Some(true));
+            idle = true;
+        }
+        if arrived.is_none() && readable() {
+            arrived = Some(now - last_start);
+        }
+        if idle && now >= end {
+            return arrived;
+        }
+    }
+}
+
+/// The metrics of one frame of the error test
+fn report_fault(run: &Run, samples: &mut Samples, cycles: &CycleCounter, frame: u32, detected: bool, latency: Option<u64>) {
+    let detected = u32::from(detected);
+    match latency {
+        Some(latency_cycles) => {
+            samples.record(latency_cycles as u32);
+            run.sample(frame, &[
+                Metric::new("detected", detected, "frames"),
+                Metric::new("flag_latency", latency_cycles as f32 / cycles.cycles_per_micro() as f32, "us"),
+                Metric::new("flag_latency", latency_cycles, "cycles"),
+            ]);
+        }
+        None => run.sample(frame, &[Metric::new("detected", detected, "frames")]),
+    }
+}
+
+fn loopback_config(baud: u32, parity: Option<Parity>) -> UartConfig {
+    UartConfig::new(baud.Hz(), DataBits::Eight, parity, StopBits::One)
+}
+
+/// Runs the loopback benchmarks with the HAL UART driver. Takes UART0
+/// disabled, enables it for each test and gives it back disabled.
+pub fn benchmark_uart_loopback<PINS>(
+    mut uart: UartPeripheral<Disabled, UART0, PINS>,
+    peripheral_clock: HertzU32,
+    cycles: &CycleCounter,
+    delay: &mut impl DelayNs,
+) -> UartPeripheral<Disabled, UART0, PINS>
+where
+    PINS: ValidUartPinout<UART0>,
+{
+    let enable = |uart: UartPeripheral<Disabled, UART0, PINS>, baud, parity| {
+        uart.enable(loopback_config(baud, parity), peripheral_clock).unwrap()
+    };
+    let drain = |uart: &UartPeripheral<Enabled, UART0, PINS>| {
+        let mut byte = [0u8; 1];
+        while uart.uart_is_readable() {
+            let _ = uart.read_raw(&mut byte);
+        }
+        take_overrun();
+    };
+
+    // --- RX latency ---
+    let enabled = enable(uart, BASE_BAUD, None);
+    set_loopback(true);
+    drain(&enabled);
+    let run = Run { task: "uart_rx", method: "loopback_hal", params: &[("baud", BASE_BAUD)], clock_hz: cycles.system_clock_hz() };
+    let timeout = frame_cycles(cycles, BASE_BAUD, 4);
+    let mut samples = Samples::new("cycles");
+    let mut missed = 0;
+    for iteration in 0..RX_ITERATIONS {
+        let sent = b'A' + (iteration % 26) as u8;
+        let mut byte = [0u8; 1];
+
+        let start = cycles.now();
+        enabled.write_full_blocking(&[sent]);
+        while !enabled.uart_is_readable() && cycles.now() - start < timeout {}
+        let latency_cycles = cycles.since(start);
+
+        if enabled.uart_is_readable() && enabled.read_full_blocking(&mut byte).is_ok() && byte[0] == sent {
+            samples.record(latency_cycles as u32);
+            run.sample(iteration, &[
+                Metric::new("rx_latency", latency_cycles as f32 / cycles.cycles_per_micro() as f32, "us"),
+                Metric::new("rx_latency", latency_cycles, "cycles"),
+            ]);
+        } else {
+            missed += 1;
+            drain(&enabled);
+        }
+    }
+    run.summary(&samples);
+    if missed > 0 {
+        rprintln!("{} of {} loopback bytes did not come back.", missed, RX_ITERATIONS);
+    }
+
+    // --- FIFO behaviour ---
+    let run = Run { task: "uart_fifo", method: "loopback_hal", params: &[("bytes", FIFO_BURST as u32)], clock_hz: cycles.system_clock_hz() };
+    let mut samples = Samples::new("cycles");
+    let burst = [0x55u8; FIFO_BURST];
+    for iteration in 0..FIFO_ITERATIONS {
+        drain(&enabled);
+
+        // A full TX FIFO's worth, with nothing to wait for
+        let start = cycles.now();
+        enabled.write_full_blocking(&burst[..FIFO_DEPTH]);
+        let write_cycles = cycles.since(start);
+
+        // The rest waits for room, then everything goes out unread
+        enabled.write_full_blocking(&burst[FIFO_DEPTH..]);
+        while enabled.uart_is_busy() {}
+        delay.delay_us(100);
+
+        let overrun = take_overrun();
+        let mut received = 0u32;
+        let mut byte = [0u8; 1];
+        while enabled.uart_is_readable() {
+            if enabled.read_full_blocking(&mut byte).is_ok() {
+                received += 1;
+            }
+        }
+
+        samples.record(write_cycles as u32);
+        run.sample(iteration, &[
+            Metric::new("burst_write", write_cycles, "cycles"),
+            Metric::new("received", received, "bytes"),
+            Metric::new("overrun", u32::from(overrun), "flag"),
+        ]);
+    }
+    run.summary(&samples);
+    uart = enabled.disable();
+
+    // --- Stream throughput ---
+    let mut tx = [0u8; STREAM_BYTES];
+    for (i, byte) in tx.iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+    for baud in STREAM_BAUD_RATES {
+        let enabled = enable(uart, baud, None);
+        set_loopback(true);
+        let params = [("baud", baud), ("bytes", STREAM_BYTES as u32)];
+        let run = Run { task: "uart_loopback", method: "loopback_hal", params: &params, clock_hz: cycles.system_clock_hz() };
+        let timeout = frame_cycles(cycles, baud, 4 * STREAM_BYTES as u64);
+        let mut samples = Samples::new("cycles");
+        for iteration in 0..STREAM_ITERATIONS {
+            drain(&enabled);
+            // `None` for a byte read with an error, so the compare below
+            // counts it once
+            let mut rx = [None; STREAM_BYTES];
+            let mut sent = 0;
+            let mut received = 0;
+
+            let start = cycles.now();
+            while received < STREAM_BYTES && cycles.now() - start < timeout {
+                if sent < STREAM_BYTES {
+                    if let Ok(rest) = enabled.write_raw(&tx[sent..]) {
+                        sent = STREAM_BYTES - rest.len();
+                    }
+                }
+                if enabled.uart_is_readable() {
+                    let mut byte = [0u8];
+                    rx[received] = enabled.read_full_blocking(&mut byte).ok().map(|()| byte[0]);
+                    received += 1;
+                }
+            }
+            let duration_cycles = cycles.since(start);
+            let duration_us = cycles.to_micros(duration_cycles);
+            let mut errors = (STREAM_BYTES - received) as u32;
+            errors += rx.iter().zip(&tx).take(received).filter(|&(r, t)| *r != Some(*t)).count() as u32;
+            let bytes_per_sec = (received as u64 * 1_000_000).checked_div(duration_us).unwrap_or(0);
+
+            samples.record(duration_cycles as u32);
+            run.sample(iteration, &[
+                Metric::new("time", duration_us, "us"),
+                Metric::new("throughput", bytes_per_sec, "bytes_per_sec"),
+                Metric::new("time", duration_cycles, "cycles"),
+                Metric::new("errors", errors, "bytes"),
+            ]);
+        }
+        run.summary(&samples);
+        uart = enabled.disable();
+    }
+
+    // --- Error detection, loopback off ---
+    let enabled = enable(uart, BASE_BAUD, Some(Parity::Even));
+    force_rx(Some(true));
+    delay.delay_us(100);
+    drain(&enabled);
+    let bit_cycles = u64::from(cycles.system_clock_hz() / BASE_BAUD);
+    for fault in Fault::ALL {
+        let run = Run { task: "uart_errors", method: fault.method(false), params: &[("baud", BASE_BAUD)], clock_hz: cycles.system_clock_hz() };
+        let mut samples = Samples::new("cycles");
+        let mut detected_count = 0;
+        for frame in 0..FRAMES_PER_FAULT {
+            let byte = b'A' + (frame % 26) as u8;
+            let latency = inject(cycles, bit_cycles, &fault.levels(byte), || enabled.uart_is_readable());
+
+            let mut received = [0u8; 1];
+            let detected = latency.is_some()
+                && match enabled.read_full_blocking(&mut received) {
+                    Ok(()) => Received::Data(received[0]),
+                    Err(error) => Received::from(error),
+                } == fault.expected(byte);
+            drain(&enabled);
+
+            detected_count += u32::from(detected);
+            report_fault(&run, &mut samples, cycles, frame, detected, latency);
+        }
+        run.summary(&samples);
+        rprintln!("{}: {} of {} frames reported as expected.", fault.method(false), detected_count, FRAMES_PER_FAULT);
+    }
+    force_rx(None);
+
+    rprintln!("UART loopback benchmark (HAL) finished.");
+    enabled.disable()
+}
+
+/// Sets UART0's baud rate and line settings and enables it, in loopback
+fn configure_raw(baud_rate: u32, parity: bool, peripheral_clock_hz: u32) {
+    let uart0 = unsafe { &*hal::pac::UART0::ptr() };
+
+    // BAUDDIV = UARTCLK / (16 * baud rate), with 6 fraction bits
+    let baud_div = (8 * peripheral_clock_hz) / baud_rate;
+    let baud_div_int = baud_div / 16;
+    let baud_div_frac = ((baud_div % 16) * 64 + 8) / 16;
+
+    uart0.uartcr().write(|w| unsafe { w.bits(0) });
+    uart0.uartibrd().write(|w| unsafe { w.baud_divint().bits(baud_div_int as u16) });
+    uart0.uartfbrd().write(|w| unsafe { w.baud_divfrac().bits(baud_div_frac as u8) });
+    // 8 bits, optional even parity, 1 stop bit, FIFOs on; latches the divisors
+    uart0.uartlcr_h().write(|w| unsafe { w.wlen().bits(0b11).fen().set_bit().pen().bit(parity).eps().bit(parity) });
+    uart0.uartcr().write(|w| w.uarten().set_bit().txe().set_bit().rxe().set_bit().lbe().set_bit());
+}
+
+/// Empties the RX FIFO and clears its error flags
+fn drain_raw() {
+    let uart0 = unsafe { &*hal::pac::UART0::ptr() };
+    while uart0.uartfr().read().rxfe().bit_is_clear() {
+        let _ = uart0.uartdr().read();
+    }
+    take_overrun();
+}
+
+/// Runs the loopback benchmarks using raw register access
+pub fn benchmark_uart_loopback_raw(
+    cycles: &CycleCounter,
+    delay: &mut impl DelayNs,
+    peripheral_clock_hz: u32,
+) {
+    // Get direct pointers to UART and reset controller
+    let uart0 = unsafe { &*hal::pac::UART0::ptr() };
+    let resets = unsafe { &*hal::pac::RESETS::ptr() };
+    let io_bank0 = unsafe { &*hal::pac::IO_BANK0::ptr() };
+
+    // Reset UART
+    resets.reset().modify(|_, w| w.uart0().set_bit());
+    resets.reset().modify(|_, w| w.uart0().clear_bit());
+    while resets.reset_done().read().uart0().bit_is_clear() {}
+
+    // --- RX latency ---
+    configure_raw(BASE_BAUD, false, peripheral_clock_hz);
+    drain_raw();
+    let run = Run { task: "uart_rx", method: "loopback_raw", params: &[("baud", BASE_BAUD)], clock_hz: cycles.system_clock_hz() };
+    let timeout = frame_cycles(cycles, BASE_BAUD, 4);
+    let mut samples = Samples::new("cycles");
+    let mut missed = 0;
+    for iteration in 0..RX_ITERATIONS {
+        let sent = b'A' + (iteration % 26) as u8;
+
+        let start = cycles.now();
+        uart0.uartdr().write(|w| unsafe { w.data().bits(sent) });
+        while uart0.uartfr().read().rxfe().bit_is_set() && cycles.now() - start < timeout {}
+        let latency_cycles = cycles.since(start);
+
+        let readable = uart0.uartfr().read().rxfe().bit_is_clear();
+        if readable && Received::from_dr(&uart0.uartdr().read()) == Received::Data(sent) {
+            samples.record(latency_cycles as u32);
+            run.sample(iteration, &[
+                Metric::new("rx_latency", latency_cycles as f32 / cycles.cycles_per_micro() as f32, "us"),
+                Metric::new("rx_latency", latency_cycles, "cycles"),
+            ]);
+        } else {
+            missed += 1;
+            drain_raw();
+        }
+    }
+    run.summary(&samples);
+    if missed > 0 {
+        rprintln!("{} of {} loopback bytes did not come back.", missed, RX_ITERATIONS);
+    }
+
+    // --- FIFO behaviour ---
+    let run = Run { task: "uart_fifo", method: "loopback_raw", params: &[("bytes", FIFO_BURST as u32)], clock_hz: cycles.system_clock_hz() };
+    let mut samples = Samples::new("cycles");
+    for iteration in 0..FIFO_ITERATIONS {
+        drain_raw();
+
+        // A full TX FIFO's worth, with nothing to wait for
+        let start = cycles.now();
+        for _ in 0..FIFO_DEPTH {
+            uart0.uartdr().write(|w| unsafe { w.data().bits(0x55) });
+        }
+        let write_cycles = cycles.since(start);
+
+        // The rest waits for room, then everything goes out unread
+        for _ in FIFO_DEPTH..FIFO_BURST {
+            while uart0.uartfr().read().txff().bit_is_set() {}
+            uart0.uartdr().write(|w| unsafe { w.data().bits(0x55) });
+        }
+        while uart0.uartfr().read().busy().bit_is_set() {}
+        delay.delay_us(100);
+
+        let overrun = take_overrun();
+        let mut received = 0u32;
+        while uart0.uartfr().read().rxfe().bit_is_clear() {
+            if let Received::Data(_) = Received::from_dr(&uart0.uartdr().read()) {
+                received += 1;
+            }
+        }
+
+        samples.record(write_cycles as u32);
+        run.sample(iteration, &[
+            Metric::new("burst_write", write_cycles, "cycles"),
+            Metric::new("received", received, "bytes"),
+            Metric::new("overrun", u32::from(overrun), "flag"),
+        ]);
+    }
+    run.summary(&samples);
+
+    // --- Stream throughput ---
+    let mut tx = [0u8; STREAM_BYTES];
+    for (i, byte) in tx.iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+    for baud in STREAM_BAUD_RATES {
+        configure_raw(baud, false, peripheral_clock_hz);
+        let params = [("baud", baud), ("bytes", STREAM_BYTES as u32)];
+        let run = Run { task: "uart_loopback", method: "loopback_raw", params: &params, clock_hz: cycles.system_clock_hz() };
+        let timeout = frame_cycles(cycles, baud, 4 * STREAM_BYTES as u64);
+        let mut samples = Samples::new("cycles");
+        for iteration in 0..STREAM_ITERATIONS {
+            drain_raw();
+            // `None` for a byte read with an error, so the compare below
+            // counts it once
+            let mut rx = [None; STREAM_BYTES];
+            let mut sent = 0;
+            let mut received = 0;
+
+            let start = cycles.now();
+            while received < STREAM_BYTES && cycles.now() - start < timeout {
+                if sent < STREAM_BYTES && uart0.uartfr().read().txff().bit_is_clear() {
+                    uart0.uartdr().write(|w| unsafe { w.data().bits(tx[sent]) });
+                    sent += 1;
+                }
+                if uart0.uartfr().read().rxfe().bit_is_clear() {
+                    if let Received::Data(byte) = Received::from_dr(&uart0.uartdr().read()) {
+                        rx[received] = Some(byte);
+                    }
+                    received += 1;
+                }
+            }
+            let duration_cycles = cycles.since(start);
+            let duration_us = cycles.to_micros(duration_cycles);
+            let mut errors = (STREAM_BYTES - received) as u32;
+            errors += rx.iter().zip(&tx).take(received).filter(|&(r, t)| *r != Some(*t)).count() as u32;
+            let bytes_per_sec = (received as u64 * 1_000_000).checked_div(duration_us).unwrap_or(0);
+
+            samples.record(duration_cycles as u32);
+            run.sample(iteration, &[
+                Metric::new("time", duration_us, "us"),
+                Metric::new("throughput", bytes_per_sec, "bytes_per_sec"),
+                Metric::new("time", duration_cycles, "cycles"),
+                Metric::new("errors", errors, "bytes"),
+            ]);
+        }
+        run.summary(&samples);
+    }
+
+    // --- Error detection, loopback off ---
+    configure_raw(BASE_BAUD, true, peripheral_clock_hz);
+    uart0.uartcr().modify(|_, w| w.lbe().clear_bit());
+    force_rx(Some(true));
+    io_bank0.gpio(RX_PIN).gpio_ctrl().modify(|_, w| unsafe { w.funcsel().bits(2) }); // UART
+    delay.delay_us(100);
+    drain_raw();
+    let bit_cycles = u64::from(cycles.system_clock_hz() / BASE_BAUD);
+    for fault in Fault::ALL {
+        let run = Run { task: "uart_errors", method: fault.method(true), params: &[("baud", BASE_BAUD)], clock_hz: cycles.system_clock_hz() };
+        let mut samples = Samples::new("cycles");
+        let mut detected_count = 0;
+        for frame in 0..FRAMES_PER_FAULT {
+            let byte = b'A' + (frame % 26) as u8;
+            let latency = inject(cycles, bit_cycles, &fault.levels(byte), || uart0.uartfr().read().rxfe().bit_is_clear());
+
+            let detected = latency.is_some() && Received::from_dr(&uart0.uartdr().read()) == fault.expected(byte);
+            drain_raw();
+
+            detected_count += u32::from(detected);
+            report_fault(&run, &mut samples, cycles, frame, detected, latency);
+        }
+        run.summary(&samples);
+        rprintln!("{}: {} of {} frames reported as expected.", fault.method(true), detected_count, FRAMES_PER_FAULT);
+    }
+
+    // Release UART0 and GPIO1
+    uart0.uartcr().write(|w| unsafe { w.bits(0) });
+    force_rx(None);
+    io_bank0.gpio(RX_PIN).gpio_ctrl().modify(|_, w| unsafe { w.funcsel().bits(0x1f) });
+
+    rprintln!("UART loopback benchmark (Raw) finished.");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Start bit, 0x03 LSB first, then `parity` and `stop`
+    fn frame_0x03(parity: bool, stop: bool) -> [(bool, u32); 11] {
+        let (low, high) = ((false, 1), (true, 1));
+        [low, high, high, low, low, low, low, low, low, (parity, 1), (stop, 1)]
+    }
+
+    #[test]
+    fn clean_frame_is_8e1() {
+        assert_eq!(Fault::Clean.levels(0x03), frame_0x03(false, true));
+        // An odd count of ones sets the parity bit
+        assert_eq!(Fault::Clean.levels(0x01)[9], (true, 1));
+    }
+
+    #[test]
+    fn parity_fault_flips_the_parity_bit() {
+        assert_eq!(Fault::Parity.levels(0x03), frame_0x03(true, true));
+        assert_eq!(Fault::Parity.levels(0x01)[9], (false, 1));
+    }
+
+    #[test]
+    fn framing_fault_holds_the_stop_bit_low() {
+        assert_eq!(Fault::Framing.levels(0x03), frame_0x03(false, false));
+    }
+
+    #[test]
+    fn break_holds_the_line_low_for_two_frames() {
+        assert_eq!(Fault::Break.levels(0x03), [(false, 22)]);
+    }
+}
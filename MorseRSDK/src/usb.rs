@@ -0,0 +1,193 @@
+//! # USB CDC-ACM Serial Port
+//!
+//! Makes the Pico enumerate as a USB serial port. The device is polled
+//! from the `USBCTRL_IRQ` interrupt, so the main loop only has to queue
+//! text with [`write`] and collect typed input with [`read`]. Call
+//! [`init`] once, then bind the interrupt:
+//!
+//! ```ignore
+//! #[interrupt]
+//! fn USBCTRL_IRQ() {
+//!     morse_rsdk::usb::poll();
+//! }
+//! ```
+
+use core::cell::RefCell;
+use core::fmt;
+
+use critical_section::Mutex;
+use rp2040_hal::clocks::UsbClock;
+use rp2040_hal::pac::{self, RESETS, USBCTRL_DPRAM, USBCTRL_REGS};
+use rp2040_hal::usb::UsbBus;
+use usb_device::bus::UsbBusAllocator;
+use usb_device::device::{StringDescriptors, UsbDevice, UsbDeviceBuilder, UsbVidPid};
+use usbd_serial::SerialPort;
+
+use crate::keyer::LineEditor;
+
+/// pid.codes test VID/PID, as used by the rp-pico USB examples
+const USB_VID_PID: UsbVidPid = UsbVidPid(0x16c0, 0x27dd);
+
+/// CDC class code, see usb.org "Defined Class Codes"
+const USB_CLASS_CDC: u8 = 2;
+
+/// Polls [`write_all`] makes without progress before giving up
+const WRITE_ALL_POLLS: u32 = 100_000;
+
+struct UsbSerial {
+    device: UsbDevice<'static, UsbBus>,
+    serial: SerialPort<'static, UsbBus>,
+}
+
+static USB_SERIAL: Mutex<RefCell<Option<UsbSerial>>> = Mutex::new(RefCell::new(None));
+
+/// Creates the USB device and enables its interrupt.
+///
+/// `product` is the name the host shows for the port, e.g. "Morse Receiver".
+/// Panics if called more than once.
+pub fn init(
+    regs: USBCTRL_REGS,
+    dpram: USBCTRL_DPRAM,
+    usb_clock: UsbClock,
+    resets: &mut RESETS,
+    product: &'static str,
+) {
+    let bus: &'static UsbBusAllocator<UsbBus> = cortex_m::singleton!(
+        : UsbBusAllocator<UsbBus> = UsbBusAllocator::new(UsbBus::new(regs, dpram, usb_clock, true, resets))
+    )
+    .unwrap();
+
+    let serial = SerialPort::new(bus);
+    let device = UsbDeviceBuilder::new(bus, USB_VID_PID)
+        .strings(&[StringDescriptors::default()
+            .manufacturer("MorseRSDK")
+            .product(product)
+            .serial_number("0001")])
+        .unwrap()
+        .device_class(USB_CLASS_CDC)
+        .build();
+
+    critical_section::with(|cs| {
+        USB_SERIAL.borrow(cs).replace(Some(UsbSerial { device, serial }));
+    });
+
+    unsafe {
+        pac::NVIC::unmask(pac::Interrupt::USBCTRL_IRQ);
+    }
+}
+
+/// Services the USB controller. Call from the `USBCTRL_IRQ` handler.
+pub fn poll() {
+    critical_section::with(|cs| {
+        if let Some(usb) = USB_SERIAL.borrow(cs).borrow_mut().as_mut() {
+            usb.device.poll(&mut [&mut usb.serial]);
+        }
+    });
+}
+
+/// Queues bytes for the host and returns how many were accepted.
+///
+/// Output is dropped while no terminal has the port open (DTR low) or
+/// when the endpoint buffer is full, so a missing host never stalls the
+/// receiver.
+pub fn write(bytes: &[u8]) -> usize {
+    critical_section::with(|cs| {
+        let mut usb = USB_SERIAL.borrow(cs).borrow_mut();
+        let Some(usb) = usb.as_mut() else {
+            return 0;
+        };
+        if !usb.serial.dtr() {
+            return 0;
+        }
+
+        let mut written = 0;
+        while written < bytes.len() {
+            match usb.serial.write(&bytes[written..]) {
+                Ok(count) => written += count,
+                Err(_) => break,
+            }
+        }
+        written
+    })
+}
+
+/// Like [`write`], but services the device while the endpoint buffer is
+/// full, so long output such as a log dump or `show` is not cut short.
+/// Gives up if the host stops reading. Each attempt takes the critical
+/// section on its own, so interrupts still run in between; call it
+/// outside any critical section of your own. Safe to call from the
+/// `USBCTRL_IRQ` handler.
+pub fn write_all(bytes: &[u8]) -> usize {
+    let mut written = 0;
+    let mut stalled = 0;
+    while written < bytes.len() && stalled < WRITE_ALL_POLLS {
+        let accepted = critical_section::with(|cs| {
+            let mut usb = USB_SERIAL.borrow(cs).borrow_mut();
+            let usb = usb.as_mut().filter(|usb| usb.serial.dtr())?;
+            match usb.serial.write(&bytes[written..]) {
+                Ok(count) => Some(count),
+                Err(_) => {
+                    usb.device.poll(&mut [&mut usb.serial]);
+                    Some(0)
+                }
+            }
+        });
+        match accepted {
+            None => break,
+            Some(0) => stalled += 1,
+            Some(count) => {
+                written += count;
+                stalled = 0;
+            }
+        }
+    }
+    written
+}
+
+/// Copies bytes typed on the host into `buf` and returns how many.
+/// Returns 0 when nothing is waiting.
+pub fn read(buf: &mut [u8]) -> usize {
+    critical_section::with(|cs| {
+        match USB_SERIAL.borrow(cs).borrow_mut().as_mut() {
+            Some(usb) => usb.serial.read(buf).unwrap_or(0),
+            None => 0,
+        }
+    })
+}
+
+/// Reads typed input into `editor`, echoing it back so the terminal shows
+/// what is typed, and calls `on_line` for every finished line
+pub fn read_lines<const N: usize>(editor: &mut LineEditor<N>, mut on_line: impl FnMut(&str)) {
+    let mut buf = [0u8; 64];
+    let count = read(&mut buf);
+    for &byte in &buf[..count] {
+        match byte {
+            b'\r' | b'\n' => write(b"\r\n"),
+            0x08 | 0x7F => write(b"\x08 \x08"),
+            _ => write(&[byte]),
+        };
+        if let Some(line) = editor.push(byte) {
+            on_line(line.as_str());
+        }
+    }
+}
+
+/// `core::fmt::Write` onto the USB port, for `write!`
+pub struct UsbWriter;
+
+impl fmt::Write for UsbWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        write(s.as_bytes());
+        Ok(())
+    }
+}
+
+/// `core::fmt::Write` onto the USB port through [`write_all`]
+pub struct UsbBlockingWriter;
+
+impl fmt::Write for UsbBlockingWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        write_all(s.as_bytes());
+        Ok(())
+    }
+}